@@ -4,6 +4,10 @@
 #include <stdio.h>
 #include <string.h>
 
+static void on_change(CskkChange change, void *user_data) {
+  printf("Changed: %d\n", change);
+}
+
 /**
  * Shallow checking of libcskk.h
  * Use rust tests for meaningful integration tests.
@@ -16,7 +20,7 @@ int main() {
   skk_free_string(version);
 
   CskkDictionaryFfi *dict[1];
-  dict[0] = skk_file_dict_new("./tests/data/SKK-JISYO.S", "euc-jp", false);
+  dict[0] = skk_file_dict_new("./tests/data/SKK-JISYO.S", "euc-jp", false, false);
   CskkContext *context;
 
   context = skk_context_new(dict, 1);
@@ -29,8 +33,48 @@ int main() {
   char *output = skk_context_poll_output(context);
   printf("%s\n", output);
   assert(strcmp(output, "あ") == 0);
+  assert(skk_context_poll_delete_surrounding(context) == 0);
   skk_free_string(output);
 
+  skk_context_set_dynamic_completion_count(context, 3);
+  assert(!skk_context_accept_completion_suggestion(context, 0));
+  skk_context_set_fuzzy_fallback_max_cost(context, 2);
+  InputMode kana_forms[] = {Katakana, Hiragana};
+  skk_context_set_kana_form_candidates(context, kana_forms, 2);
+  skk_context_set_sagyo_henkaku_search(context, true);
+  skk_context_set_auto_okuri_process(context, true);
+  skk_context_set_builtin_candidate_selection(context, true);
+  skk_context_start_reconversion(context, "漢字", NULL);
+  skk_context_set_surrounding_text(context, "かんじ");
+  assert(skk_context_poll_events(context, NULL) == NULL);
+  unsigned int event_count = 0;
+  CskkEventFfi *events = skk_context_poll_events(context, &event_count);
+  skk_free_events(events, event_count);
+  int observer_id = skk_context_add_observer(context, on_change, NULL);
+  assert(observer_id >= 0);
+  assert(skk_context_remove_observer(context, observer_id));
+  char *snapshot = skk_context_snapshot(context);
+  assert(snapshot != NULL);
+  assert(skk_context_restore(context, snapshot));
+  skk_free_string(snapshot);
+  skk_context_focus_out(context, FocusOutKeep);
+  assert(skk_context_focus_in(context));
+  skk_context_focus_out(context, FocusOutCommit);
+  skk_context_reset(context);
+
+  unsigned int entry_count = 0;
+  DictionaryEntryFfi *entries =
+      skk_dictionary_search_entries_by_midashi_prefix(dict[0], "あい", &entry_count);
+  assert(entry_count > 0);
+  printf("%s /%s/\n", entries[0].midashi, entries[0].candidates[0].kouho_text);
+  skk_free_dictionary_entries(entries, entry_count);
+
+  CskkDictionaryFfi *background_dict = skk_file_dict_new_in_background(
+      "./tests/data/SKK-JISYO.S", "euc-jp", false, false, NULL, NULL);
+  assert(background_dict != NULL);
+  printf("Loading: %f\n", skk_dictionary_load_progress(background_dict));
+  skk_free_dictionary(background_dict);
+
   skk_free_dictionary(dict[0]);
   skk_free_context(context);
 }
\ No newline at end of file
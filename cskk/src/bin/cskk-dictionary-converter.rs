@@ -0,0 +1,64 @@
+//!
+//! SKK-JISYO形式の辞書と他のIMEのユーザー辞書形式を相互変換するコマンド
+//!
+//! cskk-dictionary-converter import <format> <ime_dictionary> <skk_jisyo> [skk_encoding]
+//! cskk-dictionary-converter export <format> <skk_jisyo> <ime_dictionary> [skk_encoding]
+//!
+//! formatは mozc, msime, apple-plist, apple-csv のいずれか。skk_encodingのデフォルトはutf-8
+//!
+use cskk::dictionary::ime_format::{
+    convert_ime_to_skk_jisyo, convert_skk_jisyo_to_ime, ImeDictionaryFormat,
+};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+const USAGE: &str = "Usage:
+  cskk-dictionary-converter import <format> <ime_dictionary> <skk_jisyo> [skk_encoding]
+  cskk-dictionary-converter export <format> <skk_jisyo> <ime_dictionary> [skk_encoding]
+
+format: mozc, msime, apple-plist, apple-csv
+skk_encoding: defaults to utf-8";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.len() < 4 || args.len() > 5 {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+    let format = match ImeDictionaryFormat::from_str(&args[1]) {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let skk_encoding = args.get(4).map_or("utf-8", |encoding| encoding.as_str());
+    let result = match args[0].as_str() {
+        "import" => convert_ime_to_skk_jisyo(&args[2], format, &args[3], skk_encoding),
+        "export" => {
+            convert_skk_jisyo_to_ime(&args[2], skk_encoding, &args[3], format).map(|report| {
+                for skipped in &report.skipped {
+                    eprintln!(
+                        "Skipped: {} [{}] {}",
+                        skipped.midashi, skipped.strict_okuri, skipped.kouho_text
+                    );
+                }
+                report.written
+            })
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match result {
+        Ok(count) => {
+            println!("Converted {count} words.");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
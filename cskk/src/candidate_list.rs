@@ -2,7 +2,7 @@ use crate::dictionary::candidate::Candidate;
 use crate::dictionary::CompositeKey;
 use crate::error::CskkError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct CandidateList {
     // 現在保持している選択肢の元
     to_composite: CompositeKey,
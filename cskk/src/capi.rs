@@ -1,7 +1,14 @@
 use crate::cskkstate::PreCompositionData;
-use crate::dictionary::CskkDictionary;
+use crate::dictionary::ime_format::{
+    convert_ime_to_skk_jisyo, convert_skk_jisyo_to_ime, ImeDictionaryFormat,
+};
+use crate::dictionary::{
+    CskkDictionary, DictionaryEntryData, DictionaryEntryOrder, DictionaryLoadCallback,
+    DictionaryReading,
+};
+use crate::event::{CskkChange, CskkEvent, CskkObserver, DictionaryUpdateKind};
 use crate::keyevent::CskkKeyEvent;
-use crate::skk_modes::{CommaStyle, CompositionMode, InputMode, PeriodStyle};
+use crate::skk_modes::{CommaStyle, CompositionMode, FocusOutPolicy, InputMode, PeriodStyle};
 use crate::CskkError::Error;
 use crate::{
     get_available_rules, skk_context_confirm_candidate_at_rs, skk_context_get_composition_mode_rs,
@@ -16,7 +23,7 @@ use crate::{
 use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
 use std::mem::ManuallyDrop;
-use std::os::raw::{c_char, c_int, c_uint};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
 use std::sync::Arc;
 use std::{ptr, slice};
 
@@ -55,6 +62,131 @@ impl Drop for CskkRulesFfi {
     }
 }
 
+///
+/// 辞書編集用の候補一つ分。
+/// 各要素は存在すれば\0終端のUTF-8文字配列、存在しなければNULLが含まれる。
+///
+#[repr(C)]
+pub struct DictionaryCandidateFfi {
+    pub kouho_text: *mut c_char,
+    pub annotation: *mut c_char,
+    /// 厳密な送り仮名の候補の場合、その送り仮名
+    pub strict_okuri: *mut c_char,
+}
+
+impl Drop for DictionaryCandidateFfi {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.kouho_text.is_null() {
+                drop(CString::from_raw(self.kouho_text));
+            }
+            if !self.annotation.is_null() {
+                drop(CString::from_raw(self.annotation));
+            }
+            if !self.strict_okuri.is_null() {
+                drop(CString::from_raw(self.strict_okuri));
+            }
+        }
+    }
+}
+
+///
+/// 辞書編集用のエントリ一つ分。
+///
+#[repr(C)]
+pub struct DictionaryEntryFfi {
+    /// 辞書上の見出し。送りありエントリは"かんがe"のように送り仮名のローマ字が末尾に付く。
+    pub midashi: *mut c_char,
+    pub is_okuri_ari: bool,
+    pub candidates: *mut DictionaryCandidateFfi,
+    pub candidate_count: c_uint,
+}
+
+impl DictionaryEntryFfi {
+    fn new(entry_data: DictionaryEntryData) -> Result<Self, CskkError> {
+        let mut candidates = vec![];
+        for candidate in entry_data.candidates {
+            candidates.push(DictionaryCandidateFfi {
+                kouho_text: CString::new(candidate.kouho_text)?.into_raw(),
+                annotation: optional_string_into_raw(candidate.annotation)?,
+                strict_okuri: optional_string_into_raw(candidate.strict_okuri)?,
+            });
+        }
+        let candidate_count = u32::try_from(candidates.len())
+            .map_err(|_| CskkError::Error("Too many candidates to pass to C side".to_string()))?;
+        Ok(DictionaryEntryFfi {
+            midashi: CString::new(entry_data.midashi)?.into_raw(),
+            is_okuri_ari: entry_data.is_okuri_ari,
+            candidates: vec_into_c_array(candidates),
+            candidate_count,
+        })
+    }
+}
+
+impl Drop for DictionaryEntryFfi {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.midashi.is_null() {
+                drop(CString::from_raw(self.midashi));
+            }
+            if !self.candidates.is_null() {
+                let length = self.candidate_count as usize;
+                drop(Vec::from_raw_parts(self.candidates, length, length));
+            }
+        }
+    }
+}
+
+///
+/// 逆引きで得た読み一つ分。
+/// 各要素は存在すれば\0終端のUTF-8文字配列、存在しなければNULLが含まれる。
+///
+#[repr(C)]
+pub struct DictionaryReadingFfi {
+    /// 辞書上の見出し。送りありエントリは"かんがe"のように送り仮名のローマ字が末尾に付く。
+    pub midashi: *mut c_char,
+    /// 送り仮名のローマ字を除いた読み
+    pub reading: *mut c_char,
+    pub is_okuri_ari: bool,
+    /// 厳密な送り仮名の候補として見つかった場合、その送り仮名
+    pub strict_okuri: *mut c_char,
+    pub annotation: *mut c_char,
+    /// contextの辞書の並びの中での、見つかった辞書の位置
+    pub dictionary_index: c_uint,
+}
+
+impl DictionaryReadingFfi {
+    fn new(reading: DictionaryReading) -> Result<Self, CskkError> {
+        Ok(DictionaryReadingFfi {
+            midashi: CString::new(reading.midashi)?.into_raw(),
+            reading: CString::new(reading.reading)?.into_raw(),
+            is_okuri_ari: reading.is_okuri_ari,
+            strict_okuri: optional_string_into_raw(reading.strict_okuri)?,
+            annotation: optional_string_into_raw(reading.annotation)?,
+            dictionary_index: reading.dictionary_index as c_uint,
+        })
+    }
+}
+
+impl Drop for DictionaryReadingFfi {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.midashi.is_null() {
+                drop(CString::from_raw(self.midashi));
+            }
+            if !self.reading.is_null() {
+                drop(CString::from_raw(self.reading));
+            }
+            if !self.strict_okuri.is_null() {
+                drop(CString::from_raw(self.strict_okuri));
+            }
+            if !self.annotation.is_null() {
+                drop(CString::from_raw(self.annotation));
+            }
+        }
+    }
+}
+
 ///
 /// 入力途上の状態を返す構造体群
 /// CompositionModeに合わせた構造体で、各要素は存在すれば\0終端のUTF-8文字配列、存在しなければNULLが含まれる。
@@ -71,6 +203,89 @@ pub enum CskkStateInfoFfi {
     CompleteStateInfo(CompleteDataFfi),
 }
 
+///
+/// skk_context_poll_eventsで返すイベント。各文字列は\0終端のUTF-8文字配列。
+///
+// Using long common postfix name because C header cannot have same name even in different enum.
+#[repr(C)]
+pub enum CskkEventFfi {
+    /// 確定した文字列。ホスト側ではこれを入力する。
+    CommitEvent(*mut c_char),
+    /// 既に確定した文字列のうち、ホスト側でカーソルの直前から消すべき文字数。同時に返すCommitEventの前に消す。
+    DeleteSurroundingEvent(c_uint),
+    /// キー入力で入力モードかCompositionModeが変わった。変わった後のモードを持つ。
+    ModeChangedEvent(InputMode, CompositionMode),
+    /// キー入力で変換候補の一覧か、その中で選択している候補が変わった。
+    CandidatesChangedEvent,
+    /// 辞書が更新された。
+    DictionaryUpdatedEvent(DictionaryUpdateFfi),
+    /// 操作を受け付けられなかったので警告音等で知らせる。
+    BeepEvent,
+}
+
+impl Drop for CskkEventFfi {
+    fn drop(&mut self) {
+        if let CskkEventFfi::CommitEvent(committed) = self {
+            unsafe {
+                if !committed.is_null() {
+                    drop(CString::from_raw(*committed));
+                }
+            }
+        }
+    }
+}
+
+#[repr(C)]
+pub struct DictionaryUpdateFfi {
+    /// 辞書の更新の種類
+    pub kind: DictionaryUpdateKind,
+    /// 更新されたエントリの見出し
+    pub midashi: *mut c_char,
+    /// 更新された候補
+    pub kouho_text: *mut c_char,
+}
+
+impl Drop for DictionaryUpdateFfi {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.midashi.is_null() {
+                drop(CString::from_raw(self.midashi));
+            }
+            if !self.kouho_text.is_null() {
+                drop(CString::from_raw(self.kouho_text));
+            }
+        }
+    }
+}
+
+impl CskkEventFfi {
+    fn new(event: CskkEvent) -> Self {
+        match event {
+            CskkEvent::Commit(committed) => {
+                CskkEventFfi::CommitEvent(CString::new(committed).unwrap_or_default().into_raw())
+            }
+            CskkEvent::DeleteSurrounding(count) => {
+                CskkEventFfi::DeleteSurroundingEvent(u32::try_from(count).unwrap_or_default())
+            }
+            CskkEvent::ModeChanged {
+                input_mode,
+                composition_mode,
+            } => CskkEventFfi::ModeChangedEvent(input_mode, composition_mode),
+            CskkEvent::CandidatesChanged => CskkEventFfi::CandidatesChangedEvent,
+            CskkEvent::DictionaryUpdated {
+                kind,
+                midashi,
+                kouho_text,
+            } => CskkEventFfi::DictionaryUpdatedEvent(DictionaryUpdateFfi {
+                kind,
+                midashi: CString::new(midashi).unwrap_or_default().into_raw(),
+                kouho_text: CString::new(kouho_text).unwrap_or_default().into_raw(),
+            }),
+            CskkEvent::Beep => CskkEventFfi::BeepEvent,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct DirectDataFfi {
     /// pollされた時に返す確定済み文字列。
@@ -104,6 +319,22 @@ pub struct CompositionSelectionDataFfi {
     pub okuri: *mut c_char,
     /// 現在の候補のアノテーション
     pub annotation: *mut c_char,
+    /// 現在の候補が打ち間違いを訂正した読みで見つかったものであれば、その訂正した読み
+    pub corrected_reading: *mut c_char,
+    /// 入力中のヒントの読み。ヒントを入力していなければNULL
+    pub hint: *mut c_char,
+    /// 入力中のヒントのかなに変換されていない部分。ヒントを入力していなければNULL
+    pub hint_unconverted: *mut c_char,
+    /// 組み込みの候補選択で一覧を表示している時、現在のページの変換候補の配列。表示していなければNULL
+    pub page_candidates: *mut *mut c_char,
+    /// page_candidatesの各候補を選ぶキーの表示の配列。表示していなければNULL
+    pub page_labels: *mut *mut c_char,
+    /// page_candidatesとpage_labelsの長さ
+    pub page_length: c_uint,
+    /// 0から数えた現在のページ番号
+    pub page_index: c_uint,
+    /// 一覧のページ数。一覧を表示していなければ0
+    pub page_count: c_uint,
 }
 
 impl Drop for CompositionSelectionDataFfi {
@@ -121,6 +352,23 @@ impl Drop for CompositionSelectionDataFfi {
             if !self.annotation.is_null() {
                 drop(CString::from_raw(self.annotation));
             }
+            if !self.corrected_reading.is_null() {
+                drop(CString::from_raw(self.corrected_reading));
+            }
+            if !self.hint.is_null() {
+                drop(CString::from_raw(self.hint));
+            }
+            if !self.hint_unconverted.is_null() {
+                drop(CString::from_raw(self.hint_unconverted));
+            }
+            let page_length = self.page_length as usize;
+            for string_array in [self.page_candidates, self.page_labels] {
+                if !string_array.is_null() {
+                    for string in Vec::from_raw_parts(string_array, page_length, page_length) {
+                        drop(CString::from_raw(string));
+                    }
+                }
+            }
         }
     }
 }
@@ -133,6 +381,10 @@ pub struct PreCompositionDataFfi {
     pub confirmed: *mut c_char,
     /// 漢字変換に用いようとしている部分
     pub kana_to_composite: *mut c_char,
+    /// 漢字変換に用いようとしている部分の中のカーソル位置を文字数で表したもの。
+    ///
+    /// PreCompositionでかな変換が成されていない入力はこの位置に付く。PreCompositionOkuriganaでは常に末尾を指す。
+    pub cursor: c_uint,
     /// 漢字変換時に送り仮名として用いようとしている部分
     pub okuri: *mut c_char,
     /// かな変換が成されていない入力キーの文字列表現。
@@ -143,6 +395,11 @@ pub struct PreCompositionDataFfi {
     ///
     /// surrounding_textで指定範囲のみからの変換に対応していない現在、正常な遷移ではRegisterには存在しない。
     pub unconverted: *mut c_char,
+    /// 漢字変換に用いようとしている部分から補完した候補の配列。補完候補がなければNULL。
+    ///
+    /// skk_context_set_dynamic_completion_countで件数を設定した時のみ存在する。
+    pub completion_suggestions: *mut CompletionSuggestionFfi,
+    pub completion_suggestion_count: c_uint,
 }
 
 impl Drop for PreCompositionDataFfi {
@@ -160,6 +417,49 @@ impl Drop for PreCompositionDataFfi {
             if !self.unconverted.is_null() {
                 drop(CString::from_raw(self.unconverted));
             }
+            if !self.completion_suggestions.is_null() {
+                let length = self.completion_suggestion_count as usize;
+                drop(Vec::from_raw_parts(
+                    self.completion_suggestions,
+                    length,
+                    length,
+                ));
+            }
+        }
+    }
+}
+
+///
+/// 入力中の読みに対する補完候補一つ分。
+/// 各要素は存在すれば\0終端のUTF-8文字配列、存在しなければNULLが含まれる。
+///
+#[repr(C)]
+pub struct CompletionSuggestionFfi {
+    /// 補完された見出し
+    pub midashi: *mut c_char,
+    /// 補完された変換候補
+    pub output: *mut c_char,
+    /// 送りありエントリから補完した候補の送り仮名
+    pub okuri: *mut c_char,
+    /// 候補のアノテーション
+    pub annotation: *mut c_char,
+}
+
+impl Drop for CompletionSuggestionFfi {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.midashi.is_null() {
+                drop(CString::from_raw(self.midashi));
+            }
+            if !self.output.is_null() {
+                drop(CString::from_raw(self.output));
+            }
+            if !self.okuri.is_null() {
+                drop(CString::from_raw(self.okuri));
+            }
+            if !self.annotation.is_null() {
+                drop(CString::from_raw(self.annotation));
+            }
         }
     }
 }
@@ -209,7 +509,7 @@ pub struct CompleteDataFfi {
     pub completed_midashi: *mut c_char,
     /// 現在選択されている変換候補。見出しの候補ではなく変換候補そのもの。
     pub completed: *mut c_char,
-    /// 現在の変換候補に付く送り仮名。v3.0.0では送り仮名付きからの補完は未実装。
+    /// 現在の変換候補に付く送り仮名。送りありエントリから補完した候補ではその送り仮名。
     pub okuri: *mut c_char,
     /// 現在の候補のアノテーション
     pub annotation: *mut c_char,
@@ -293,6 +593,7 @@ pub unsafe extern "C" fn skk_context_new_with_empty_fallback(
 
 ///
 /// Creates a skk static file dict based on the path_string. Returns the pointer of it.
+/// is_kakuteiがtrueなら確定辞書(ddskkのskk-kakutei-jisyo)にする。確定辞書の候補が変換候補の先頭に来ると、選択せずに確定する。
 /// Returns NULL on error. In error case, you don't have to free it.
 ///
 /// # Safety
@@ -306,11 +607,13 @@ pub unsafe extern "C" fn skk_file_dict_new(
     c_path_string: *const c_char,
     c_encoding: *const c_char,
     use_for_completion: bool,
+    is_kakutei: bool,
 ) -> *mut CskkDictionaryFfi {
     let maybe_dictionary = (|| -> anyhow::Result<CskkDictionaryFfi> {
         let path = CStr::from_ptr(c_path_string).to_str()?;
         let encoding = CStr::from_ptr(c_encoding).to_str()?;
-        let dictionary = CskkDictionary::new_static_dict(path, encoding, use_for_completion)?;
+        let dictionary = CskkDictionary::new_static_dict(path, encoding, use_for_completion)?
+            .with_kakutei(is_kakutei);
         Ok(CskkDictionaryFfi {
             dictionary: Arc::new(dictionary),
         })
@@ -325,6 +628,7 @@ pub unsafe extern "C" fn skk_file_dict_new(
 
 ///
 /// Creates a skk read and write user dict based on the path_string. Returns the pointer of it.
+/// is_kakuteiがtrueなら確定辞書(ddskkのskk-kakutei-jisyo)にする。確定辞書の候補が変換候補の先頭に来ると、選択せずに確定する。
 /// Returns NULL on error. In error case, you don't have to free it.
 ///
 /// # Safety
@@ -338,11 +642,129 @@ pub unsafe extern "C" fn skk_user_dict_new(
     c_path_string: *const c_char,
     c_encoding: *const c_char,
     use_for_completion: bool,
+    is_kakutei: bool,
+) -> *mut CskkDictionaryFfi {
+    let maybe_dictionary = (|| -> anyhow::Result<CskkDictionaryFfi> {
+        let path = CStr::from_ptr(c_path_string).to_str()?;
+        let encoding = CStr::from_ptr(c_encoding).to_str()?;
+        let dictionary = CskkDictionary::new_user_dict(path, encoding, use_for_completion)?
+            .with_kakutei(is_kakutei);
+        Ok(CskkDictionaryFfi {
+            dictionary: Arc::new(dictionary),
+        })
+    })();
+
+    if let Ok(ffi_dictionary) = maybe_dictionary {
+        Box::into_raw(Box::new(ffi_dictionary))
+    } else {
+        ptr::null_mut()
+    }
+}
+
+/// 辞書の読み込み終了時に呼ばれるCの関数とその引数
+struct CLoadCallback {
+    callback: unsafe extern "C" fn(*mut c_void),
+    user_data: *mut c_void,
+}
+
+// user_dataを読み込みスレッドから使えるかは呼び出し側の責任
+unsafe impl Send for CLoadCallback {}
+
+impl CLoadCallback {
+    fn into_dictionary_load_callback(
+        callback: Option<unsafe extern "C" fn(*mut c_void)>,
+        user_data: *mut c_void,
+    ) -> Option<DictionaryLoadCallback> {
+        callback.map(|callback| {
+            let c_callback = CLoadCallback {
+                callback,
+                user_data,
+            };
+            Box::new(move || c_callback.call()) as DictionaryLoadCallback
+        })
+    }
+
+    fn call(self) {
+        unsafe { (self.callback)(self.user_data) }
+    }
+}
+
+///
+/// skk_file_dict_newと同様だが、辞書ファイルを別スレッドで読み込み、読み込みの終了を待たずに返す。
+/// 読み込みが終わるまでは空の辞書として検索し、読み込みが終わると読み込んだエントリに入れ替える。
+/// 読み込みが終わると、読み込みスレッドからon_complete(user_data)が呼ばれる。on_completeはNULLでもよい。
+/// Returns NULL on error such as the file cannot be opened. In error case, you don't have to free it.
+///
+/// # Safety
+/// c_path_string and c_encoidng must be a valid c string that terminates with \0.
+/// on_complete and user_data must be usable from another thread.
+///
+/// Dictionary must be freed by skk_free_dictionary
+/// If not, memory leaks.
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_file_dict_new_in_background(
+    c_path_string: *const c_char,
+    c_encoding: *const c_char,
+    use_for_completion: bool,
+    is_kakutei: bool,
+    on_complete: Option<unsafe extern "C" fn(*mut c_void)>,
+    user_data: *mut c_void,
+) -> *mut CskkDictionaryFfi {
+    let maybe_dictionary = (|| -> anyhow::Result<CskkDictionaryFfi> {
+        let path = CStr::from_ptr(c_path_string).to_str()?;
+        let encoding = CStr::from_ptr(c_encoding).to_str()?;
+        let dictionary = CskkDictionary::new_static_dict_in_background(
+            path,
+            encoding,
+            use_for_completion,
+            CLoadCallback::into_dictionary_load_callback(on_complete, user_data),
+        )?
+        .with_kakutei(is_kakutei);
+        Ok(CskkDictionaryFfi {
+            dictionary: Arc::new(dictionary),
+        })
+    })();
+
+    if let Ok(ffi_dictionary) = maybe_dictionary {
+        Box::into_raw(Box::new(ffi_dictionary))
+    } else {
+        ptr::null_mut()
+    }
+}
+
+///
+/// skk_user_dict_newと同様だが、辞書ファイルを別スレッドで読み込み、読み込みの終了を待たずに返す。
+/// 読み込み中に確定した候補は読み込んだエントリと合わせられ、保存は読み込みが終わるのを待ってから行う。
+/// 読み込みが終わると、読み込みスレッドからon_complete(user_data)が呼ばれる。on_completeはNULLでもよい。
+/// Returns NULL on error such as the file cannot be opened. In error case, you don't have to free it.
+///
+/// # Safety
+/// c_path_string and c_encoidng must be a valid c string that terminates with \0.
+/// on_complete and user_data must be usable from another thread.
+///
+/// Dictionary must be freed by skk_free_dictionary
+/// If not, memory leaks.
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_user_dict_new_in_background(
+    c_path_string: *const c_char,
+    c_encoding: *const c_char,
+    use_for_completion: bool,
+    is_kakutei: bool,
+    on_complete: Option<unsafe extern "C" fn(*mut c_void)>,
+    user_data: *mut c_void,
 ) -> *mut CskkDictionaryFfi {
     let maybe_dictionary = (|| -> anyhow::Result<CskkDictionaryFfi> {
         let path = CStr::from_ptr(c_path_string).to_str()?;
         let encoding = CStr::from_ptr(c_encoding).to_str()?;
-        let dictionary = CskkDictionary::new_user_dict(path, encoding, use_for_completion)?;
+        let dictionary = CskkDictionary::new_user_dict_in_background(
+            path,
+            encoding,
+            use_for_completion,
+            CLoadCallback::into_dictionary_load_callback(on_complete, user_data),
+        )?
+        .with_kakutei(is_kakutei);
         Ok(CskkDictionaryFfi {
             dictionary: Arc::new(dictionary),
         })
@@ -355,6 +777,22 @@ pub unsafe extern "C" fn skk_user_dict_new(
     }
 }
 
+///
+/// 辞書の読み込みが終わっていればtrueを返す。
+///
+#[no_mangle]
+pub extern "C" fn skk_dictionary_is_ready(dictionary: &CskkDictionaryFfi) -> bool {
+    dictionary.dictionary.is_ready()
+}
+
+///
+/// 辞書ファイルを読み込んだ割合を0.0から1.0で返す。
+///
+#[no_mangle]
+pub extern "C" fn skk_dictionary_load_progress(dictionary: &CskkDictionaryFfi) -> f64 {
+    dictionary.dictionary.load_progress()
+}
+
 ///
 /// Creates an empty dictionary. Returns the pointer of it.
 /// On error returns NULL pointer.
@@ -494,80 +932,256 @@ pub extern "C" fn skk_context_poll_output(context: &mut CskkContext) -> *mut c_c
 }
 
 ///
-/// preedit文字列を返す。
-/// 返り値は\0終端のUTF-8文字配列。C++20で言うchar8_t*
-/// 失敗時にはNULLを返す。
+/// 既にpollした確定済み文字列のうち、取り消されたのでカーソルの直前から消すべき文字数を返す。
 ///
-/// # Safety
-/// 返り値のポインタの文字列を直接編集して文字列長を変えてはいけない。
-/// 返り値はcallerがskk_free_stringしないとメモリリークする。
-/// ueno/libskkと違う点なので注意が必要
+/// 確定辞書で確定した直後やUndoKakuteiで確定を取り消した時、BackwardSetHenkanPointで確定済みのかなを読みにした時などに0以外を返す。
+/// skk_context_poll_outputで得た文字列を確定する前に、この文字数をホスト側で消す。
 ///
 #[no_mangle]
-pub extern "C" fn skk_context_get_preedit(context: &CskkContext) -> *mut c_char {
-    let maybe_result = (|| -> anyhow::Result<CString> {
-        let maybe_preedit = context.get_preedit();
-        if let Some(preedit) = maybe_preedit {
-            Ok(CString::new(preedit)?)
-        } else {
-            // 実質 unreachable!()
-            Err(Error("no preedit".to_string()).into())
-        }
-    })();
+pub extern "C" fn skk_context_poll_delete_surrounding(context: &mut CskkContext) -> c_uint {
+    context.poll_delete_surrounding() as c_uint
+}
 
-    if let Ok(result) = maybe_result {
-        result.into_raw()
-    } else {
-        ptr::null_mut()
+/// 状態の変化を知らせるCの関数とその引数
+struct CObserver {
+    callback: unsafe extern "C" fn(CskkChange, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// user_dataをcontextと共に使えるかは呼び出し側の責任
+unsafe impl Send for CObserver {}
+
+impl CObserver {
+    fn into_observer(self) -> CskkObserver {
+        Box::new(move |change| unsafe { (self.callback)(change, self.user_data) })
     }
 }
 
 ///
-/// preedit状態の配列を返す。
-/// 最初のものが一番外側の状態で、Registerモードの時には後にその内側の状態が続く。
-/// 結果の配列の長さは引数のstate_stack_lenにセットする。
-/// 失敗時にはNULLを返す。
+/// キー入力の処理で入力モード、CompositionMode、変換候補の一覧やその中で選択している候補、入力中の表示が変わった時に呼ばれるobserverを登録する。
+/// observerは変わったものごとにcallback(change, user_data)として、キー入力の処理の後に呼ばれる。
+/// skk_context_resetやskk_context_start_reconversionなど、キー入力以外で状態を変える関数の後にも同様に呼ばれる。
+/// skk_context_remove_observerで登録を解除するためのidを返す。callbackがNULLの場合は-1を返す。
 ///
 /// # Safety
-/// 返り値の内容およびポインタは変更してはならない。
-/// 返り値はcallerがskk_free_preedit_detailで解放しないとメモリリークする
-/// state_stack_lenが有効なunsigned intへのポインタでないと予期せぬ動作を起こす。
+/// callbackとuser_dataはcontextが解放されるか登録を解除するまで有効でなければならない。
+/// callbackはcontextの処理の途中で呼ばれるので、callbackの中から同じcontextを引数とする関数を呼んではならない。
 ///
 #[no_mangle]
-pub unsafe extern "C" fn skk_context_get_preedit_detail(
-    context: &CskkContext,
-    state_stack_len: *mut c_uint,
-) -> *mut CskkStateInfoFfi {
-    let preedit = context.get_preedit_detail();
-    let mut converted = preedit
-        .into_iter()
-        .map(convert_state_info)
-        .collect::<Vec<CskkStateInfoFfi>>();
-    *state_stack_len = u32::try_from(converted.len()).unwrap_or_default();
-
-    if !converted.is_empty() {
-        // Make Vec capacity to be equals length so that we can restore on free function.
-        converted.set_len(converted.len());
-        let mut retval = ManuallyDrop::new(converted);
-
-        retval.as_mut_ptr()
+pub unsafe extern "C" fn skk_context_add_observer(
+    context: &mut CskkContext,
+    callback: Option<unsafe extern "C" fn(CskkChange, *mut c_void)>,
+    user_data: *mut c_void,
+) -> c_int {
+    if let Some(callback) = callback {
+        let observer = CObserver {
+            callback,
+            user_data,
+        };
+        c_int::try_from(context.add_observer(observer.into_observer())).unwrap_or(-1)
     } else {
-        // Must treat specially since Vec with 0 capacity has some value not guaranteed to be NULL in C.
-        // See https://doc.rust-lang.org/std/vec/struct.Vec.html#guarantees
-        ptr::null_mut()
+        -1
     }
 }
 
-/// CskkStateInfoのStringからcstringに変換したFFI用の構造体を返す。
-fn convert_state_info(state_info: CskkStateInfo) -> CskkStateInfoFfi {
-    match state_info {
-        CskkStateInfo::Direct(direct_data) => {
-            let confirmed = CString::new(direct_data.confirmed)
-                .unwrap_or_default()
-                .into_raw();
-
-            let unconverted = if direct_data.unconverted.is_some() {
-                CString::new(direct_data.unconverted.unwrap())
+///
+/// skk_context_add_observerで登録したobserverの登録を解除する。該当するobserverがなければfalseを返す。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_remove_observer(context: &mut CskkContext, id: c_int) -> bool {
+    usize::try_from(id).is_ok_and(|id| context.remove_observer(id))
+}
+
+///
+/// 前回からのイベントを古いものから順に取り出して配列で返す。
+/// skk_context_poll_delete_surroundingの文字数とskk_context_poll_outputの確定文字列も、それぞれDeleteSurroundingEventとCommitEventとしてこの順で最後に含める。
+/// 結果の配列の長さは引数のlengthにセットする。イベントがなければNULLを返す。
+///
+/// # Safety
+/// lengthは有効なunsigned intへのポインタかNULLでなければならない。
+/// lengthがNULLの場合はイベントを取り出さずにNULLを返す。
+/// 返り値はcallerがskk_free_eventsで解放しないとメモリリークする。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_context_poll_events(
+    context: &mut CskkContext,
+    length: *mut c_uint,
+) -> *mut CskkEventFfi {
+    if length.is_null() {
+        return ptr::null_mut();
+    }
+    let events = context
+        .poll_events()
+        .into_iter()
+        .map(CskkEventFfi::new)
+        .collect::<Vec<CskkEventFfi>>();
+    *length = u32::try_from(events.len()).unwrap_or_default();
+    vec_into_c_array(events)
+}
+
+///
+/// skk_context_poll_eventsで得たイベントの配列を解放する。
+///
+/// # Safety
+/// ptrとlengthはskk_context_poll_eventsの返り値とその長さでなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_free_events(ptr: *mut CskkEventFfi, length: c_uint) {
+    if ptr.is_null() {
+        return;
+    }
+    let length = length as usize;
+    drop(Vec::from_raw_parts(ptr, length, length))
+}
+
+///
+/// 入力中の状態をバージョン付きの文字列として保存する。辞書の内容は含めない。
+/// 返り値は\0終端のUTF-8文字配列。失敗時にはNULLを返す。
+///
+/// # Safety
+/// 返り値はcallerがskk_free_stringしないとメモリリークする。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_snapshot(context: &CskkContext) -> *mut c_char {
+    let maybe_result = (|| -> anyhow::Result<CString> { Ok(CString::new(context.snapshot()?)?) })();
+    if let Ok(result) = maybe_result {
+        result.into_raw()
+    } else {
+        ptr::null_mut()
+    }
+}
+
+///
+/// skk_context_snapshotで保存した入力中の状態に戻す。pollされていない確定済み文字列はそのまま残る。
+/// 読めない文字列やバージョンの異なる場合は状態を変えずにfalseを返す。
+///
+/// # Safety
+/// c_snapshotは\0終端の有効なC文字列でなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_context_restore(
+    context: &mut CskkContext,
+    c_snapshot: *const c_char,
+) -> bool {
+    let result = (|| -> anyhow::Result<()> {
+        let snapshot = CStr::from_ptr(c_snapshot).to_str()?;
+        context.restore(snapshot)?;
+        Ok(())
+    })();
+    result.is_ok()
+}
+
+///
+/// ホスト側の入力欄からフォーカスが外れた時に呼び、policyに従って入力中の状態を片付ける。
+/// FocusOutCommitでは表示している候補かかなを確定し、FocusOutDiscardでは捨て、FocusOutKeepでは保存してskk_context_focus_inで戻せるようにする。
+/// 確定した文字列はこの後skk_context_poll_outputで取り出す。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_focus_out(context: &mut CskkContext, policy: FocusOutPolicy) {
+    context.focus_out(policy);
+}
+
+///
+/// ホスト側の入力欄にフォーカスが戻った時に呼び、FocusOutKeepで保存した入力中の状態に戻す。
+/// 保存した状態がなければfalseを返す。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_focus_in(context: &mut CskkContext) -> bool {
+    context.focus_in()
+}
+
+///
+/// 既にpollされてカーソルの直前にある文字列を渡す。次のskk_context_process_key_eventの処理でのみ使われる。
+/// BackwardSetHenkanPointでpollされていない確定済み文字列より前まで遡るために、キー入力を渡す直前に毎回渡す。
+///
+/// # Safety
+/// c_textは\0終端の有効なC文字列でなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_context_set_surrounding_text(
+    context: &mut CskkContext,
+    c_text: *const c_char,
+) {
+    if let Ok(text) = CStr::from_ptr(c_text).to_str() {
+        context.set_surrounding_text(text);
+    }
+}
+
+///
+/// preedit文字列を返す。
+/// 返り値は\0終端のUTF-8文字配列。C++20で言うchar8_t*
+/// 失敗時にはNULLを返す。
+///
+/// # Safety
+/// 返り値のポインタの文字列を直接編集して文字列長を変えてはいけない。
+/// 返り値はcallerがskk_free_stringしないとメモリリークする。
+/// ueno/libskkと違う点なので注意が必要
+///
+#[no_mangle]
+pub extern "C" fn skk_context_get_preedit(context: &CskkContext) -> *mut c_char {
+    let maybe_result = (|| -> anyhow::Result<CString> {
+        let maybe_preedit = context.get_preedit();
+        if let Some(preedit) = maybe_preedit {
+            Ok(CString::new(preedit)?)
+        } else {
+            // 実質 unreachable!()
+            Err(Error("no preedit".to_string()).into())
+        }
+    })();
+
+    if let Ok(result) = maybe_result {
+        result.into_raw()
+    } else {
+        ptr::null_mut()
+    }
+}
+
+///
+/// preedit状態の配列を返す。
+/// 最初のものが一番外側の状態で、Registerモードの時には後にその内側の状態が続く。
+/// 結果の配列の長さは引数のstate_stack_lenにセットする。
+/// 失敗時にはNULLを返す。
+///
+/// # Safety
+/// 返り値の内容およびポインタは変更してはならない。
+/// 返り値はcallerがskk_free_preedit_detailで解放しないとメモリリークする
+/// state_stack_lenが有効なunsigned intへのポインタでないと予期せぬ動作を起こす。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_context_get_preedit_detail(
+    context: &CskkContext,
+    state_stack_len: *mut c_uint,
+) -> *mut CskkStateInfoFfi {
+    let preedit = context.get_preedit_detail();
+    let mut converted = preedit
+        .into_iter()
+        .map(convert_state_info)
+        .collect::<Vec<CskkStateInfoFfi>>();
+    *state_stack_len = u32::try_from(converted.len()).unwrap_or_default();
+
+    if !converted.is_empty() {
+        // Make Vec capacity to be equals length so that we can restore on free function.
+        converted.set_len(converted.len());
+        let mut retval = ManuallyDrop::new(converted);
+
+        retval.as_mut_ptr()
+    } else {
+        // Must treat specially since Vec with 0 capacity has some value not guaranteed to be NULL in C.
+        // See https://doc.rust-lang.org/std/vec/struct.Vec.html#guarantees
+        ptr::null_mut()
+    }
+}
+
+/// CskkStateInfoのStringからcstringに変換したFFI用の構造体を返す。
+fn convert_state_info(state_info: CskkStateInfo) -> CskkStateInfoFfi {
+    match state_info {
+        CskkStateInfo::Direct(direct_data) => {
+            let confirmed = CString::new(direct_data.confirmed)
+                .unwrap_or_default()
+                .into_raw();
+
+            let unconverted = if direct_data.unconverted.is_some() {
+                CString::new(direct_data.unconverted.unwrap())
                     .unwrap()
                     .into_raw()
             } else {
@@ -633,11 +1247,40 @@ fn convert_state_info(state_info: CskkStateInfo) -> CskkStateInfoFfi {
             } else {
                 ptr::null_mut()
             };
+            let corrected_reading =
+                optional_string_into_raw(composition_selection_data.corrected_reading)
+                    .unwrap_or(ptr::null_mut());
+            let hint = optional_string_into_raw(composition_selection_data.hint)
+                .unwrap_or(ptr::null_mut());
+            let hint_unconverted =
+                optional_string_into_raw(composition_selection_data.hint_unconverted)
+                    .unwrap_or(ptr::null_mut());
+            let (page_candidates, page_labels, page_length, page_index, page_count) =
+                if let Some(page) = composition_selection_data.page {
+                    let page_length = page.candidates.len() as c_uint;
+                    (
+                        strings_into_c_array(page.candidates),
+                        strings_into_c_array(page.labels),
+                        page_length,
+                        page.page_index as c_uint,
+                        page.page_count as c_uint,
+                    )
+                } else {
+                    (ptr::null_mut(), ptr::null_mut(), 0, 0, 0)
+                };
             CskkStateInfoFfi::CompositionSelectionStateInfo(CompositionSelectionDataFfi {
                 confirmed,
                 composited,
                 okuri,
                 annotation,
+                corrected_reading,
+                hint,
+                hint_unconverted,
+                page_candidates,
+                page_labels,
+                page_length,
+                page_index,
+                page_count,
             })
         }
         CskkStateInfo::Complete(complete_data) => {
@@ -649,6 +1292,11 @@ fn convert_state_info(state_info: CskkStateInfo) -> CskkStateInfoFfi {
                 .unwrap()
                 .into_raw();
             let completed = CString::new(complete_data.completed).unwrap().into_raw();
+            let okuri = if let Some(okuri) = complete_data.okuri {
+                CString::new(okuri).unwrap_or_default().into_raw()
+            } else {
+                ptr::null_mut()
+            };
             let annotation = if let Some(annotation) = complete_data.annotation {
                 CString::new(annotation).unwrap().into_raw()
             } else {
@@ -660,7 +1308,7 @@ fn convert_state_info(state_info: CskkStateInfo) -> CskkStateInfoFfi {
                 origin_okuri: ptr::null_mut(),
                 completed_midashi,
                 completed,
-                okuri: ptr::null_mut(),
+                okuri,
                 annotation,
             })
         }
@@ -685,11 +1333,31 @@ fn convert_precomposition_data(precomposition_data: PreCompositionData) -> PreCo
         ptr::null_mut()
     };
 
+    let completion_suggestions = precomposition_data
+        .completion_suggestions
+        .into_iter()
+        .map(|suggestion| CompletionSuggestionFfi {
+            midashi: CString::new(suggestion.midashi)
+                .unwrap_or_default()
+                .into_raw(),
+            output: CString::new(suggestion.output)
+                .unwrap_or_default()
+                .into_raw(),
+            okuri: optional_string_into_raw(suggestion.okuri).unwrap_or(ptr::null_mut()),
+            annotation: optional_string_into_raw(suggestion.annotation).unwrap_or(ptr::null_mut()),
+        })
+        .collect::<Vec<_>>();
+    let completion_suggestion_count =
+        u32::try_from(completion_suggestions.len()).unwrap_or_default();
+
     PreCompositionDataFfi {
         confirmed,
         kana_to_composite,
+        cursor: u32::try_from(precomposition_data.cursor).unwrap_or_default(),
         okuri,
         unconverted,
+        completion_suggestions: vec_into_c_array(completion_suggestions),
+        completion_suggestion_count,
     }
 }
 
@@ -1013,6 +1681,93 @@ pub extern "C" fn skk_context_set_period_style(
     skk_context_set_period_style_rs(context, period_style)
 }
 
+///
+/// PreCompositionの入力中に補完候補として探す件数を設定する。0ならば探さない。
+///
+/// 補完候補はskk_context_get_preedit_detailのPreCompositionの状態で得られる。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_set_dynamic_completion_count(
+    context: &mut CskkContext,
+    count: c_uint,
+) {
+    context.set_dynamic_completion_count(count as usize)
+}
+
+///
+/// 変換候補が見つからない時に打ち間違いを訂正した読みで辞書を引く、編集コストの上限を設定する。0ならば訂正しない。
+///
+/// 訂正した読みで見つかった候補は、skk_context_get_preedit_detailのCompositionSelectionの状態でcorrected_readingを持つ。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_set_fuzzy_fallback_max_cost(
+    context: &mut CskkContext,
+    max_cost: c_uint,
+) {
+    context.set_fuzzy_fallback_max_cost(max_cost as usize)
+}
+
+///
+/// 変換候補の末尾に、読みを指定した入力モードのかなに変換した候補を加えるようにする。
+/// 指定できるのはHiragana, Katakana, HankakuKatakanaで、input_mode_countが0ならば加えない。
+///
+/// # Safety
+/// input_mode_arrayはinput_mode_count個以上のInputModeを持つC配列へのポインタでなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_context_set_kana_form_candidates(
+    context: &mut CskkContext,
+    input_mode_array: *const InputMode,
+    input_mode_count: c_uint,
+) {
+    let input_modes = if input_mode_count < 1 || input_mode_array.is_null() {
+        vec![]
+    } else {
+        slice::from_raw_parts(input_mode_array, input_mode_count as usize).to_vec()
+    };
+    context.set_kana_form_candidates(input_modes);
+}
+
+///
+/// 送り仮名がさ・し・す・せの送りあり変換で、送りなしの語幹の候補も引くかを設定する。ddskkのskk-search-sagyo-henkakuに相当する。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_set_sagyo_henkaku_search(context: &mut CskkContext, enabled: bool) {
+    context.set_sagyo_henkaku_search(enabled)
+}
+
+///
+/// 送り仮名を指定せずに入力した読みを、語幹と送り仮名に分けて送りありエントリからも引くかを設定する。ddskkのskk-auto-okuri-processに相当する。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_set_auto_okuri_process(context: &mut CskkContext, enabled: bool) {
+    context.set_auto_okuri_process(enabled)
+}
+
+///
+/// 変換候補の一覧の表示とページ送り、選択キーでの選択をライブラリ側で行うかを設定する。
+/// 一覧の設定はルールファイルの[composition_selection.candidate_selection]による。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_set_builtin_candidate_selection(
+    context: &mut CskkContext,
+    enabled: bool,
+) {
+    context.set_builtin_candidate_selection(enabled)
+}
+
+///
+/// index番目の入力中の補完候補で確定する。
+/// 確定した場合はtrueを、該当する補完候補がない場合はfalseを返す。
+///
+#[no_mangle]
+pub extern "C" fn skk_context_accept_completion_suggestion(
+    context: &mut CskkContext,
+    index: c_uint,
+) -> bool {
+    context.accept_completion_suggestion(index as usize)
+}
+
 ///
 /// Comma style を設定する
 ///
@@ -1088,6 +1843,461 @@ pub unsafe extern "C" fn skk_get_rules(length: *mut c_uint) -> *mut CskkRulesFfi
     }
 }
 
+///
+/// 辞書の全エントリの配列を返す。送りありエントリ、送りなしエントリの順にそれぞれorderの順で並ぶ。
+/// 結果の配列の長さは引数のlengthにセットする。
+/// 失敗時やエントリがない場合にはNULLを返す。
+///
+/// # Safety
+/// lengthは有効なunsigned intへのポインタでなければならない。
+/// 返り値はcallerがskk_free_dictionary_entriesで解放しないとメモリリークする。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_get_entries(
+    dictionary: &CskkDictionaryFfi,
+    order: DictionaryEntryOrder,
+    length: *mut c_uint,
+) -> *mut DictionaryEntryFfi {
+    dictionary_entries_into_c_array(dictionary.dictionary.get_entries(order), length)
+}
+
+///
+/// 見出しがc_prefixから始まるエントリの配列を見出しの辞書順で返す。
+/// 結果の配列の長さは引数のlengthにセットする。
+/// 失敗時やエントリがない場合にはNULLを返す。
+///
+/// # Safety
+/// c_prefixは\0終端の有効なC文字列でなければならない。
+/// lengthは有効なunsigned intへのポインタでなければならない。
+/// 返り値はcallerがskk_free_dictionary_entriesで解放しないとメモリリークする。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_search_entries_by_midashi_prefix(
+    dictionary: &CskkDictionaryFfi,
+    c_prefix: *const c_char,
+    length: *mut c_uint,
+) -> *mut DictionaryEntryFfi {
+    *length = 0;
+    if let Ok(prefix) = CStr::from_ptr(c_prefix).to_str() {
+        dictionary_entries_into_c_array(
+            dictionary
+                .dictionary
+                .search_entries_by_midashi_prefix(prefix),
+            length,
+        )
+    } else {
+        ptr::null_mut()
+    }
+}
+
+///
+/// 候補にc_substringを含むエントリの配列を見出しの辞書順で返す。
+/// 結果の配列の長さは引数のlengthにセットする。
+/// 失敗時やエントリがない場合にはNULLを返す。
+///
+/// # Safety
+/// c_substringは\0終端の有効なC文字列でなければならない。
+/// lengthは有効なunsigned intへのポインタでなければならない。
+/// 返り値はcallerがskk_free_dictionary_entriesで解放しないとメモリリークする。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_search_entries_by_candidate(
+    dictionary: &CskkDictionaryFfi,
+    c_substring: *const c_char,
+    length: *mut c_uint,
+) -> *mut DictionaryEntryFfi {
+    *length = 0;
+    if let Ok(substring) = CStr::from_ptr(c_substring).to_str() {
+        dictionary_entries_into_c_array(
+            dictionary.dictionary.search_entries_by_candidate(substring),
+            length,
+        )
+    } else {
+        ptr::null_mut()
+    }
+}
+
+///
+/// skk_dictionary_get_entries等で得たエントリの配列を解放する。
+///
+/// # Safety
+/// ptrとlengthはskk_dictionary_get_entries等の返り値とその長さでなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_free_dictionary_entries(ptr: *mut DictionaryEntryFfi, length: c_uint) {
+    if ptr.is_null() {
+        return;
+    }
+    let length = length as usize;
+    drop(Vec::from_raw_parts(ptr, length, length))
+}
+
+///
+/// 見出しc_midashiのエントリの先頭に候補を追加する。既に同じ候補がある場合は先頭に移動し、アノテーションを置き換える。
+/// 送りありエントリの場合、見出しは"かんがe"のように送り仮名のローマ字を末尾に付ける。
+/// c_annotation, c_strict_okuriは不要ならNULLを渡す。
+///
+/// 辞書を更新した場合trueを返す。読み込み専用の辞書や失敗時にはfalseを返す。
+///
+/// # Safety
+/// c_midashi, c_kouho_textは\0終端の有効なC文字列でなければならない。
+/// c_annotation, c_strict_okuriは\0終端の有効なC文字列かNULLでなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_add_candidate(
+    dictionary: &CskkDictionaryFfi,
+    c_midashi: *const c_char,
+    c_kouho_text: *const c_char,
+    c_annotation: *const c_char,
+    c_strict_okuri: *const c_char,
+) -> bool {
+    let result = (|| -> anyhow::Result<bool> {
+        let midashi = CStr::from_ptr(c_midashi).to_str()?;
+        let kouho_text = CStr::from_ptr(c_kouho_text).to_str()?;
+        let annotation = optional_str_from_ptr(c_annotation)?;
+        let strict_okuri = optional_str_from_ptr(c_strict_okuri)?;
+        Ok(dictionary
+            .dictionary
+            .add_candidate(midashi, kouho_text, annotation, strict_okuri)?)
+    })();
+    result.unwrap_or(false)
+}
+
+///
+/// 見出しc_midashiのエントリの候補c_kouho_textをnew_positionの位置に移動する。
+/// c_strict_okuriを与えた場合はその厳密な送り仮名の候補の中で、NULLの場合は厳密な送り仮名のない候補の中で移動する。
+///
+/// 辞書を更新した場合trueを返す。
+///
+/// # Safety
+/// c_midashi, c_kouho_textは\0終端の有効なC文字列でなければならない。
+/// c_strict_okuriは\0終端の有効なC文字列かNULLでなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_move_candidate(
+    dictionary: &CskkDictionaryFfi,
+    c_midashi: *const c_char,
+    c_strict_okuri: *const c_char,
+    c_kouho_text: *const c_char,
+    new_position: c_uint,
+) -> bool {
+    let result = (|| -> anyhow::Result<bool> {
+        let midashi = CStr::from_ptr(c_midashi).to_str()?;
+        let strict_okuri = optional_str_from_ptr(c_strict_okuri)?;
+        let kouho_text = CStr::from_ptr(c_kouho_text).to_str()?;
+        Ok(dictionary.dictionary.move_candidate(
+            midashi,
+            strict_okuri,
+            kouho_text,
+            new_position as usize,
+        )?)
+    })();
+    result.unwrap_or(false)
+}
+
+///
+/// 見出しc_midashiのエントリから候補c_kouho_textを削除する。
+/// c_strict_okuriがNULLの場合は厳密な送り仮名の有無にかかわらず削除し、与えた場合はその厳密な送り仮名の候補からのみ削除する。
+///
+/// 辞書を更新した場合trueを返す。
+///
+/// # Safety
+/// c_midashi, c_kouho_textは\0終端の有効なC文字列でなければならない。
+/// c_strict_okuriは\0終端の有効なC文字列かNULLでなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_delete_candidate(
+    dictionary: &CskkDictionaryFfi,
+    c_midashi: *const c_char,
+    c_strict_okuri: *const c_char,
+    c_kouho_text: *const c_char,
+) -> bool {
+    let result = (|| -> anyhow::Result<bool> {
+        let midashi = CStr::from_ptr(c_midashi).to_str()?;
+        let strict_okuri = optional_str_from_ptr(c_strict_okuri)?;
+        let kouho_text = CStr::from_ptr(c_kouho_text).to_str()?;
+        Ok(dictionary
+            .dictionary
+            .delete_candidate(midashi, strict_okuri, kouho_text)?)
+    })();
+    result.unwrap_or(false)
+}
+
+///
+/// 見出しc_midashiのエントリを削除する。
+///
+/// 辞書を更新した場合trueを返す。
+///
+/// # Safety
+/// c_midashiは\0終端の有効なC文字列でなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_delete_entry(
+    dictionary: &CskkDictionaryFfi,
+    c_midashi: *const c_char,
+) -> bool {
+    let result = (|| -> anyhow::Result<bool> {
+        let midashi = CStr::from_ptr(c_midashi).to_str()?;
+        Ok(dictionary.dictionary.delete_entry(midashi)?)
+    })();
+    result.unwrap_or(false)
+}
+
+///
+/// SKK辞書形式のファイルを読み込み、含まれる候補を辞書に追加する。
+/// 追加した候補の数を返す。失敗時には-1を返す。
+///
+/// # Safety
+/// c_path_string and c_encoding must be a valid c string that terminates with \0.
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_import(
+    dictionary: &CskkDictionaryFfi,
+    c_path_string: *const c_char,
+    c_encoding: *const c_char,
+) -> c_int {
+    let result = (|| -> anyhow::Result<c_int> {
+        let path = CStr::from_ptr(c_path_string).to_str()?;
+        let encoding = CStr::from_ptr(c_encoding).to_str()?;
+        let count = dictionary.dictionary.import_entries(path, encoding)?;
+        Ok(c_int::try_from(count).unwrap_or(c_int::MAX))
+    })();
+    result.unwrap_or(-1)
+}
+
+///
+/// 辞書のエントリをSKK辞書形式でファイルに書き出す。
+/// 成功時にはtrueを返す。
+///
+/// # Safety
+/// c_path_string and c_encoding must be a valid c string that terminates with \0.
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_dictionary_export(
+    dictionary: &CskkDictionaryFfi,
+    c_path_string: *const c_char,
+    c_encoding: *const c_char,
+    order: DictionaryEntryOrder,
+) -> bool {
+    let result = (|| -> anyhow::Result<()> {
+        let path = CStr::from_ptr(c_path_string).to_str()?;
+        let encoding = CStr::from_ptr(c_encoding).to_str()?;
+        dictionary
+            .dictionary
+            .export_entries(path, encoding, order)?;
+        Ok(())
+    })();
+    result.is_ok()
+}
+
+///
+/// 辞書に変更があれば保存する。
+/// 保存した場合trueを返す。読み込み専用の辞書や変更がない場合、失敗時にはfalseを返す。
+///
+#[no_mangle]
+pub extern "C" fn skk_dictionary_save(dictionary: &CskkDictionaryFfi) -> bool {
+    dictionary.dictionary.save().unwrap_or(false)
+}
+
+///
+/// 他のIMEの辞書c_ime_pathをSKK辞書形式に変換してc_skk_pathに書き出す。
+/// 変換した単語の数を返す。失敗時には-1を返す。
+///
+/// # Safety
+/// c_ime_path, c_skk_path and c_skk_encoding must be a valid c string that terminates with \0.
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_convert_ime_dictionary_to_skk(
+    c_ime_path: *const c_char,
+    format: ImeDictionaryFormat,
+    c_skk_path: *const c_char,
+    c_skk_encoding: *const c_char,
+) -> c_int {
+    let result = (|| -> anyhow::Result<c_int> {
+        let ime_path = CStr::from_ptr(c_ime_path).to_str()?;
+        let skk_path = CStr::from_ptr(c_skk_path).to_str()?;
+        let skk_encoding = CStr::from_ptr(c_skk_encoding).to_str()?;
+        let count = convert_ime_to_skk_jisyo(ime_path, format, skk_path, skk_encoding)?;
+        Ok(c_int::try_from(count).unwrap_or(c_int::MAX))
+    })();
+    result.unwrap_or(-1)
+}
+
+///
+/// SKK辞書形式の辞書c_skk_pathを他のIMEの辞書形式に変換してc_ime_pathに書き出す。
+/// 書き出した単語の数を返す。失敗時には-1を返す。
+/// 終止形を復元できない送りありの候補や、書き出し先の形式で表せない候補は書き出さず、ログに警告を出す。
+///
+/// # Safety
+/// c_skk_path, c_skk_encoding and c_ime_path must be a valid c string that terminates with \0.
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_convert_skk_dictionary_to_ime(
+    c_skk_path: *const c_char,
+    c_skk_encoding: *const c_char,
+    c_ime_path: *const c_char,
+    format: ImeDictionaryFormat,
+) -> c_int {
+    let result = (|| -> anyhow::Result<c_int> {
+        let skk_path = CStr::from_ptr(c_skk_path).to_str()?;
+        let skk_encoding = CStr::from_ptr(c_skk_encoding).to_str()?;
+        let ime_path = CStr::from_ptr(c_ime_path).to_str()?;
+        let report = convert_skk_jisyo_to_ime(skk_path, skk_encoding, ime_path, format)?;
+        for skipped in &report.skipped {
+            log::warn!(
+                "Skipped candidate: {} [{}] {}",
+                skipped.midashi,
+                skipped.strict_okuri,
+                skipped.kouho_text
+            );
+        }
+        Ok(c_int::try_from(report.written).unwrap_or(c_int::MAX))
+    })();
+    result.unwrap_or(-1)
+}
+
+///
+/// 確定済みの文字列や選択中の文字列c_textを再変換する。c_readingがNULLならば辞書の逆引きで読みを求める。
+/// 変換中の入力がある場合や読みが見つからない場合はfalseを返す。
+/// 確定した時はskk_context_poll_delete_surroundingでc_textの文字数を返すので、その分をカーソルの前から消してからskk_context_poll_outputの文字列を入れる。
+///
+/// # Safety
+/// c_textは\0終端の有効なC文字列でなければならない。
+/// c_readingは\0終端の有効なC文字列かNULLでなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_context_start_reconversion(
+    context: &mut CskkContext,
+    c_text: *const c_char,
+    c_reading: *const c_char,
+) -> bool {
+    let result = (|| -> anyhow::Result<bool> {
+        let text = CStr::from_ptr(c_text).to_str()?;
+        let reading = optional_str_from_ptr(c_reading)?;
+        Ok(context.start_reconversion(text, reading))
+    })();
+    result.unwrap_or(false)
+}
+
+///
+/// contextの辞書群からc_wordを候補に持つ読みを逆引きし、その配列を辞書の順で返す。
+/// 結果の配列の長さは引数のlengthにセットする。
+/// 失敗時や見つからなかった場合にはNULLを返す。
+///
+/// # Safety
+/// c_wordは\0終端の有効なC文字列でなければならない。
+/// lengthは有効なunsigned intへのポインタでなければならない。
+/// 返り値はcallerがskk_free_readingsで解放しないとメモリリークする。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_context_get_readings_for(
+    context: &CskkContext,
+    c_word: *const c_char,
+    length: *mut c_uint,
+) -> *mut DictionaryReadingFfi {
+    *length = 0;
+    let maybe_readings = (|| -> anyhow::Result<Vec<DictionaryReadingFfi>> {
+        let word = CStr::from_ptr(c_word).to_str()?;
+        let readings = context
+            .get_readings_for(word)
+            .into_iter()
+            .map(DictionaryReadingFfi::new)
+            .collect::<Result<Vec<DictionaryReadingFfi>, CskkError>>()?;
+        *length = u32::try_from(readings.len())?;
+        Ok(readings)
+    })();
+
+    if let Ok(readings) = maybe_readings {
+        vec_into_c_array(readings)
+    } else {
+        *length = 0;
+        ptr::null_mut()
+    }
+}
+
+///
+/// skk_context_get_readings_forで得た読みの配列を解放する。
+///
+/// # Safety
+/// ptrとlengthはskk_context_get_readings_forの返り値とその長さでなければならない。
+///
+#[no_mangle]
+pub unsafe extern "C" fn skk_free_readings(ptr: *mut DictionaryReadingFfi, length: c_uint) {
+    if ptr.is_null() {
+        return;
+    }
+    let length = length as usize;
+    drop(Vec::from_raw_parts(ptr, length, length))
+}
+
+///
+/// # Safety
+/// lengthは有効なunsigned intへのポインタでなければならない。
+///
+unsafe fn dictionary_entries_into_c_array(
+    entries: Vec<DictionaryEntryData>,
+    length: *mut c_uint,
+) -> *mut DictionaryEntryFfi {
+    *length = 0;
+    let maybe_entries = entries
+        .into_iter()
+        .map(DictionaryEntryFfi::new)
+        .collect::<Result<Vec<DictionaryEntryFfi>, CskkError>>();
+    if let Ok(entries) = maybe_entries {
+        if let Ok(count) = u32::try_from(entries.len()) {
+            *length = count;
+            return vec_into_c_array(entries);
+        }
+    }
+    ptr::null_mut()
+}
+
+///
+/// 長さと容量が一致する配列としてC側に渡す。Vec::from_raw_parts(ptr, len, len)で戻せる。
+/// 空の場合はNULLを返す。
+///
+fn vec_into_c_array<T>(vec: Vec<T>) -> *mut T {
+    if vec.is_empty() {
+        // Must treat specially since Vec with 0 capacity has some value not guaranteed to be NULL in C.
+        // See https://doc.rust-lang.org/std/vec/struct.Vec.html#guarantees
+        ptr::null_mut()
+    } else {
+        Box::into_raw(vec.into_boxed_slice()) as *mut T
+    }
+}
+
+///
+/// 文字列の配列をC文字列の配列としてC側に渡す。
+///
+fn strings_into_c_array(strings: Vec<String>) -> *mut *mut c_char {
+    vec_into_c_array(
+        strings
+            .into_iter()
+            .map(|string| CString::new(string).unwrap_or_default().into_raw())
+            .collect(),
+    )
+}
+
+fn optional_string_into_raw(string: Option<String>) -> Result<*mut c_char, CskkError> {
+    if let Some(string) = string {
+        Ok(CString::new(string)?.into_raw())
+    } else {
+        Ok(ptr::null_mut())
+    }
+}
+
+///
+/// # Safety
+/// c_stringは\0終端の有効なC文字列かNULLでなければならない。
+///
+unsafe fn optional_str_from_ptr<'a>(c_string: *const c_char) -> anyhow::Result<Option<&'a str>> {
+    if c_string.is_null() {
+        Ok(None)
+    } else {
+        Ok(Some(CStr::from_ptr(c_string).to_str()?))
+    }
+}
+
 ///
 /// # Safety
 ///
@@ -1123,4 +2333,26 @@ mod test {
             assert_eq!(b'\0', *rule.id.offset(2) as u8);
         }
     }
+
+    #[test]
+    fn poll_events_without_length() {
+        let mut context = CskkContext::new_from_specified_paths(
+            InputMode::Hiragana,
+            CompositionMode::Direct,
+            vec![],
+            "../assets/rule/kana_form.toml",
+            "../assets/rule/ascii_form.toml",
+            "../assets/rules",
+        );
+        context.process_key_events_string("a");
+        unsafe {
+            assert!(skk_context_poll_events(&mut context, ptr::null_mut()).is_null());
+            // lengthがNULLの時はイベントを取り出さない
+            let mut length = 0;
+            let events = skk_context_poll_events(&mut context, &mut length);
+            assert!(!events.is_null());
+            assert!(length > 0);
+            skk_free_events(events, length);
+        }
+    }
 }
@@ -1,14 +1,20 @@
-use crate::rule::{CskkCommandRule, CskkRule};
+use crate::rule::{CandidateSelectionRule, CskkCommandRule, CskkRule};
 use crate::{CompositionMode, CskkKeyEvent, InputMode, Instruction, SkkKeyModifier};
 
 pub(crate) struct ConfigurableCommandHandler {
     command_rule: CskkCommandRule,
+    // UndoPreeditかRedoPreeditがどこかのキーに割り当てられているか。キー入力ごとにルールを探さないように持っておく。
+    uses_preedit_history: bool,
 }
 
 impl ConfigurableCommandHandler {
     pub(crate) fn new(rule: &CskkRule) -> Self {
+        let command_rule = rule.get_command_rule().clone();
+        let uses_preedit_history = command_rule.contains_instruction(&Instruction::UndoPreedit)
+            || command_rule.contains_instruction(&Instruction::RedoPreedit);
         Self {
-            command_rule: rule.get_command_rule().clone(),
+            command_rule,
+            uses_preedit_history,
         }
     }
 
@@ -18,9 +24,19 @@ impl ConfigurableCommandHandler {
     pub(crate) fn new_empty() -> Self {
         Self {
             command_rule: CskkCommandRule::new_empty(),
+            uses_preedit_history: false,
         }
     }
 
+    /// 入力中の状態の履歴を戻すコマンドがルールで使われていればtrue
+    pub(crate) fn uses_preedit_history(&self) -> bool {
+        self.uses_preedit_history
+    }
+
+    pub(crate) fn get_candidate_selection_rule(&self) -> &CandidateSelectionRule {
+        self.command_rule.get_candidate_selection_rule()
+    }
+
     /// コマンドとして処理し、文字入力として処理しない場合にinstructionを返す。
     pub(crate) fn get_instruction(
         &self,
@@ -42,6 +42,26 @@ pub(crate) enum Instruction {
     TryNextCompletion,
     // 前のの補完候補を指そうとする、無ければ何もしない。
     TryPreviousCompletion,
+    // 変換候補を絞り込むヒントの入力を始める。ddskkのskk-hint。
+    StartHint,
+    // 直前のキー入力での確定を取り消し、確定前の変換候補の一覧に戻って次の候補を選ぶ。ddskkのskk-undo-kakutei。
+    UndoKakutei,
+    // 確定済みの末尾から続くかなを全て遡り、そこを変換開始位置として読みにする。ddskkのskk-backward-and-set-henkan-point。
+    BackwardSetHenkanPoint,
+    // 確定済みのかなを1文字だけ遡って読みの先頭に加える。繰り返すと1文字ずつ読みが伸びる。
+    BackwardSetHenkanPointChar,
+    // ▽モードの読みの中でカーソルを1文字左に動かす。
+    MoveCursorLeft,
+    // ▽モードの読みの中でカーソルを1文字右に動かす。
+    MoveCursorRight,
+    // ▽モードの読みの先頭にカーソルを動かす。
+    MoveCursorHome,
+    // ▽モードの読みの末尾にカーソルを動かす。
+    MoveCursorEnd,
+    // 入力中の状態を、変換中の直前のキー入力の前の状態に戻す。
+    UndoPreedit,
+    // UndoPreeditで戻した状態をやり直す。
+    RedoPreedit,
 }
 //
 impl FromStr for Instruction {
@@ -65,6 +85,16 @@ impl FromStr for Instruction {
             "PassthroughKeyEvent" => Some(Instruction::PassthroughKeyEvent),
             "TryNextCompletion" => Some(Instruction::TryNextCompletion),
             "TryPreviousCompletion" => Some(Instruction::TryPreviousCompletion),
+            "StartHint" => Some(Instruction::StartHint),
+            "UndoKakutei" => Some(Instruction::UndoKakutei),
+            "BackwardSetHenkanPoint" => Some(Instruction::BackwardSetHenkanPoint),
+            "BackwardSetHenkanPointChar" => Some(Instruction::BackwardSetHenkanPointChar),
+            "MoveCursorLeft" => Some(Instruction::MoveCursorLeft),
+            "MoveCursorRight" => Some(Instruction::MoveCursorRight),
+            "MoveCursorHome" => Some(Instruction::MoveCursorHome),
+            "MoveCursorEnd" => Some(Instruction::MoveCursorEnd),
+            "UndoPreedit" => Some(Instruction::UndoPreedit),
+            "RedoPreedit" => Some(Instruction::RedoPreedit),
             // 以下旧版の互換性維持のため。メジャーバージョンアップで消しうる。
             "ConfirmAsHiragana" => Some(Instruction::ConfirmAs(InputMode::Hiragana)),
             "ConfirmAsKatakana" => Some(Instruction::ConfirmAs(InputMode::Katakana)),
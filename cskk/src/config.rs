@@ -1,10 +1,22 @@
-use crate::skk_modes::{CommaStyle, PeriodStyle};
+use crate::skk_modes::{CommaStyle, InputMode, PeriodStyle};
 
 pub(crate) struct CskkConfig {
     pub(crate) auto_start_henkan_keywords: Vec<String>,
     // Easy override only for period and comma for libskk compatibility.
     pub(crate) period_style: PeriodStyle,
     pub(crate) comma_style: CommaStyle,
+    // 入力中に補完候補として保持する件数。0なら補完候補を探さない。
+    pub(crate) dynamic_completion_count: usize,
+    // 変換候補がない時に打ち間違いを訂正した読みを探す編集コストの上限。0なら探さない。
+    pub(crate) fuzzy_fallback_max_cost: usize,
+    // 変換候補の末尾に読みをかな変換して加える入力モードの並び。空なら加えない。
+    pub(crate) kana_form_candidates: Vec<InputMode>,
+    // 送り仮名がさ・し・す・せの送りあり変換で送りなしの語幹も引くか。ddskkのskk-search-sagyo-henkaku。
+    pub(crate) sagyo_henkaku_search: bool,
+    // 送りなしの読みを語幹と送り仮名に分けて送りありエントリも引くか。ddskkのskk-auto-okuri-process。
+    pub(crate) auto_okuri_process: bool,
+    // 変換候補の一覧の表示とページ送り、選択キーでの選択をライブラリ側で行うか。
+    pub(crate) builtin_candidate_selection: bool,
 }
 
 impl Default for CskkConfig {
@@ -18,6 +30,12 @@ impl Default for CskkConfig {
             ],
             period_style: PeriodStyle::PeriodJa,
             comma_style: CommaStyle::CommaJa,
+            dynamic_completion_count: 0,
+            fuzzy_fallback_max_cost: 0,
+            kana_form_candidates: vec![],
+            sagyo_henkaku_search: false,
+            auto_okuri_process: false,
+            builtin_candidate_selection: false,
         }
     }
 }
@@ -34,4 +52,28 @@ impl CskkConfig {
     pub fn set_comma_style(&mut self, comma_style: CommaStyle) {
         self.comma_style = comma_style;
     }
+
+    pub fn set_dynamic_completion_count(&mut self, count: usize) {
+        self.dynamic_completion_count = count;
+    }
+
+    pub fn set_fuzzy_fallback_max_cost(&mut self, max_cost: usize) {
+        self.fuzzy_fallback_max_cost = max_cost;
+    }
+
+    pub fn set_kana_form_candidates(&mut self, input_modes: Vec<InputMode>) {
+        self.kana_form_candidates = input_modes;
+    }
+
+    pub fn set_sagyo_henkaku_search(&mut self, enabled: bool) {
+        self.sagyo_henkaku_search = enabled;
+    }
+
+    pub fn set_auto_okuri_process(&mut self, enabled: bool) {
+        self.auto_okuri_process = enabled;
+    }
+
+    pub fn set_builtin_candidate_selection(&mut self, enabled: bool) {
+        self.builtin_candidate_selection = enabled;
+    }
 }
@@ -2,6 +2,7 @@ use crate::candidate_list::CandidateList;
 use crate::cskkstate::CskkStateInfo::Complete;
 use crate::dictionary::candidate::Candidate;
 use crate::dictionary::CompositeKey;
+use crate::error::CskkError;
 use crate::form_changer::KanaFormChanger;
 use crate::skk_modes::{CompositionMode, InputMode};
 use crate::CskkStateInfo::{
@@ -19,6 +20,7 @@ const COMPOSITION_MODE_HISTORY_MAX: usize = 20;
 // candidate_listをcompositionでも共用してしまっている。こういった変数の区別を付けたい。
 /// Rough prototype yet.
 ///
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct CskkState {
     pub(crate) input_mode: InputMode,
     pub(crate) composition_mode: CompositionMode,
@@ -27,6 +29,7 @@ pub(crate) struct CskkState {
     // DirectモードからのCompositionMode遷移。Abort時に元に戻すmode。
     composition_mode_history: VecDeque<CompositionMode>,
     // 入力文字で、かな確定済みでないものすべて
+    #[serde(with = "keysyms_serde")]
     pub(crate) pre_conversion: Vec<Keysym>,
     // 変換辞書のキーとなる部分。送りなし変換の場合はconverted_kana_to_composite と同じ。
     // 送りあり変換時には'>'なども付く。Abbrebiation変換の場合kana-convertされる前の入力など
@@ -35,6 +38,9 @@ pub(crate) struct CskkState {
     raw_to_composite: String,
     // 未確定入力の漢字の読み部分。主にひらがな、Abbrev等の時は英字もありうる。出力時にInputModeにあわせて変換される。
     converted_kana_to_composite: String,
+    // PreCompositionモードでのconverted_kana_to_composite中のカーソル位置を文字数で表したもの。Noneならば末尾にある。
+    // かなの入力や削除はこの位置で行う。
+    composite_cursor: Option<usize>,
     // 未確定入力の漢字の読み以外の部分。多くの場合送り仮名であり、その想定のもとに変数名を付けてしまったが、auto_start_henkan等の強制的に変換を開始する場合にはおくりがな以外のpostfixが入ることもある。
     converted_kana_to_okuri: String,
     // 現在の変換候補リスト
@@ -48,6 +54,11 @@ pub(crate) struct CskkState {
     // 現在送り仮名を入力しているかどうか。converted_kana_to_okuriを送り仮名として用いるべきかどうか。
     // FIXME: ちゃんと意味ごとに別のフィールドに入れ、このようなboolでフィールドの意味を変えないようにリファクタリング。
     use_okurigana: bool,
+    // 入力中の読みに対する補完候補の先頭から数件。CompositionModeを変えずに保持し、表示や選択はIME側に任せる。
+    completion_suggestions: Vec<Candidate>,
+    // ddskkのskk-hintのように変換候補を絞り込むため、ヒントとして入力中の読み。Noneならヒントを入力していない。
+    // かな変換前の入力はpre_conversionに持つ。
+    hint_reading: Option<String>,
 }
 
 ///
@@ -86,6 +97,29 @@ pub struct CompositionSelectionData {
     pub okuri: Option<String>,
     /// 現在の候補のアノテーション
     pub annotation: Option<String>,
+    /// 現在の候補が打ち間違いを訂正した読みで見つかったものであれば、その訂正した読み
+    pub corrected_reading: Option<String>,
+    /// 候補を絞り込むヒントを入力中であれば、ヒントとして入力したかな
+    pub hint: Option<String>,
+    /// ヒントを入力中であれば、ヒントのかな変換が成されていない入力キーの文字列表現
+    pub hint_unconverted: Option<String>,
+    /// 組み込みの候補選択で候補の一覧を表示している時、現在のページ
+    pub page: Option<CandidatePage>,
+}
+
+///
+/// 組み込みの候補選択で表示する候補一覧の1ページ分。
+///
+#[derive(Debug, PartialEq, Eq)]
+pub struct CandidatePage {
+    /// このページの変換候補
+    pub candidates: Vec<String>,
+    /// 各変換候補を選ぶキーの表示
+    pub labels: Vec<String>,
+    /// 0から数えた現在のページ番号
+    pub page_index: usize,
+    /// 一覧のページ数
+    pub page_count: usize,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -96,6 +130,10 @@ pub struct PreCompositionData {
     pub confirmed: String,
     /// 漢字変換に用いようとしている部分
     pub kana_to_composite: String,
+    /// 漢字変換に用いようとしている部分の中のカーソル位置を文字数で表したもの。
+    ///
+    /// PreCompositionでかな変換が成されていない入力はこの位置に付く。PreCompositionOkuriganaでは常に末尾を指す。
+    pub cursor: usize,
     /// 漢字変換時に送り仮名として用いようとしている部分
     pub okuri: Option<String>,
     /// かな変換が成されていない入力キーの文字列表現。
@@ -104,6 +142,40 @@ pub struct PreCompositionData {
     ///
     /// PreCompositionOkuriganaならば送り仮名に用いようとしている部分に付く。
     pub unconverted: Option<String>,
+    /// 漢字変換に用いようとしている部分から補完した候補。
+    ///
+    /// [CskkContext::set_dynamic_completion_count]で件数が設定されていなければ空である。
+    pub completion_suggestions: Vec<CompletionSuggestion>,
+}
+
+///
+/// 入力中の読みに対する補完候補。ddskkのskk-dcompのように入力中に表示するためのもの。
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CompletionSuggestion {
+    /// 補完された見出し
+    pub midashi: String,
+    /// 補完された変換候補
+    pub output: String,
+    /// 送りありエントリから補完した候補の送り仮名
+    pub okuri: Option<String>,
+    /// 候補のアノテーション
+    pub annotation: Option<String>,
+}
+
+impl CompletionSuggestion {
+    pub(crate) fn from_candidate(candidate: &Candidate) -> Self {
+        CompletionSuggestion {
+            midashi: candidate.midashi.to_owned(),
+            output: candidate.output.to_owned(),
+            okuri: if candidate.okuri {
+                candidate.strict_okuri.to_owned()
+            } else {
+                None
+            },
+            annotation: candidate.annotation.to_owned(),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -146,12 +218,15 @@ impl CskkState {
             pre_conversion: vec![],
             raw_to_composite: "".to_string(),
             converted_kana_to_composite: "".to_string(),
+            composite_cursor: None,
             converted_kana_to_okuri: "".to_string(),
             composited_okuri: "".to_string(),
             confirmed: "".to_string(),
             candidate_list: CandidateList::new(),
             capital_transition: false,
             use_okurigana: false,
+            completion_suggestions: vec![],
+            hint_reading: None,
         }
     }
 
@@ -180,6 +255,7 @@ impl CskkState {
     pub(crate) fn clear_kanas(&mut self) {
         self.clear_preconverted_kanainputs();
         self.converted_kana_to_composite.clear();
+        self.composite_cursor = None;
         self.converted_kana_to_okuri.clear();
 
         self.use_okurigana = false;
@@ -187,6 +263,7 @@ impl CskkState {
 
     /// 確定済みではない入力を全て消す。
     pub(crate) fn clear_unconfirmed(&mut self) {
+        self.hint_reading = None;
         self.raw_to_composite.clear();
         self.clear_candidate_list();
         self.clear_kanas();
@@ -204,6 +281,77 @@ impl CskkState {
         self.confirmed.clear();
     }
 
+    /// 変換の読みの先頭にkanaを加える。確定済みのかなを遡って変換開始位置を置き直す時に使う。
+    pub(crate) fn prepend_kana_to_composite(&mut self, kana: &str) {
+        self.converted_kana_to_composite.insert_str(0, kana);
+        self.raw_to_composite.insert_str(0, kana);
+        if let Some(cursor) = self.composite_cursor.as_mut() {
+            *cursor += kana.chars().count();
+        }
+    }
+
+    /// 読みの中のカーソル位置を文字数で返す。
+    pub(crate) fn get_composite_cursor(&self) -> usize {
+        self.composite_cursor
+            .unwrap_or_else(|| self.converted_kana_to_composite.chars().count())
+    }
+
+    /// 読みの中のカーソル位置を文字数で設定する。読みの長さ以上ならば末尾に置く。
+    pub(crate) fn set_composite_cursor(&mut self, cursor: usize) {
+        self.composite_cursor = if cursor < self.converted_kana_to_composite.chars().count() {
+            Some(cursor)
+        } else {
+            None
+        };
+    }
+
+    // 読みのカーソル位置にかなを入れ、カーソルをその後ろに進める。
+    fn insert_kana_to_composite(&mut self, kana: &str) {
+        if let Some(cursor) = self.composite_cursor {
+            let byte_index = char_to_byte_index(&self.converted_kana_to_composite, cursor);
+            self.converted_kana_to_composite
+                .insert_str(byte_index, kana);
+            let byte_index = char_to_byte_index(&self.raw_to_composite, cursor);
+            self.raw_to_composite.insert_str(byte_index, kana);
+            self.composite_cursor = Some(cursor + kana.chars().count());
+        } else {
+            self.converted_kana_to_composite.push_str(kana);
+            self.raw_to_composite.push_str(kana);
+        }
+    }
+
+    // 読みのカーソル位置の直前の1文字を消す。消せたらtrueを返す。
+    fn delete_kana_before_composite_cursor(&mut self) -> bool {
+        match self.composite_cursor {
+            Some(0) => false,
+            Some(cursor) => {
+                let byte_index = char_to_byte_index(&self.converted_kana_to_composite, cursor - 1);
+                self.converted_kana_to_composite.remove(byte_index);
+                let byte_index = char_to_byte_index(&self.raw_to_composite, cursor - 1);
+                if byte_index < self.raw_to_composite.len() {
+                    self.raw_to_composite.remove(byte_index);
+                }
+                self.composite_cursor = Some(cursor - 1);
+                true
+            }
+            None => {
+                self.raw_to_composite.pop();
+                self.converted_kana_to_composite.pop().is_some()
+            }
+        }
+    }
+
+    /// 確定済み文字列の末尾がretractedであれば取り除いてtrueを返す。pollされて既に無ければfalseを返す。
+    pub(crate) fn retract_confirmed_string(&mut self, retracted: &str) -> bool {
+        if self.confirmed.ends_with(retracted) {
+            self.confirmed
+                .truncate(self.confirmed.len() - retracted.len());
+            true
+        } else {
+            false
+        }
+    }
+
     /// 現在のモードで1文字消去する。
     /// 1文字でも消去されたら、trueを返す。
     /// 何も処理されなかったら、falseを返す。
@@ -221,13 +369,14 @@ impl CskkState {
             CompositionMode::PreComposition | CompositionMode::Abbreviation => {
                 // かな変換前の入力を1文字消そうとする
                 let mut deleted = self.pre_conversion.pop().is_some();
-                // できなければ未確定かなを1文字消そうとする。
+                // できなければカーソルの前の未確定かなを1文字消そうとする。
                 if !deleted {
-                    deleted = self.converted_kana_to_composite.pop().is_some();
-                    self.raw_to_composite.pop();
+                    deleted = self.delete_kana_before_composite_cursor();
                 }
                 // それもできなければ初めてDirectにモード変更する。未確定文字0文字状態が許容される。
-                if !deleted {
+                // カーソルの後ろに未確定かなが残っている場合は何もしない。
+                if !deleted && self.converted_kana_to_composite.is_empty() {
+                    self.composite_cursor = None;
                     self.composition_mode = CompositionMode::Direct;
                 }
                 // Mode change でキーを消費するので常にtrueで良い。
@@ -271,16 +420,14 @@ impl CskkState {
                 self.confirmed.push_str(letter_or_word);
             }
             CompositionMode::PreComposition => {
-                self.converted_kana_to_composite.push_str(letter_or_word);
-                self.raw_to_composite.push_str(letter_or_word);
+                self.insert_kana_to_composite(letter_or_word);
             }
             CompositionMode::PreCompositionOkurigana => {
                 self.converted_kana_to_okuri.push_str(letter_or_word);
                 self.use_okurigana = true;
             }
             CompositionMode::Abbreviation => {
-                self.converted_kana_to_composite.push_str(letter_or_word);
-                self.raw_to_composite.push_str(letter_or_word);
+                self.insert_kana_to_composite(letter_or_word);
             }
             CompositionMode::CompositionSelection | CompositionMode::Completion => {
                 self.confirmed.push_str(letter_or_word);
@@ -327,10 +474,15 @@ impl CskkState {
                 direct_data.confirmed.to_owned() + &direct_data.unconverted.unwrap_or_default()
             }
             PreComposition(precomposition_data) => {
+                let (before_cursor, after_cursor) = split_at_char(
+                    &precomposition_data.kana_to_composite,
+                    precomposition_data.cursor,
+                );
                 precomposition_data.confirmed.to_owned()
                     + "▽"
-                    + &precomposition_data.kana_to_composite
+                    + before_cursor
                     + &precomposition_data.unconverted.unwrap_or_default()
+                    + after_cursor
             }
             PreCompositionOkurigana(precomposition_data) => {
                 precomposition_data.confirmed.to_owned()
@@ -341,10 +493,22 @@ impl CskkState {
                     + &precomposition_data.unconverted.unwrap_or_default()
             }
             CompositionSelection(composition_selection_data) => {
+                // ヒントの開始に割り当てたキーによらず、読みの入力中であることを▽で示す
+                let hint = match composition_selection_data.hint {
+                    Some(hint) => {
+                        "▽".to_string()
+                            + &hint
+                            + &composition_selection_data
+                                .hint_unconverted
+                                .unwrap_or_default()
+                    }
+                    None => "".to_string(),
+                };
                 composition_selection_data.confirmed.to_owned()
                     + "▼"
                     + &composition_selection_data.composited
                     + &composition_selection_data.okuri.unwrap_or_default()
+                    + &hint
             }
             Register(register_data) => {
                 if register_data.okuri.is_some() {
@@ -402,21 +566,38 @@ impl CskkState {
                 unconverted,
             }),
             CompositionMode::PreComposition | CompositionMode::Abbreviation => {
+                // 入力モードによってかなの文字数が変わりうるので、カーソルより前を変換した文字数を位置とする。
+                let (before_cursor, _) = split_at_char(
+                    &self.converted_kana_to_composite,
+                    self.get_composite_cursor(),
+                );
                 PreComposition(PreCompositionData {
                     confirmed: self.confirmed.to_owned(),
                     kana_to_composite: kana_form_changer
                         .adjust_kana_string(current_input_mode, &self.converted_kana_to_composite),
+                    cursor: kana_form_changer
+                        .adjust_kana_string(current_input_mode, before_cursor)
+                        .chars()
+                        .count(),
                     okuri,
                     unconverted,
+                    completion_suggestions: self
+                        .completion_suggestions
+                        .iter()
+                        .map(CompletionSuggestion::from_candidate)
+                        .collect(),
                 })
             }
             CompositionMode::PreCompositionOkurigana => {
+                let kana_to_composite = kana_form_changer
+                    .adjust_kana_string(current_input_mode, &self.converted_kana_to_composite);
                 PreCompositionOkurigana(PreCompositionData {
                     confirmed: self.confirmed.to_owned(),
-                    kana_to_composite: kana_form_changer
-                        .adjust_kana_string(current_input_mode, &self.converted_kana_to_composite),
+                    cursor: kana_to_composite.chars().count(),
+                    kana_to_composite,
                     okuri,
                     unconverted,
+                    completion_suggestions: vec![],
                 })
             }
             CompositionMode::Register => {
@@ -441,14 +622,33 @@ impl CskkState {
                 let candidate = current_candidate.unwrap_or(&fallback_candidate).to_owned();
                 let composited = candidate.output;
                 let annotation = candidate.annotation;
+                let corrected_reading = candidate.corrected_reading.map(|corrected_reading| {
+                    kana_form_changer.adjust_kana_string(current_input_mode, &corrected_reading)
+                });
+                let hint = self.hint_reading.as_ref().map(|hint_reading| {
+                    kana_form_changer.adjust_kana_string(current_input_mode, hint_reading)
+                });
+                let hint_unconverted = if hint.is_some() { unconverted } else { None };
                 CompositionSelection(CompositionSelectionData {
                     confirmed: self.confirmed.to_owned(),
                     composited,
                     okuri,
                     annotation,
+                    corrected_reading,
+                    hint,
+                    hint_unconverted,
+                    page: None,
                 })
             }
             CompositionMode::Completion => {
+                let okuri = self
+                    .get_completion_okuri()
+                    .map_or(okuri, |completion_okuri| {
+                        Some(
+                            kana_form_changer
+                                .adjust_kana_string(current_input_mode, completion_okuri),
+                        )
+                    });
                 let complete_origin = self.converted_kana_to_composite.to_owned();
                 let current_candidate = self.candidate_list.get_current_candidate();
                 let fallback_candidate = Candidate::default();
@@ -468,6 +668,24 @@ impl CskkState {
         }
     }
 
+    ///
+    /// 送りありエントリから補完した候補を選んでいる時、その候補の送り仮名を返す。
+    /// 入力には送り仮名がないので、候補の厳密な送り仮名を送り仮名とする。
+    ///
+    pub(crate) fn get_completion_okuri(&self) -> Option<&str> {
+        if self.composition_mode != CompositionMode::Completion
+            || !self.converted_kana_to_okuri.is_empty()
+        {
+            return None;
+        }
+        let candidate = self.candidate_list.get_current_candidate().ok()?;
+        if candidate.okuri {
+            candidate.strict_okuri.as_deref()
+        } else {
+            None
+        }
+    }
+
     /// 今のステートで変換する時の辞書のキーとして使うべき文字列を返す。
     pub(crate) fn get_composite_key(&self) -> CompositeKey {
         if self.use_okurigana && !self.converted_kana_to_okuri.is_empty() {
@@ -514,7 +732,7 @@ impl CskkState {
         if self.composition_mode_history.len() > COMPOSITION_MODE_HISTORY_MAX {
             self.composition_mode_history.pop_front();
         }
-        self.composition_mode = new_mode;
+        self.set_composition_mode_resetting_cursor(new_mode);
     }
 
     /// new_modeにモードを変えるが、その時直前のモードをold_modeであったとして記録する。
@@ -528,10 +746,56 @@ impl CskkState {
         if self.composition_mode_history.len() > COMPOSITION_MODE_HISTORY_MAX {
             self.composition_mode_history.pop_front();
         }
+        self.set_composition_mode_resetting_cursor(new_mode);
+    }
+
+    // 読みの中のカーソルはPreCompositionとAbbreviationでのみ使うので、他のモードに移る時には末尾に戻す。
+    fn set_composition_mode_resetting_cursor(&mut self, new_mode: CompositionMode) {
+        if !matches!(
+            new_mode,
+            CompositionMode::PreComposition | CompositionMode::Abbreviation
+        ) {
+            self.composite_cursor = None;
+        }
         self.composition_mode = new_mode;
     }
 }
 
+// 文字数で表した位置をバイト位置にする。文字数が足りなければ末尾を返す。
+fn char_to_byte_index(string: &str, char_index: usize) -> usize {
+    string
+        .char_indices()
+        .nth(char_index)
+        .map_or(string.len(), |(byte_index, _)| byte_index)
+}
+
+// 文字数で表した位置で文字列を分ける。
+fn split_at_char(string: &str, char_index: usize) -> (&str, &str) {
+    string.split_at(char_to_byte_index(string, char_index))
+}
+
+// snapshotのため、Keysymをkeysymの数値として保存する。
+mod keysyms_serde {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use xkbcommon::xkb::Keysym;
+
+    pub(super) fn serialize<S: Serializer>(
+        keysyms: &[Keysym],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(keysyms.iter().map(|keysym| keysym.raw()))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Keysym>, D::Error> {
+        Ok(Vec::<u32>::deserialize(deserializer)?
+            .into_iter()
+            .map(Keysym::new)
+            .collect())
+    }
+}
+
 // candidate_lists
 impl CskkState {
     pub(crate) fn get_candidate_list(&self) -> &CandidateList {
@@ -572,6 +836,89 @@ impl CskkState {
         self.candidate_list.set(composite_key, candidates);
         self.composited_okuri = self.converted_kana_to_okuri.to_string();
     }
+
+    pub(crate) fn get_completion_suggestions(&self) -> &Vec<Candidate> {
+        &self.completion_suggestions
+    }
+
+    pub(crate) fn set_completion_suggestions(&mut self, suggestions: Vec<Candidate>) {
+        self.completion_suggestions = suggestions;
+    }
+
+    /// 変換候補を絞り込むヒントの入力を始める。
+    pub(crate) fn start_hint(&mut self) {
+        self.clear_preconverted_kanainputs();
+        self.hint_reading = Some("".to_string());
+    }
+
+    /// ヒントを入力中ならばtrue
+    pub(crate) fn is_hint_active(&self) -> bool {
+        self.hint_reading.is_some()
+    }
+
+    /// ヒントを入力中ならば、かな変換済みの入力をヒントの読みに加える。
+    pub(crate) fn push_hint(&mut self, kana: &str) {
+        if let Some(hint_reading) = &mut self.hint_reading {
+            hint_reading.push_str(kana);
+        }
+    }
+
+    /// ヒントを一文字消す。かな変換前の入力があればそれを消す。
+    /// 消すものが無ければヒントの入力をやめる。
+    pub(crate) fn delete_hint(&mut self) {
+        if self.pre_conversion.pop().is_some() {
+            return;
+        }
+        if let Some(hint_reading) = &mut self.hint_reading {
+            if hint_reading.pop().is_none() {
+                self.hint_reading = None;
+            }
+        }
+    }
+
+    /// ヒントの入力を終え、入力したヒントの読みを返す。かな変換前の入力は捨てる。
+    pub(crate) fn take_hint(&mut self) -> Option<String> {
+        self.clear_preconverted_kanainputs();
+        self.hint_reading.take()
+    }
+
+    /// 外から読み込んだ状態が、この状態を扱う処理の前提を満たしているか確かめる。
+    pub(crate) fn validate(&self) -> Result<(), CskkError> {
+        let candidate_count = self.candidate_list.len();
+        let selection_pointer = self.candidate_list.get_selection_pointer();
+        if candidate_count == 0 {
+            if matches!(
+                self.composition_mode,
+                CompositionMode::CompositionSelection | CompositionMode::Completion
+            ) {
+                return Err(CskkError::Error(format!(
+                    "No candidate in {:?} mode",
+                    self.composition_mode
+                )));
+            }
+            if selection_pointer != 0 {
+                return Err(CskkError::Error(format!(
+                    "Selection pointer {} in empty candidate list",
+                    selection_pointer
+                )));
+            }
+        } else if selection_pointer >= candidate_count {
+            return Err(CskkError::Error(format!(
+                "Selection pointer {} out of {} candidates",
+                selection_pointer, candidate_count
+            )));
+        }
+        if let Some(cursor) = self.composite_cursor {
+            let reading_len = self.converted_kana_to_composite.chars().count();
+            if cursor >= reading_len {
+                return Err(CskkError::Error(format!(
+                    "Composite cursor {} out of reading length {}",
+                    cursor, reading_len
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Debug for CskkState {
@@ -596,6 +943,8 @@ impl Debug for CskkState {
             .field("confirmed", &self.confirmed)
             .field("capital_transition", &self.capital_transition)
             .field("candidate_list", &self.candidate_list)
+            .field("completion_suggestions", &self.completion_suggestions)
+            .field("hint_reading", &self.hint_reading)
             .finish()
     }
 }
@@ -3,7 +3,7 @@ use crate::dictionary::CompositeKey;
 
 // CandidateListに持たせる情報。
 // libskk vala Candidate classを元に、単体で送り仮名の厳密マッチの登録に必要な情報を持たせている。TODO: libskk 由来なので重複した情報を整理、valaなので外に見せすぎ、特にcomposite_keyに含まれる情報は不要かも
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Candidate {
     // 取り回しの都合上DictEntryと重複して持つ
     pub(crate) midashi: String,
@@ -16,6 +16,8 @@ pub struct Candidate {
     pub(crate) annotation: Option<String>,
     // Output to show the candidate. "第#0回"が"第壱回"のように後処理されている想定。
     pub(crate) output: String,
+    // 打ち間違いを訂正した読みで引いた候補の場合、その訂正した読み。
+    pub(crate) corrected_reading: Option<String>,
 }
 
 impl Default for Candidate {
@@ -27,6 +29,7 @@ impl Default for Candidate {
             kouho_text: "エラー".to_string(),
             annotation: None,
             output: "エラー".to_string(),
+            corrected_reading: None,
         }
     }
 }
@@ -56,6 +59,7 @@ impl Candidate {
             kouho_text,
             annotation,
             output,
+            corrected_reading: None,
         }
     }
 
@@ -73,6 +77,37 @@ impl Candidate {
             kouho_text: dictionary_cand.kouho_text.to_owned(),
             annotation: dictionary_cand.annotation.to_owned(),
             output: dictionary_cand.kouho_text.to_owned(),
+            corrected_reading: None,
+        }
+    }
+
+    ///
+    /// 読みそのものをかな変換した文字列を候補とする。辞書に無い外来語等のため、辞書の候補と同様に学習される。
+    ///
+    pub(crate) fn from_kana_form(composite_key: &CompositeKey, kana_form: String) -> Self {
+        Self {
+            midashi: composite_key.get_dict_key(),
+            strict_okuri: composite_key.get_okuri().to_owned(),
+            okuri: composite_key.has_okuri(),
+            kouho_text: kana_form.to_owned(),
+            annotation: None,
+            output: kana_form,
+            corrected_reading: None,
+        }
+    }
+
+    ///
+    /// 再変換する文字列そのものを候補とする。辞書に無くても元の文字列を選び直せるようにするため。
+    ///
+    pub(crate) fn from_reconversion_target(composite_key: &CompositeKey, target: String) -> Self {
+        Self {
+            midashi: composite_key.get_dict_key(),
+            strict_okuri: composite_key.get_okuri().to_owned(),
+            okuri: composite_key.has_okuri(),
+            kouho_text: target.to_owned(),
+            annotation: None,
+            output: target,
+            corrected_reading: None,
         }
     }
 
@@ -89,6 +124,7 @@ impl Candidate {
             kouho_text: completion_candidate.kouho_text.to_owned(),
             annotation: completion_candidate.annotation.to_owned(),
             output: completion_candidate.kouho_text.to_owned(),
+            corrected_reading: None,
         }
     }
 }
@@ -114,6 +114,123 @@ impl DictEntry {
         }
     }
 
+    /// 辞書編集用。candidateを先頭に追加する。
+    /// 既に含まれる場合は先頭に移動し、アノテーションを与えられたものに置き換える。
+    /// prioritize_candidate同様、厳密な送り仮名がある場合は厳密な送り仮名なしの候補にも追加する。
+    pub(in crate::dictionary) fn add_candidate(&mut self, candidate: &Candidate) {
+        self.prioritize_candidate(candidate);
+
+        let mut strict_okuris = vec![""];
+        if let Some(okuri) = &candidate.strict_okuri {
+            strict_okuris.push(okuri);
+        }
+        for strict_okuri in strict_okuris {
+            if let Some(cands) = self.strict_okuri_candidate_map.get_mut(strict_okuri) {
+                // prioritize済みなので先頭にある。
+                if let Some(cand) = cands.first_mut() {
+                    cand.annotation = candidate.annotation.to_owned();
+                }
+            }
+        }
+    }
+
+    /// 辞書編集用。strict_okuriの候補の中のkouho_textをnew_positionに移動する。
+    /// new_positionが候補数以上の場合は末尾に移動する。
+    /// 移動した場合trueを返す。
+    pub(in crate::dictionary) fn move_candidate(
+        &mut self,
+        strict_okuri: &str,
+        kouho_text: &str,
+        new_position: usize,
+    ) -> bool {
+        if let Some(cands) = self.strict_okuri_candidate_map.get_mut(strict_okuri) {
+            if let Some(index) = cands.iter().position(|it| it.kouho_text == kouho_text) {
+                let cand = cands.remove(index);
+                let new_position = new_position.min(cands.len());
+                cands.insert(new_position, cand);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// 辞書編集用。kouho_textに合う候補を削除する。
+    /// strict_okuriがNoneの場合、厳密な送り仮名の有無にかかわらず全ての候補から削除する。
+    /// strict_okuriがSomeの場合、その厳密な送り仮名の候補からのみ削除する。
+    /// 空になった厳密な送り仮名の候補の組は取り除く。
+    /// 削除した場合trueを返す。
+    pub(in crate::dictionary) fn delete_candidate(
+        &mut self,
+        strict_okuri: Option<&str>,
+        kouho_text: &str,
+    ) -> bool {
+        let mut deleted = false;
+        for (okuri, cands) in self.strict_okuri_candidate_map.iter_mut() {
+            if strict_okuri.is_none() || strict_okuri == Some(okuri.as_str()) {
+                let before = cands.len();
+                cands.retain(|it| it.kouho_text != kouho_text);
+                deleted |= before != cands.len();
+            }
+        }
+        self.strict_okuri_candidate_map
+            .retain(|okuri, cands| okuri.is_empty() || !cands.is_empty());
+        deleted
+    }
+
+    /// 候補が一つもない場合true
+    pub(in crate::dictionary) fn is_empty(&self) -> bool {
+        self.strict_okuri_candidate_map
+            .values()
+            .all(|cands| cands.is_empty())
+    }
+
+    /// otherの候補のうち、このエントリに含まれないものを各送り仮名の候補の末尾に追加する。
+    /// 追加した候補の数を返す。
+    pub(in crate::dictionary) fn merge(&mut self, other: DictEntry) -> usize {
+        let mut added = 0;
+        for (strict_okuri, other_cands) in other.strict_okuri_candidate_map {
+            let cands = self
+                .strict_okuri_candidate_map
+                .entry(strict_okuri)
+                .or_default();
+            for other_cand in other_cands {
+                if !cands
+                    .iter()
+                    .any(|it| it.kouho_text == other_cand.kouho_text)
+                {
+                    cands.push(other_cand);
+                    added += 1;
+                }
+            }
+        }
+        added
+    }
+
+    /// kouho_textと一致する候補があればtrue
+    pub(in crate::dictionary) fn has_candidate(&self, kouho_text: &str) -> bool {
+        self.strict_okuri_candidate_map
+            .values()
+            .flatten()
+            .any(|cand| cand.kouho_text == kouho_text)
+    }
+
+    /// kouho_textにsubstringを含む候補があればtrue
+    pub(in crate::dictionary) fn has_candidate_containing(&self, substring: &str) -> bool {
+        self.strict_okuri_candidate_map
+            .values()
+            .flatten()
+            .any(|cand| cand.kouho_text.contains(substring))
+    }
+
+    /// 厳密な送り仮名の組ごとの候補を厳密な送り仮名なしのものから順に返す。
+    pub(in crate::dictionary) fn iter_candidates_with_strict_okuri(
+        &self,
+    ) -> impl Iterator<Item = (&str, &DictionaryCandidate)> {
+        self.strict_okuri_candidate_map
+            .iter()
+            .flat_map(|(okuri, cands)| cands.iter().map(move |cand| (okuri.as_str(), cand)))
+    }
+
     /// strict_okuriのマッチするエントリを返す。
     ///
     pub(in crate::dictionary) fn get_candidates(
@@ -143,11 +260,7 @@ impl DictEntry {
 
     fn from_dict_entry_prototype(dict_entry_prototype: DictEntryPrototype) -> Self {
         let midashi = DictEntry::process_lisp_fun(dict_entry_prototype.midashi);
-        let alphabet = [
-            'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q',
-            'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
-        ];
-        let has_okuri = !midashi.starts_with(alphabet) && midashi.ends_with(alphabet);
+        let has_okuri = DictEntry::is_okuri_ari_midashi(&midashi);
 
         let strict_okuri_candidate_map =
             DictEntry::candidates_from_prototype(dict_entry_prototype.candidates);
@@ -159,6 +272,15 @@ impl DictEntry {
         }
     }
 
+    /// 見出し語先頭がアルファベットではない(abbrevエントリではないと推定) かつ 末尾にアルファベットが付くものを送りありの見出しと推定する。
+    pub(in crate::dictionary) fn is_okuri_ari_midashi(midashi: &str) -> bool {
+        let alphabet = [
+            'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q',
+            'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+        ];
+        !midashi.starts_with(alphabet) && midashi.ends_with(alphabet)
+    }
+
     fn candidates_from_prototype(
         candidates_prototype: BTreeMap<&str, Vec<CandidatePrototype>>,
     ) -> BTreeMap<String, Vec<DictionaryCandidate>> {
@@ -405,6 +527,27 @@ mod test {
         assert_eq!(None, *annotation);
     }
 
+    #[test]
+    fn move_and_delete() {
+        let jisyo = "あいs /愛/相/藍/[さ/相/愛/]/";
+        let mut dict_entry = DictEntry::from_skkjisyo_line(jisyo).unwrap();
+        assert!(dict_entry.move_candidate("", "藍", 0));
+        assert!(!dict_entry.move_candidate("", "間", 0));
+        assert_eq!("あいs /藍/愛/相/[さ/相/愛/]/", &dict_entry.to_string());
+        assert!(dict_entry.delete_candidate(Some("さ"), "相"));
+        assert_eq!("あいs /藍/愛/相/[さ/愛/]/", &dict_entry.to_string());
+        assert!(dict_entry.delete_candidate(None, "愛"));
+        assert_eq!("あいs /藍/相/", &dict_entry.to_string());
+    }
+
+    #[test]
+    fn merge() {
+        let mut dict_entry = DictEntry::from_skkjisyo_line("あい /愛/相/").unwrap();
+        let other = DictEntry::from_skkjisyo_line("あい /藍/愛;love/").unwrap();
+        assert_eq!(1, dict_entry.merge(other));
+        assert_eq!("あい /愛/相/藍/", &dict_entry.to_string());
+    }
+
     #[test]
     fn lisp_entry_slash() {
         let result = DictEntry::process_lisp_fun(r#"(concat "DOS\057V")"#);
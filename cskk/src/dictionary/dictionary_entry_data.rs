@@ -0,0 +1,75 @@
+use crate::dictionary::dictentry::DictEntry;
+use crate::dictionary::CskkDictionary;
+use std::sync::Arc;
+
+/// 辞書のエントリを列挙する順序
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictionaryEntryOrder {
+    /// 最近使われた順。ユーザー辞書の保存順と同じ。
+    Lru,
+    /// 見出しの辞書順
+    Sorted,
+}
+
+/// 辞書編集用に外部に見せる候補一つ分のデータ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictionaryCandidateData {
+    pub kouho_text: String,
+    pub annotation: Option<String>,
+    /// 厳密な送り仮名の候補の場合、その送り仮名。
+    pub strict_okuri: Option<String>,
+}
+
+/// 辞書編集用に外部に見せるエントリ一つ分のデータ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictionaryEntryData {
+    /// 辞書上の見出し。送りありエントリは"かんがe"のように送り仮名のローマ字を末尾に持つ。
+    pub midashi: String,
+    pub is_okuri_ari: bool,
+    /// 厳密な送り仮名のない候補、厳密な送り仮名の候補の順。
+    pub candidates: Vec<DictionaryCandidateData>,
+}
+
+impl DictionaryEntryData {
+    pub(in crate::dictionary) fn from_dict_entry(
+        dict_entry: &DictEntry,
+        is_okuri_ari: bool,
+    ) -> Self {
+        let candidates = dict_entry
+            .iter_candidates_with_strict_okuri()
+            .map(|(strict_okuri, cand)| DictionaryCandidateData {
+                kouho_text: cand.kouho_text.to_owned(),
+                annotation: cand.annotation.to_owned(),
+                strict_okuri: if strict_okuri.is_empty() {
+                    None
+                } else {
+                    Some(strict_okuri.to_owned())
+                },
+            })
+            .collect();
+
+        Self {
+            midashi: dict_entry.midashi.to_owned(),
+            is_okuri_ari,
+            candidates,
+        }
+    }
+}
+
+/// 逆引きで得た読み一つ分のデータ
+#[derive(Debug, Clone)]
+pub struct DictionaryReading {
+    /// 辞書上の見出し。送りありエントリは"かんがe"のように送り仮名のローマ字を末尾に持つ。
+    pub midashi: String,
+    /// 送り仮名のローマ字を除いた読み。"かんがe"ならば"かんが"
+    pub reading: String,
+    pub is_okuri_ari: bool,
+    /// 厳密な送り仮名の候補として見つかった場合、その送り仮名。
+    pub strict_okuri: Option<String>,
+    pub annotation: Option<String>,
+    /// 検索した辞書の並びの中での、見つかった辞書の位置
+    pub dictionary_index: usize,
+    /// 見つかった辞書
+    pub dictionary: Arc<CskkDictionary>,
+}
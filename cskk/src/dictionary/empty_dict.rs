@@ -1,5 +1,5 @@
 use crate::dictionary::dictentry::DictEntry;
-use crate::dictionary::{CompositeKey, Dictionary};
+use crate::dictionary::{CompositeKey, Dictionary, DictionaryEntryOrder};
 
 ///
 /// Empty dictionary
@@ -19,4 +19,16 @@ impl Dictionary for EmptyDictionary {
         let a = std::iter::empty();
         Box::new(a)
     }
+
+    fn iter_entries<'a>(
+        &'a self,
+        _order: DictionaryEntryOrder,
+        _is_okuri_ari: bool,
+    ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
+        Box::new(std::iter::empty())
+    }
+
+    fn reverse_lookup(&self, _kouho_text: &str) -> Vec<(bool, &DictEntry)> {
+        vec![]
+    }
 }
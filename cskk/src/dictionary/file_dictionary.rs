@@ -1,12 +1,21 @@
 use crate::dictionary::dictentry::DictEntry;
-use crate::dictionary::{CompositeKey, Dictionary};
+use crate::dictionary::{CompositeKey, Dictionary, DictionaryEntryOrder};
 use crate::error::CskkError;
-use encoding_rs::Encoding;
+use crate::error::CskkError::Error;
+use encoding_rs::{Encoder, EncoderResult, Encoding};
 use encoding_rs_io::DecodeReaderBytesBuilder;
 use log::warn;
 use lru_ordered_map::LruOrderedMap;
+use std::cmp::Reverse;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::Bound;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const BUF_SIZE: usize = 1024;
 
 pub(in crate::dictionary) struct DictionaryEntries {
     pub(in crate::dictionary) okuri_ari: LruOrderedMap<String, DictEntry>,
@@ -20,6 +29,23 @@ pub(in crate::dictionary) trait FileDictionary: Dictionary {
 
     fn set_dictionary(&mut self, dictionary: DictionaryEntries);
 
+    /// 別スレッドで読み込み中であれば、その読み込み
+    fn background_load(&self) -> Option<&BackgroundLoad>;
+
+    /// 別スレッドでの読み込みが終わっていれば、読み込んだエントリに入れ替える。waitがtrueなら読み込みが終わるまで待つ。
+    fn absorb_loaded_entries(&mut self, wait: bool);
+
+    /// 読み込みが終わっていればtrue
+    fn is_ready(&self) -> bool {
+        self.background_load().is_none()
+    }
+
+    /// 読み込んだ割合。0.0から1.0
+    fn load_progress(&self) -> f64 {
+        self.background_load()
+            .map_or(1.0, |background_load| background_load.progress())
+    }
+
     fn reload(&mut self) -> Result<(), CskkError> {
         let dictionary = load_dictionary(self.file_path(), self.encode().as_bytes())?;
         self.set_dictionary(dictionary);
@@ -35,45 +61,102 @@ pub(in crate::dictionary) trait FileDictionary: Dictionary {
         &'a self,
         midashi_head: &'a CompositeKey,
     ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
-        // iter_sortedから先頭一致する部分を返す。
-        // starts_withが真となる要素はsortされていれば並んでいる。
-        //let dict_key_head = midashi_head.get_dict_key();
-        if midashi_head.has_okuri() {
-            Box::new(
-                self.get_okuri_ari_dictionary()
-                    .iter_sorted()
-                    .skip_while(move |(midashi, _entry)| {
-                        midashi.is_some()
-                            && !(*midashi)
-                                .unwrap()
-                                .starts_with(&midashi_head.get_dict_key())
-                    })
-                    .take_while(move |(midashi, _entry)| {
-                        midashi.is_some()
-                            && (*midashi)
-                                .unwrap()
-                                .starts_with(&midashi_head.get_dict_key())
-                    })
-                    .filter_map(|(_k, v)| v),
-            )
+        let dictionary = if midashi_head.has_okuri() {
+            self.get_okuri_ari_dictionary()
+        } else {
+            self.get_okuri_nashi_dictionary()
+        };
+        sorted_entries_with_prefix(dictionary, midashi_head.get_dict_key())
+    }
+
+    /// [complete]と同様に先頭一致するエントリを、使用回数の多い順に、同じ回数なら最近使用した順に返す。
+    ///
+    /// 先頭一致するエントリを見出しの順に全て集めてから並べ替えるので、一致するエントリが多いと[complete]より遅い。
+    fn complete_by_usage<'a>(
+        &'a self,
+        midashi_head: &'a CompositeKey,
+    ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
+        let is_okuri_ari = midashi_head.has_okuri();
+        let entries = FileDictionary::complete(self, midashi_head);
+        self.order_by_usage(is_okuri_ari, entries)
+    }
+
+    /// 送り仮名を除いた読みがreadingから始まる送りありエントリを返す。
+    /// by_usageがtrueなら[complete_by_usage]と同じく使用回数と最近使用した順に、falseなら見出しの順に返す。
+    ///
+    /// e.g.
+    /// complete_okuri_ari("かんが", false) -> (("かんがe", "/考/"), ("かんがえなおs", "/考え直/"), ...)
+    fn complete_okuri_ari<'a>(
+        &'a self,
+        reading: &str,
+        by_usage: bool,
+    ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
+        let entries =
+            sorted_entries_with_prefix(self.get_okuri_ari_dictionary(), reading.to_string());
+        if by_usage {
+            self.order_by_usage(true, entries)
         } else {
-            Box::new(
-                self.get_okuri_nashi_dictionary()
-                    .iter_sorted()
-                    .skip_while(move |(midashi, _entry)| {
-                        midashi.is_some()
-                            && !(*midashi)
-                                .unwrap()
-                                .starts_with(&midashi_head.get_dict_key())
-                    })
-                    .take_while(move |(midashi, _entry)| {
-                        midashi.is_some()
-                            && (*midashi)
-                                .unwrap()
-                                .starts_with(&midashi_head.get_dict_key())
-                    })
-                    .filter_map(|(_k, v)| v),
-            )
+            entries
+        }
+    }
+
+    /// 見出しのエントリから候補を確定した回数。辞書を作ってからの回数で、辞書ファイルには保存しない。
+    /// 学習しない辞書では常に0
+    fn use_count(&self, _is_okuri_ari: bool, _midashi: &str) -> u32 {
+        0
+    }
+
+    /// entriesを使用回数の多い順に、同じ回数なら最近使用した順に並べる。
+    fn order_by_usage<'a>(
+        &'a self,
+        is_okuri_ari: bool,
+        entries: Box<dyn Iterator<Item = &'a DictEntry> + 'a>,
+    ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
+        // LRU順のリストは見出しで探せないので、先頭一致するエントリだけを集めてから並べる。
+        let dictionary = if is_okuri_ari {
+            self.get_okuri_ari_dictionary()
+        } else {
+            self.get_okuri_nashi_dictionary()
+        };
+        let mut entries = entries.collect::<Vec<_>>();
+        entries.sort_by_key(|entry| {
+            Reverse((
+                self.use_count(is_okuri_ari, &entry.midashi),
+                dictionary.recency(&entry.midashi),
+            ))
+        });
+        Box::new(entries.into_iter())
+    }
+
+    /// kouho_textを候補に持つエントリを線形探索し、送りありエントリかどうかと共に返す。
+    fn reverse_lookup(&self, kouho_text: &str) -> Vec<(bool, &DictEntry)> {
+        let mut result = vec![];
+        for is_okuri_ari in [true, false] {
+            result.extend(
+                FileDictionary::iter_entries(self, DictionaryEntryOrder::Sorted, is_okuri_ari)
+                    .filter(|dict_entry| dict_entry.has_candidate(kouho_text))
+                    .map(|dict_entry| (is_okuri_ari, dict_entry)),
+            );
+        }
+        result
+    }
+
+    /// is_okuri_ariに合わせて送りありエントリか送りなしエントリの全エントリを返す。
+    fn iter_entries<'a>(
+        &'a self,
+        order: DictionaryEntryOrder,
+        is_okuri_ari: bool,
+    ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
+        let dictionary = if is_okuri_ari {
+            self.get_okuri_ari_dictionary()
+        } else {
+            self.get_okuri_nashi_dictionary()
+        };
+        match order {
+            DictionaryEntryOrder::Lru => Box::new(dictionary.iter_lru().filter_map(|(_k, v)| v)),
+            DictionaryEntryOrder::Sorted => {
+                Box::new(dictionary.iter_sorted().filter_map(|(_k, v)| v))
+            }
         }
     }
 }
@@ -83,19 +166,58 @@ enum DictionaryLoadMode {
     OkuriNashi,
 }
 
+/// dictionaryからprefixで始まる見出しのエントリを見出しの順に返す。
+fn sorted_entries_with_prefix(
+    dictionary: &LruOrderedMap<String, DictEntry>,
+    prefix: String,
+) -> Box<dyn Iterator<Item = &DictEntry> + '_> {
+    // 先頭一致する要素はsortされていれば並んでいるので、二分探索で先頭を求めてから一致する間だけ返す。
+    let start = prefix.clone();
+    Box::new(
+        dictionary
+            .range_sorted::<str, _>((Bound::Included(start.as_str()), Bound::Unbounded))
+            .take_while(move |(midashi, _entry)| {
+                midashi.is_some_and(|midashi| midashi.starts_with(&prefix))
+            })
+            .filter_map(|(_k, v)| v),
+    )
+}
+
 /// 順序付きで辞書を読む。
 pub(in crate::dictionary) fn load_dictionary(
     file_path: &str,
     encode: &[u8],
 ) -> Result<DictionaryEntries, CskkError> {
     let dict_file = File::open(file_path)?;
+    let mut okuri_ari_dictionary = LruOrderedMap::new();
+    let mut okuri_nashi_dictionary = LruOrderedMap::new();
+    // ユーザー辞書は最近使用した順に保存されているので、先に読んだエントリほど最近使用したものにする。
+    read_dictionary_entries(dict_file, encode, |is_okuri_ari, dict_entry| {
+        if is_okuri_ari {
+            okuri_ari_dictionary.push_least_recent(dict_entry.midashi.clone(), dict_entry);
+        } else {
+            okuri_nashi_dictionary.push_least_recent(dict_entry.midashi.clone(), dict_entry);
+        }
+        true
+    });
+    Ok(DictionaryEntries {
+        okuri_nashi: okuri_nashi_dictionary,
+        okuri_ari: okuri_ari_dictionary,
+    })
+}
+
+/// 辞書を行順に読み、エントリごとに送りありエントリかどうかと共にon_entryを呼ぶ。
+/// on_entryがfalseを返したら読むのをやめる。
+fn read_dictionary_entries<R, F>(dict_file: R, encode: &[u8], mut on_entry: F)
+where
+    R: Read,
+    F: FnMut(bool, DictEntry) -> bool,
+{
     let enc = Encoding::for_label_no_replacement(encode);
     let decoder = DecodeReaderBytesBuilder::new()
         .encoding(enc)
         .build(dict_file);
     let reader = BufReader::new(decoder);
-    let mut okuri_ari_dictionary = LruOrderedMap::new();
-    let mut okuri_nashi_dictionary = LruOrderedMap::new();
 
     // 後の送り仮名再確認の時にabbrevエントリを読み間違えないため、デフォルトはOkuriAri
     let mut mode = DictionaryLoadMode::OkuriAri;
@@ -111,19 +233,16 @@ pub(in crate::dictionary) fn load_dictionary(
                 } else {
                     let parsed = DictEntry::from_skkjisyo_line(&line);
                     match parsed {
-                        Ok(parsed) => match mode {
-                            DictionaryLoadMode::OkuriAri => {
+                        Ok(parsed) => {
+                            let is_okuri_ari = match mode {
                                 // 過去の辞書でokuri-ari,nasiを無視して保存していた互換性のため、行をparseした内容で確認しなおす。
-                                if parsed.is_okuri_ari_entry() {
-                                    okuri_ari_dictionary.push(parsed.midashi.clone(), parsed);
-                                } else {
-                                    okuri_nashi_dictionary.push(parsed.midashi.clone(), parsed);
-                                }
+                                DictionaryLoadMode::OkuriAri => parsed.is_okuri_ari_entry(),
+                                DictionaryLoadMode::OkuriNashi => false,
+                            };
+                            if !on_entry(is_okuri_ari, parsed) {
+                                return;
                             }
-                            DictionaryLoadMode::OkuriNashi => {
-                                okuri_nashi_dictionary.push(parsed.midashi.clone(), parsed);
-                            }
-                        },
+                        }
                         Err(_) => {
                             warn!("Dict is ill formatted. Ignored line {}", &line);
                         }
@@ -135,8 +254,240 @@ pub(in crate::dictionary) fn load_dictionary(
             }
         }
     }
-    Ok(DictionaryEntries {
-        okuri_nashi: okuri_nashi_dictionary,
-        okuri_ari: okuri_ari_dictionary,
-    })
+}
+
+/// 辞書の読み込みが終わった時に読み込みスレッドから呼ばれる。
+pub type DictionaryLoadCallback = Box<dyn FnOnce() + Send + 'static>;
+
+///
+/// 別スレッドで読み込み中の辞書。
+///
+/// 読み込みスレッドはparseしたエントリを順に送り、辞書側はロックを取った時に受け取り済みのエントリを取り込む。
+/// 読み込み中も検索できるよう、辞書の構築自体は辞書を持つ側で少しずつ行う。
+///
+#[derive(Debug)]
+pub(in crate::dictionary) struct BackgroundLoad {
+    // 辞書をスレッド間で共有できるよう、Mutexで包む
+    receiver: Mutex<Receiver<DictionaryEntries>>,
+    progress: Arc<LoadProgress>,
+}
+
+#[derive(Debug)]
+struct LoadProgress {
+    read_bytes: AtomicU64,
+    total_bytes: u64,
+    // 受け取る辞書がなくなったらtrue
+    cancelled: AtomicBool,
+}
+
+/// 読んだバイト数を数えるReader
+struct ProgressReader<R: Read> {
+    inner: R,
+    progress: Arc<LoadProgress>,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress
+            .read_bytes
+            .fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl BackgroundLoad {
+    /// ファイルを開き、読み込みスレッドを開始する。
+    /// ファイルを開けない場合はその場でエラーを返す。
+    pub(in crate::dictionary) fn start(
+        file_path: &str,
+        encode: &str,
+        on_complete: Option<DictionaryLoadCallback>,
+    ) -> Result<Self, CskkError> {
+        let dict_file = File::open(file_path)?;
+        let progress = Arc::new(LoadProgress {
+            read_bytes: AtomicU64::new(0),
+            total_bytes: dict_file.metadata()?.len(),
+            cancelled: AtomicBool::new(false),
+        });
+        let reader = ProgressReader {
+            inner: dict_file,
+            progress: progress.clone(),
+        };
+        let encode = encode.as_bytes().to_vec();
+        let (sender, receiver) = channel();
+        thread::Builder::new()
+            .name("cskk-dictionary-loader".to_string())
+            .spawn(move || {
+                // 検索中の辞書を止めないよう、辞書の構築までこのスレッドで行ってから渡す。
+                let mut okuri_ari_dictionary = LruOrderedMap::new();
+                let mut okuri_nashi_dictionary = LruOrderedMap::new();
+                let progress = reader.progress.clone();
+                read_dictionary_entries(reader, &encode, |is_okuri_ari, dict_entry| {
+                    let dictionary = if is_okuri_ari {
+                        &mut okuri_ari_dictionary
+                    } else {
+                        &mut okuri_nashi_dictionary
+                    };
+                    dictionary.push_least_recent(dict_entry.midashi.clone(), dict_entry);
+                    // 受け取る辞書がなくなっていたら読むのをやめる。
+                    !progress.cancelled.load(Ordering::Relaxed)
+                });
+                let loaded = DictionaryEntries {
+                    okuri_ari: okuri_ari_dictionary,
+                    okuri_nashi: okuri_nashi_dictionary,
+                };
+                if sender.send(loaded).is_ok() {
+                    if let Some(on_complete) = on_complete {
+                        on_complete();
+                    }
+                }
+            })?;
+        Ok(BackgroundLoad {
+            receiver: Mutex::new(receiver),
+            progress,
+        })
+    }
+
+    ///
+    /// 読み込みが終わっていれば、辞書を読み込んだエントリに入れ替える。waitがtrueなら読み込みが終わるまで待つ。
+    /// 読み込み中に辞書に入ったエントリは、読み込んだエントリより最近使用したものとして残す。
+    /// 同じ見出しのエントリを読み込んでいた場合、そのエントリにない候補を末尾に追加する。
+    ///
+    /// 読み込みが終わり入れ替えたらtrueを返す。
+    ///
+    pub(in crate::dictionary) fn absorb(
+        &self,
+        okuri_ari_dictionary: &mut LruOrderedMap<String, DictEntry>,
+        okuri_nashi_dictionary: &mut LruOrderedMap<String, DictEntry>,
+        wait: bool,
+    ) -> bool {
+        let receiver = self.receiver.lock().unwrap();
+        let received = if wait {
+            receiver.recv().map_err(|_| TryRecvError::Disconnected)
+        } else {
+            receiver.try_recv()
+        };
+        match received {
+            Ok(loaded) => {
+                replace_with_loaded(okuri_ari_dictionary, loaded.okuri_ari);
+                replace_with_loaded(okuri_nashi_dictionary, loaded.okuri_nashi);
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => true,
+        }
+    }
+
+    /// 読み込んだファイルの割合。0.0から1.0
+    pub(in crate::dictionary) fn progress(&self) -> f64 {
+        if self.progress.total_bytes == 0 {
+            return 1.0;
+        }
+        let read_bytes = self.progress.read_bytes.load(Ordering::Relaxed);
+        (read_bytes as f64 / self.progress.total_bytes as f64).min(1.0)
+    }
+}
+
+impl Drop for BackgroundLoad {
+    fn drop(&mut self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// dictionaryのエントリを古いものから順にloadedへ最近使用したものとして移し、dictionaryをloadedに入れ替える。
+fn replace_with_loaded(
+    dictionary: &mut LruOrderedMap<String, DictEntry>,
+    mut loaded: LruOrderedMap<String, DictEntry>,
+) {
+    let midashi_list = dictionary
+        .iter_lru()
+        .rev()
+        .filter_map(|(midashi, _entry)| midashi.map(|midashi| midashi.to_string()))
+        .collect::<Vec<_>>();
+    for midashi in midashi_list {
+        if let Some(mut dict_entry) = dictionary.remove(midashi.clone()) {
+            if let Some(loaded_entry) = loaded.remove(midashi.clone()) {
+                dict_entry.merge(loaded_entry);
+            }
+            loaded.push(midashi, dict_entry);
+        }
+    }
+    *dictionary = loaded;
+}
+
+/// 辞書ファイルとしてfile_pathに書き出す。
+/// 辞書ファイルのフォーマットは SKK 16.2 user manual 5.10.7 辞書の書式 に依る
+/// エンコードできないエントリは書き出さない。
+pub(in crate::dictionary) fn write_dictionary<'a>(
+    file_path: &str,
+    encode: &str,
+    okuri_ari_entries: impl Iterator<Item = &'a DictEntry>,
+    okuri_nashi_entries: impl Iterator<Item = &'a DictEntry>,
+) -> Result<(), CskkError> {
+    let mut enc = Encoding::for_label(encode.as_bytes())
+        .ok_or_else(|| Error(format!("Unknown encoding: {encode}")))?
+        .new_encoder();
+    let dict_file = File::create(file_path)?;
+    let mut stream = BufWriter::new(dict_file);
+
+    // Not using. Can't compile on mac.
+    // let encoded = encode_string(
+    //     &mut enc,
+    //     &format!(
+    //         ";; Save on {} \n",
+    //         chrono::offset::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+    //     ),
+    // )?;
+    // stream.write_all(encoded.as_slice())?;
+
+    let encoded = encode_string(&mut enc, ";; okuri-ari entries.\n")?;
+    stream.write_all(encoded.as_slice())?;
+    for dict_entry in okuri_ari_entries {
+        let mut dict_entry_string = dict_entry.to_string();
+        dict_entry_string += "\n";
+        if let Ok(encoded) = encode_string(&mut enc, dict_entry_string.as_mut_str()) {
+            stream.write_all(encoded.as_slice())?;
+        }
+    }
+    let encoded = encode_string(&mut enc, ";; okuri-nasi entries.\n")?;
+    stream.write_all(encoded.as_slice())?;
+    for dict_entry in okuri_nashi_entries {
+        let mut dict_entry_string = dict_entry.to_string();
+        dict_entry_string += "\n";
+        if let Ok(encoded) = encode_string(&mut enc, dict_entry_string.as_mut_str()) {
+            stream.write_all(encoded.as_slice())?;
+        }
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+fn encode_string(encoder: &mut Encoder, to_encode: &str) -> Result<Vec<u8>, CskkError> {
+    let mut encoded_vec = Vec::with_capacity(BUF_SIZE);
+    let mut source = to_encode;
+    let mut tmp_buf = Vec::with_capacity(BUF_SIZE);
+    loop {
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(source, &mut tmp_buf, true);
+        if read == 0 {
+            return Err(Error(
+                "Cannot read on encoding. Give up whole string.".to_string(),
+            ));
+        }
+        match result {
+            EncoderResult::Unmappable(_char) => {
+                return Err(Error("Encoding failed. Give up whole string.".to_string()));
+            }
+            EncoderResult::InputEmpty => {
+                encoded_vec.append(&mut tmp_buf);
+                break;
+            }
+            EncoderResult::OutputFull => {
+                encoded_vec.append(&mut tmp_buf);
+                source = &source[read..];
+            }
+        }
+    }
+    Ok(encoded_vec)
 }
@@ -0,0 +1,355 @@
+use crate::dictionary::{get_all_candidates, Candidate, CompositeKey, CskkDictionary};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// 打ち間違いとしてよくある編集のコスト
+pub(crate) const FUZZY_CHEAP_EDIT_COST: usize = 1;
+/// それ以外の一文字の編集のコスト
+pub(crate) const FUZZY_EDIT_COST: usize = 2;
+/// 読みの候補を総当たりで作るため、これより大きなコストは許さない。
+pub(crate) const FUZZY_MAX_COST_LIMIT: usize = 2;
+/// 候補を探す訂正した読みの数の上限
+const FUZZY_MAX_READINGS: usize = 5;
+
+/// 挿入と削除を打ち間違いとして安く扱うかな。促音、長音、小書き、母音、撥音。
+const CHEAP_INDEL_KANA: &str = "っーぁぃぅぇぉゃゅょゎあいうえおん";
+
+/// 訂正後の読みとして挿入や置換に使うかな
+const ALL_KANA: &str = "ぁあぃいぅうぇえぉおかがきぎくぐけげこごさざしじすずせぜそぞただちぢっつづてでとどなにぬねのはばぱひびぴふぶぷへべぺほぼぽまみむめもゃやゅゆょよらりるれろゎわをんゔー";
+
+/// 清音と濁音・半濁音、小書きと並字のように、互いに置換しても打ち間違いとして安く扱うかなの組。
+const SIMILAR_KANA: [&str; 27] = [
+    "かが",
+    "きぎ",
+    "くぐ",
+    "けげ",
+    "こご",
+    "さざ",
+    "しじ",
+    "すず",
+    "せぜ",
+    "そぞ",
+    "ただ",
+    "ちぢ",
+    "つづっ",
+    "てで",
+    "とど",
+    "はばぱ",
+    "ひびぴ",
+    "ふぶぷ",
+    "へべぺ",
+    "ほぼぽ",
+    "あぁ",
+    "いぃ",
+    "うぅゔ",
+    "えぇ",
+    "おぉ",
+    "やゃゆゅよょ",
+    "わゎ",
+];
+
+/// かなとローマ字の対応。ローマ字で隣のキーを押してしまった時の打ち間違いを推定するのに用いる。
+const KANA_ROMAJI: [(char, &str); 70] = [
+    ('あ', "a"),
+    ('い', "i"),
+    ('う', "u"),
+    ('え', "e"),
+    ('お', "o"),
+    ('か', "ka"),
+    ('き', "ki"),
+    ('く', "ku"),
+    ('け', "ke"),
+    ('こ', "ko"),
+    ('さ', "sa"),
+    ('し', "si"),
+    ('す', "su"),
+    ('せ', "se"),
+    ('そ', "so"),
+    ('た', "ta"),
+    ('ち', "ti"),
+    ('つ', "tu"),
+    ('て', "te"),
+    ('と', "to"),
+    ('な', "na"),
+    ('に', "ni"),
+    ('ぬ', "nu"),
+    ('ね', "ne"),
+    ('の', "no"),
+    ('は', "ha"),
+    ('ひ', "hi"),
+    ('ふ', "hu"),
+    ('へ', "he"),
+    ('ほ', "ho"),
+    ('ま', "ma"),
+    ('み', "mi"),
+    ('む', "mu"),
+    ('め', "me"),
+    ('も', "mo"),
+    ('や', "ya"),
+    ('ゆ', "yu"),
+    ('よ', "yo"),
+    ('ら', "ra"),
+    ('り', "ri"),
+    ('る', "ru"),
+    ('れ', "re"),
+    ('ろ', "ro"),
+    ('わ', "wa"),
+    ('を', "wo"),
+    ('が', "ga"),
+    ('ぎ', "gi"),
+    ('ぐ', "gu"),
+    ('げ', "ge"),
+    ('ご', "go"),
+    ('ざ', "za"),
+    ('じ', "zi"),
+    ('ず', "zu"),
+    ('ぜ', "ze"),
+    ('ぞ', "zo"),
+    ('だ', "da"),
+    ('ぢ', "di"),
+    ('づ', "du"),
+    ('で', "de"),
+    ('ど', "do"),
+    ('ば', "ba"),
+    ('び', "bi"),
+    ('ぶ', "bu"),
+    ('べ', "be"),
+    ('ぼ', "bo"),
+    ('ぱ', "pa"),
+    ('ぴ', "pi"),
+    ('ぷ', "pu"),
+    ('ぺ', "pe"),
+    ('ぽ', "po"),
+];
+
+/// QWERTY配列のキーの行。同じ行で隣り合うキーを押し間違えやすいものとする。
+const KEYBOARD_ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+lazy_static! {
+    /// かなごとに、安く置換できるかなの一覧
+    static ref CHEAP_SUBSTITUTIONS: HashMap<char, Vec<char>> = cheap_substitutions();
+}
+
+fn is_adjacent_key(a: char, b: char) -> bool {
+    KEYBOARD_ROWS.iter().any(|row| {
+        let row = row.as_bytes();
+        row.windows(2).any(|pair| {
+            (pair[0] as char == a && pair[1] as char == b)
+                || (pair[0] as char == b && pair[1] as char == a)
+        })
+    })
+}
+
+/// ローマ字で一文字だけ隣のキーを押し間違えたかな同士ならtrue
+fn is_adjacent_key_typo(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut differences = a.chars().zip(b.chars()).filter(|(x, y)| x != y);
+    match (differences.next(), differences.next()) {
+        (Some((x, y)), None) => is_adjacent_key(x, y),
+        _ => false,
+    }
+}
+
+fn cheap_substitutions() -> HashMap<char, Vec<char>> {
+    let mut result: HashMap<char, Vec<char>> = HashMap::new();
+    for similar in SIMILAR_KANA {
+        for a in similar.chars() {
+            for b in similar.chars().filter(|b| *b != a) {
+                result.entry(a).or_default().push(b);
+            }
+        }
+    }
+    for (a, a_romaji) in KANA_ROMAJI {
+        for (b, b_romaji) in KANA_ROMAJI {
+            if a != b && is_adjacent_key_typo(a_romaji, b_romaji) {
+                result.entry(a).or_default().push(b);
+            }
+        }
+    }
+    for substitutes in result.values_mut() {
+        substitutes.sort_unstable();
+        substitutes.dedup();
+    }
+    result
+}
+
+fn indel_cost(kana: char) -> usize {
+    if CHEAP_INDEL_KANA.contains(kana) {
+        FUZZY_CHEAP_EDIT_COST
+    } else {
+        FUZZY_EDIT_COST
+    }
+}
+
+fn substitution_cost(from: char, to: char) -> usize {
+    if CHEAP_SUBSTITUTIONS
+        .get(&from)
+        .is_some_and(|substitutes| substitutes.contains(&to))
+    {
+        FUZZY_CHEAP_EDIT_COST
+    } else {
+        FUZZY_EDIT_COST
+    }
+}
+
+///
+/// readingから一文字ずつの挿入・削除・置換のコストの合計がmax_cost以内の読みを、コストの小さい順に返す。readingそのものは含まない。
+///
+/// 辞書を走査せず読みを作って引くため、辞書の大きさによらず一定の時間で済む。
+/// 作る読みの数が増えすぎないよう、max_costは[FUZZY_MAX_COST_LIMIT]までに制限する。
+///
+pub(crate) fn corrected_readings(reading: &str, max_cost: usize) -> Vec<(String, usize)> {
+    let max_cost = max_cost.min(FUZZY_MAX_COST_LIMIT);
+    let mut costs: HashMap<String, usize> = HashMap::new();
+    costs.insert(reading.to_string(), 0);
+    let mut frontier = vec![(reading.chars().collect::<Vec<char>>(), 0)];
+
+    while !frontier.is_empty() {
+        let mut next_frontier = vec![];
+        for (chars, cost) in frontier {
+            let remaining = max_cost - cost;
+            let mut add = |edited: Vec<char>, edit_cost: usize| {
+                if edit_cost > remaining || edited.is_empty() {
+                    return;
+                }
+                let new_cost = cost + edit_cost;
+                let key = edited.iter().collect::<String>();
+                match costs.get(&key) {
+                    Some(&known_cost) if known_cost <= new_cost => {}
+                    _ => {
+                        costs.insert(key, new_cost);
+                        if new_cost < max_cost {
+                            next_frontier.push((edited, new_cost));
+                        }
+                    }
+                }
+            };
+            // 安い編集しかできない場合、全てのかなを試さない。
+            let insertable = if remaining >= FUZZY_EDIT_COST {
+                ALL_KANA
+            } else {
+                CHEAP_INDEL_KANA
+            };
+
+            for i in 0..chars.len() {
+                let mut deleted = chars.clone();
+                deleted.remove(i);
+                add(deleted, indel_cost(chars[i]));
+
+                if remaining >= FUZZY_EDIT_COST {
+                    for substitute in ALL_KANA.chars().filter(|c| *c != chars[i]) {
+                        let mut substituted = chars.clone();
+                        substituted[i] = substitute;
+                        add(substituted, substitution_cost(chars[i], substitute));
+                    }
+                } else if let Some(substitutes) = CHEAP_SUBSTITUTIONS.get(&chars[i]) {
+                    for substitute in substitutes {
+                        let mut substituted = chars.clone();
+                        substituted[i] = *substitute;
+                        add(substituted, FUZZY_CHEAP_EDIT_COST);
+                    }
+                }
+            }
+            for i in 0..=chars.len() {
+                for inserted_kana in insertable.chars() {
+                    let mut inserted = chars.clone();
+                    inserted.insert(i, inserted_kana);
+                    add(inserted, indel_cost(inserted_kana));
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+
+    costs.remove(reading);
+    let mut result = costs.into_iter().collect::<Vec<_>>();
+    result.sort_unstable_by(|(a, a_cost), (b, b_cost)| a_cost.cmp(b_cost).then(a.cmp(b)));
+    result
+}
+
+///
+/// composite_keyの読みに打ち間違いがあったものとして、訂正した読みで辞書を引いた候補を返す。
+/// 候補は訂正した読みを[Candidate::corrected_reading]に持つ。
+///
+/// 送り仮名は訂正せず、読みの部分のみを訂正する。
+///
+pub(crate) fn get_fuzzy_candidates(
+    dictionaries: &[Arc<CskkDictionary>],
+    composite_key: &CompositeKey,
+    max_cost: usize,
+) -> Vec<Candidate> {
+    if max_cost == 0 || composite_key.get_to_composite().is_empty() {
+        return vec![];
+    }
+    let mut result: Vec<Candidate> = vec![];
+    let mut found_outputs = HashSet::new();
+    let corrected_keys = corrected_readings(composite_key.get_to_composite(), max_cost)
+        .into_iter()
+        .map(|(reading, _cost)| CompositeKey::new(&reading, composite_key.get_okuri().to_owned()))
+        .filter(|corrected_key| has_entry(dictionaries, corrected_key))
+        .take(FUZZY_MAX_READINGS);
+
+    for corrected_key in corrected_keys {
+        for mut candidate in get_all_candidates(dictionaries, &corrected_key) {
+            if found_outputs.insert(candidate.output.to_owned()) {
+                candidate.corrected_reading = Some(corrected_key.get_to_composite().to_string());
+                result.push(candidate);
+            }
+        }
+    }
+    result
+}
+
+fn has_entry(dictionaries: &[Arc<CskkDictionary>], composite_key: &CompositeKey) -> bool {
+    use crate::dictionary::{CskkDictionaryType, Dictionary};
+    dictionaries.iter().any(|cskkdict| match &*cskkdict.read() {
+        CskkDictionaryType::StaticFile(dict) => dict.lookup(composite_key).is_some(),
+        CskkDictionaryType::UserFile(dict) => dict.lookup(composite_key).is_some(),
+        CskkDictionaryType::EmptyDict(dict) => dict.lookup(composite_key).is_some(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn corrected_readings_cheap_edits() {
+        let readings = corrected_readings("しゅおるい", 2);
+        assert!(readings.contains(&("しょるい".to_string(), 2)));
+        assert!(readings.contains(&("しゅるい".to_string(), 1)));
+        assert!(!readings.iter().any(|(reading, _)| reading == "しゅおるい"));
+    }
+
+    #[test]
+    fn corrected_readings_sorted_by_cost() {
+        let readings = corrected_readings("がっこ", 2);
+        assert_eq!(1, readings[0].1);
+        assert!(readings.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        // 長音の脱落
+        assert!(readings.contains(&("がっこう".to_string(), 1)));
+        // 濁点の付け忘れ
+        assert!(corrected_readings("かっこう", 1).contains(&("がっこう".to_string(), 1)));
+    }
+
+    #[test]
+    fn corrected_readings_adjacent_key() {
+        // uとiは隣のキー
+        assert!(corrected_readings("かんい", 1).contains(&("かんう".to_string(), 1)));
+        // sとdは隣のキー
+        assert!(corrected_readings("さいがく", 1).contains(&("だいがく".to_string(), 1)));
+        // 隣でないキーは高い
+        assert!(!corrected_readings("かんじ", 1).contains(&("まんじ".to_string(), 1)));
+    }
+
+    #[test]
+    fn corrected_readings_limited() {
+        assert_eq!(
+            corrected_readings("あい", FUZZY_MAX_COST_LIMIT),
+            corrected_readings("あい", FUZZY_MAX_COST_LIMIT + 5)
+        );
+        assert!(corrected_readings("あい", 0).is_empty());
+    }
+}
@@ -0,0 +1,878 @@
+//!
+//! SKK-JISYO形式の辞書と他のIMEのユーザー辞書形式との相互変換
+//!
+//! 対応する形式はMozcのTSV、MS-IMEのテキスト形式、Apple(ことえり/日本語入力)のplistとCSV。
+//! 品詞は可能な範囲で送りあり/送りなしエントリに対応させる。
+//!
+//! - 他のIMEからSKK-JISYOへ: 品詞が動詞または形容詞の単語は、読みと単語の末尾のひらがな一文字を送り仮名とみなして送りありエントリにする。
+//!   一段動詞は「考える」を「かんがe /考/[え/考/]」のように、るの前のえ段かい段のひらがなを送り仮名とする。
+//!   それ以外の単語や送り仮名を切り出せない単語は送りなしエントリにする。
+//! - SKK-JISYOから他のIMEへ: 送りなしエントリは名詞として書き出す。
+//!   送りありエントリは厳密な送り仮名のある候補のみ、送り仮名から終止形を復元して動詞か形容詞として書き出す。
+//!   う段といの送り仮名はそのまま、それ以外のえ段かい段の送り仮名はるを付けた一段動詞とする。
+//!   厳密な送り仮名のない候補や終止形を復元できない候補は書き出さず、[ImeExportReport]で知らせる。
+//!   タブ区切りの形式ではタブや改行を含む候補も書き出さずに知らせる。
+//!
+use crate::dictionary::dictentry::DictEntry;
+use crate::dictionary::file_dictionary::{load_dictionary, write_dictionary};
+use crate::dictionary::Candidate;
+use crate::error::CskkError;
+use crate::form_changer::kana_form_changer::KanaFormChanger;
+use encoding_rs::UTF_16LE;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::str::FromStr;
+
+/// 変換に対応するIMEの辞書形式
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImeDictionaryFormat {
+    /// Mozcのユーザー辞書のTSV。UTF-8で"読み\t単語\t品詞\tコメント"
+    MozcTsv,
+    /// MS-IMEのテキスト形式。BOM付きUTF-16LEで"読み\t単語\t品詞"
+    MsImeText,
+    /// Appleのユーザー辞書のplist。phraseとshortcutを持つdictの配列
+    ApplePlist,
+    /// Appleのユーザー辞書のCSV。"読み","単語","品詞"
+    AppleCsv,
+}
+
+impl FromStr for ImeDictionaryFormat {
+    type Err = CskkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mozc" | "mozc-tsv" => Ok(ImeDictionaryFormat::MozcTsv),
+            "msime" | "ms-ime" | "msime-text" => Ok(ImeDictionaryFormat::MsImeText),
+            "apple-plist" | "plist" => Ok(ImeDictionaryFormat::ApplePlist),
+            "apple-csv" | "csv" => Ok(ImeDictionaryFormat::AppleCsv),
+            _ => Err(CskkError::Error(format!("Unknown dictionary format: {s}"))),
+        }
+    }
+}
+
+impl Display for ImeDictionaryFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImeDictionaryFormat::MozcTsv => write!(f, "mozc"),
+            ImeDictionaryFormat::MsImeText => write!(f, "msime"),
+            ImeDictionaryFormat::ApplePlist => write!(f, "apple-plist"),
+            ImeDictionaryFormat::AppleCsv => write!(f, "apple-csv"),
+        }
+    }
+}
+
+/// SKK-JISYOから他のIMEの辞書への変換結果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImeExportReport {
+    /// 実際に書き出した単語の数
+    pub written: usize,
+    /// 終止形を復元できない送りありの候補や、書き出し先の形式で表せない候補
+    pub skipped: Vec<SkippedCandidate>,
+}
+
+/// 書き出さなかった候補
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedCandidate {
+    pub midashi: String,
+    /// 厳密な送り仮名。無い場合は空文字列。
+    pub strict_okuri: String,
+    pub kouho_text: String,
+}
+
+/// 他のIMEの辞書の単語一つ分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImeDictionaryWord {
+    /// 読み。ひらがな。
+    pub reading: String,
+    pub word: String,
+    /// 元の辞書形式での品詞名。品詞を持たない形式では空文字列。
+    pub part_of_speech: String,
+    pub comment: Option<String>,
+}
+
+/// file_pathの他のIMEの辞書を読む。
+pub fn read_ime_dictionary(
+    file_path: &str,
+    format: ImeDictionaryFormat,
+) -> Result<Vec<ImeDictionaryWord>, CskkError> {
+    let mut bytes = vec![];
+    File::open(file_path)?.read_to_end(&mut bytes)?;
+    let content = match format {
+        ImeDictionaryFormat::MsImeText => {
+            // BOMがあればそれに従う
+            let (content, _, _) = UTF_16LE.decode(&bytes);
+            content.into_owned()
+        }
+        _ => {
+            let (content, _, _) = encoding_rs::UTF_8.decode(&bytes);
+            content.into_owned()
+        }
+    };
+    Ok(match format {
+        ImeDictionaryFormat::MozcTsv => parse_tsv(&content, '#'),
+        ImeDictionaryFormat::MsImeText => parse_tsv(&content, '!'),
+        ImeDictionaryFormat::ApplePlist => parse_plist(&content),
+        ImeDictionaryFormat::AppleCsv => parse_csv(&content),
+    })
+}
+
+/// wordsを他のIMEの辞書としてfile_pathに書き出す。
+pub fn write_ime_dictionary(
+    file_path: &str,
+    format: ImeDictionaryFormat,
+    words: &[ImeDictionaryWord],
+) -> Result<(), CskkError> {
+    let content = match format {
+        ImeDictionaryFormat::MozcTsv => format_mozc_tsv(words),
+        ImeDictionaryFormat::MsImeText => format_msime_text(words),
+        ImeDictionaryFormat::ApplePlist => format_plist(words),
+        ImeDictionaryFormat::AppleCsv => format_csv(words),
+    };
+    let mut stream = BufWriter::new(File::create(file_path)?);
+    match format {
+        ImeDictionaryFormat::MsImeText => {
+            // encoding_rsはUTF-16へのエンコードをしないので自前で書く。
+            stream.write_all(&[0xFF, 0xFE])?;
+            for unit in content.encode_utf16() {
+                stream.write_all(&unit.to_le_bytes())?;
+            }
+        }
+        _ => {
+            stream.write_all(content.as_bytes())?;
+        }
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// 他のIMEの辞書をSKK-JISYO形式の辞書に変換する。
+/// 変換した単語の数を返す。読みか単語が空の単語は変換せず、数にも含めない。
+pub fn convert_ime_to_skk_jisyo(
+    ime_file_path: &str,
+    format: ImeDictionaryFormat,
+    skk_file_path: &str,
+    skk_encode: &str,
+) -> Result<usize, CskkError> {
+    let words = read_ime_dictionary(ime_file_path, format)?;
+    let (okuri_ari, okuri_nashi) = dict_entries_from_words(&words);
+    write_dictionary(
+        skk_file_path,
+        skk_encode,
+        okuri_ari.iter(),
+        okuri_nashi.iter(),
+    )?;
+    Ok(words
+        .iter()
+        .filter(|word| !word.reading.is_empty() && !word.word.is_empty())
+        .count())
+}
+
+/// SKK-JISYO形式の辞書を他のIMEの辞書に変換する。
+/// 書き出した単語の数と、書き出さなかった候補を返す。
+pub fn convert_skk_jisyo_to_ime(
+    skk_file_path: &str,
+    skk_encode: &str,
+    ime_file_path: &str,
+    format: ImeDictionaryFormat,
+) -> Result<ImeExportReport, CskkError> {
+    let dictionary = load_dictionary(skk_file_path, skk_encode.as_bytes())?;
+    let mut words = vec![];
+    let mut skipped = vec![];
+    let mut seen = BTreeSet::new();
+    // 辞書ファイル上の順に書き出す
+    let entries = dictionary
+        .okuri_ari
+        .iter_lru()
+        .filter_map(|(_, entry)| entry.map(|entry| (true, entry)))
+        .chain(
+            dictionary
+                .okuri_nashi
+                .iter_lru()
+                .filter_map(|(_, entry)| entry.map(|entry| (false, entry))),
+        );
+    for (is_okuri_ari, dict_entry) in entries {
+        for word in words_from_dict_entry(dict_entry, is_okuri_ari, format, &mut skipped) {
+            if seen.insert((word.reading.to_owned(), word.word.to_owned())) {
+                words.push(word);
+            }
+        }
+    }
+    write_ime_dictionary(ime_file_path, format, &words)?;
+    Ok(ImeExportReport {
+        written: words.len(),
+        skipped,
+    })
+}
+
+/// 単語の並びから送りあり、送りなしのエントリを作る。
+/// 同じ見出しの単語は一つのエントリにまとめ、元の並び順を保つ。
+fn dict_entries_from_words(words: &[ImeDictionaryWord]) -> (Vec<DictEntry>, Vec<DictEntry>) {
+    let mut okuri_ari: Vec<DictEntry> = vec![];
+    let mut okuri_nashi: Vec<DictEntry> = vec![];
+    let mut okuri_ari_index: HashMap<String, usize> = HashMap::new();
+    let mut okuri_nashi_index: HashMap<String, usize> = HashMap::new();
+
+    for word in words {
+        if word.reading.is_empty() || word.word.is_empty() {
+            continue;
+        }
+        let candidate = candidate_from_word(word);
+        let (entries, index) = if candidate.okuri {
+            (&mut okuri_ari, &mut okuri_ari_index)
+        } else {
+            (&mut okuri_nashi, &mut okuri_nashi_index)
+        };
+        let dict_entry = DictEntry::new(&candidate.midashi, &candidate);
+        if let Some(&i) = index.get(&candidate.midashi) {
+            entries[i].merge(dict_entry);
+        } else {
+            index.insert(candidate.midashi.to_owned(), entries.len());
+            entries.push(dict_entry);
+        }
+    }
+    (okuri_ari, okuri_nashi)
+}
+
+/// 単語を辞書の候補にする。
+/// 動詞か形容詞であり送り仮名を切り出せる場合は送りありの候補、それ以外は送りなしの候補になる。
+fn candidate_from_word(word: &ImeDictionaryWord) -> Candidate {
+    if is_okuri_ari_part_of_speech(&word.part_of_speech) {
+        let is_ichidan = word.part_of_speech.contains("一段");
+        if let Some((reading_stem, word_stem, okuri)) =
+            split_okuri(&word.reading, &word.word, is_ichidan)
+        {
+            if let Some(prefix) = KanaFormChanger::kana_to_okuri_prefix(&okuri) {
+                let midashi = format!("{reading_stem}{prefix}");
+                return Candidate::new(
+                    midashi,
+                    Some(okuri.to_string()),
+                    true,
+                    word_stem.to_owned(),
+                    word.comment.to_owned(),
+                    word_stem,
+                );
+            }
+        }
+    }
+    Candidate::new(
+        word.reading.to_owned(),
+        None,
+        false,
+        word.word.to_owned(),
+        word.comment.to_owned(),
+        word.word.to_owned(),
+    )
+}
+
+/// 動詞または形容詞の品詞名ならtrue
+fn is_okuri_ari_part_of_speech(part_of_speech: &str) -> bool {
+    (part_of_speech.contains("動詞") && !part_of_speech.contains("形容動詞"))
+        || part_of_speech.contains("形容詞")
+}
+
+/// 読みと単語の末尾で共通するひらがな一文字を送り仮名として、(読みの語幹, 単語の語幹, 送り仮名)を返す。
+/// is_ichidanの場合、るの前にい以外のえ段かい段の共通するひらがながあればそれを送り仮名とし、るは語幹にも含めない。
+/// 書き出し時にはこの送り仮名から終止形を復元する。
+/// どちらかの語幹が空になる場合はNone
+fn split_okuri(reading: &str, word: &str, is_ichidan: bool) -> Option<(String, String, char)> {
+    let reading_chars = reading.chars().collect::<Vec<_>>();
+    let word_chars = word.chars().collect::<Vec<_>>();
+    let common_len = reading_chars
+        .iter()
+        .rev()
+        .zip(word_chars.iter().rev())
+        .take_while(|(r, w)| r == w && is_hiragana(**r))
+        .count();
+    if common_len == 0 || common_len >= reading_chars.len() || common_len >= word_chars.len() {
+        return None;
+    }
+    let before_ru = reading_chars.len().checked_sub(2).map(|i| reading_chars[i]);
+    let suffix_len = if is_ichidan
+        && common_len >= 2
+        && reading_chars.last() == Some(&'る')
+        && before_ru.is_some_and(|c| c != 'い' && is_i_or_e_row(c))
+    {
+        2
+    } else {
+        1
+    };
+    if suffix_len >= reading_chars.len() || suffix_len >= word_chars.len() {
+        return None;
+    }
+    let reading_stem_len = reading_chars.len() - suffix_len;
+    let word_stem_len = word_chars.len() - suffix_len;
+    Some((
+        reading_chars[..reading_stem_len].iter().collect(),
+        word_chars[..word_stem_len].iter().collect(),
+        reading_chars[reading_stem_len],
+    ))
+}
+
+fn is_hiragana(c: char) -> bool {
+    ('ぁ'..='ゖ').contains(&c)
+}
+
+/// 辞書のエントリから他のIMEの単語を作る。
+/// 送りありの候補で終止形を復元できないものや、formatで表せない単語になるものはskippedに加える。
+/// 厳密な送り仮名の無い候補は、同じ候補が厳密な送り仮名の候補にも無ければ終止形を復元できないものとする。
+fn words_from_dict_entry(
+    dict_entry: &DictEntry,
+    is_okuri_ari: bool,
+    format: ImeDictionaryFormat,
+    skipped: &mut Vec<SkippedCandidate>,
+) -> Vec<ImeDictionaryWord> {
+    let mut result = vec![];
+    if is_okuri_ari {
+        let reading_stem = dict_entry
+            .midashi
+            .trim_end_matches(|c: char| c.is_ascii_lowercase());
+        let strict_kouho_texts = dict_entry
+            .iter_candidates_with_strict_okuri()
+            .filter(|(strict_okuri, _)| !strict_okuri.is_empty())
+            .map(|(_, cand)| cand.kouho_text.as_str())
+            .collect::<BTreeSet<_>>();
+        for (strict_okuri, cand) in dict_entry.iter_candidates_with_strict_okuri() {
+            // 厳密な送り仮名の候補と重複する候補は、その厳密な送り仮名の候補として書き出す。
+            if strict_okuri.is_empty() && strict_kouho_texts.contains(cand.kouho_text.as_str()) {
+                continue;
+            }
+            let word =
+                WordKind::dictionary_form_okuri(reading_stem, strict_okuri).map(|(okuri, kind)| {
+                    ImeDictionaryWord {
+                        reading: format!("{reading_stem}{okuri}"),
+                        word: format!("{}{okuri}", cand.kouho_text),
+                        part_of_speech: part_of_speech_name(format, kind),
+                        comment: cand.annotation.to_owned(),
+                    }
+                });
+            match word.filter(|word| is_writable_word(format, word)) {
+                Some(word) => result.push(word),
+                None => skipped.push(SkippedCandidate {
+                    midashi: dict_entry.midashi.to_owned(),
+                    strict_okuri: strict_okuri.to_owned(),
+                    kouho_text: cand.kouho_text.to_owned(),
+                }),
+            }
+        }
+    } else {
+        for (strict_okuri, cand) in dict_entry.iter_candidates_with_strict_okuri() {
+            let word = ImeDictionaryWord {
+                reading: dict_entry.midashi.to_owned(),
+                word: cand.kouho_text.to_owned(),
+                part_of_speech: part_of_speech_name(format, WordKind::Noun),
+                comment: cand.annotation.to_owned(),
+            };
+            if is_writable_word(format, &word) {
+                result.push(word);
+            } else {
+                skipped.push(SkippedCandidate {
+                    midashi: dict_entry.midashi.to_owned(),
+                    strict_okuri: strict_okuri.to_owned(),
+                    kouho_text: cand.kouho_text.to_owned(),
+                });
+            }
+        }
+    }
+    result
+}
+
+/// wordをformatの一行として書き出せるならtrue
+/// 読みか単語が空の単語と、タブ区切りの形式でタブや改行を含む単語は書き出せない。
+fn is_writable_word(format: ImeDictionaryFormat, word: &ImeDictionaryWord) -> bool {
+    if word.reading.is_empty() || word.word.is_empty() {
+        return false;
+    }
+    let fields = match format {
+        ImeDictionaryFormat::MozcTsv => vec![
+            word.reading.as_str(),
+            word.word.as_str(),
+            word.part_of_speech.as_str(),
+            word.comment.as_deref().unwrap_or_default(),
+        ],
+        ImeDictionaryFormat::MsImeText => vec![
+            word.reading.as_str(),
+            word.word.as_str(),
+            word.part_of_speech.as_str(),
+        ],
+        ImeDictionaryFormat::ApplePlist | ImeDictionaryFormat::AppleCsv => return true,
+    };
+    !fields
+        .iter()
+        .any(|field| field.contains(['\t', '\n', '\r']))
+}
+
+/// 書き出し先の形式での品詞名。
+fn part_of_speech_name(format: ImeDictionaryFormat, kind: WordKind) -> String {
+    match (format, kind) {
+        (ImeDictionaryFormat::ApplePlist, _) => "".to_string(),
+        (ImeDictionaryFormat::AppleCsv, WordKind::Noun) => "普通名詞".to_string(),
+        (_, WordKind::Noun) => "名詞".to_string(),
+        (_, WordKind::Adjective) => "形容詞".to_string(),
+        (ImeDictionaryFormat::MozcTsv, WordKind::IchidanVerb) => "動詞一段".to_string(),
+        (ImeDictionaryFormat::MozcTsv, WordKind::GodanVerb(gyou)) => {
+            format!("動詞{gyou}行五段")
+        }
+        (_, WordKind::IchidanVerb) | (_, WordKind::GodanVerb(_)) => "動詞".to_string(),
+    }
+}
+
+/// 書き出す単語の種類。送りありの候補は送り仮名から推定する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WordKind {
+    Noun,
+    Adjective,
+    IchidanVerb,
+    // 活用する行のカタカナ一文字
+    GodanVerb(char),
+}
+
+impl WordKind {
+    /// 厳密な送り仮名から、読みの語幹に続けて終止形にする送り仮名と単語の種類を返す。
+    /// 送り仮名が一文字でない場合や、終止形を復元できない送り仮名の場合はNone
+    fn dictionary_form_okuri(reading_stem: &str, okuri: &str) -> Option<(String, Self)> {
+        let mut chars = okuri.chars();
+        let okuri_char = chars.next()?;
+        if chars.next().is_some() {
+            return None;
+        }
+        let kind = match okuri_char {
+            'い' => WordKind::Adjective,
+            'る' => {
+                if reading_stem.chars().last().is_some_and(is_i_or_e_row) {
+                    WordKind::IchidanVerb
+                } else {
+                    WordKind::GodanVerb('ラ')
+                }
+            }
+            'う' => WordKind::GodanVerb('ワ'),
+            'く' => WordKind::GodanVerb('カ'),
+            'ぐ' => WordKind::GodanVerb('ガ'),
+            'す' => WordKind::GodanVerb('サ'),
+            'つ' => WordKind::GodanVerb('タ'),
+            'ぬ' => WordKind::GodanVerb('ナ'),
+            'ぶ' => WordKind::GodanVerb('バ'),
+            'む' => WordKind::GodanVerb('マ'),
+            c if is_i_or_e_row(c) => return Some((format!("{c}る"), WordKind::IchidanVerb)),
+            _ => return None,
+        };
+        Some((okuri.to_owned(), kind))
+    }
+}
+
+fn is_i_or_e_row(c: char) -> bool {
+    "いきぎしじちぢにひびぴみりえけげせぜてでねへべぺめれ".contains(c)
+}
+
+/// タブ区切りで"読み\t単語\t品詞\tコメント"の行を読む。comment_prefixで始まる行は読み飛ばす。
+fn parse_tsv(content: &str, comment_prefix: char) -> Vec<ImeDictionaryWord> {
+    content
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}'))
+        .filter(|line| !line.is_empty() && !line.starts_with(comment_prefix))
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let reading = fields.next()?.to_owned();
+            let word = fields.next()?.to_owned();
+            let part_of_speech = fields.next().unwrap_or_default().to_owned();
+            let comment = fields
+                .next()
+                .filter(|comment| !comment.is_empty())
+                .map(|comment| comment.to_owned());
+            Some(ImeDictionaryWord {
+                reading,
+                word,
+                part_of_speech,
+                comment,
+            })
+        })
+        .collect()
+}
+
+fn format_mozc_tsv(words: &[ImeDictionaryWord]) -> String {
+    let mut result = String::new();
+    for word in words {
+        result += &format!(
+            "{}\t{}\t{}\t{}\n",
+            word.reading,
+            word.word,
+            word.part_of_speech,
+            word.comment.as_deref().unwrap_or_default()
+        );
+    }
+    result
+}
+
+fn format_msime_text(words: &[ImeDictionaryWord]) -> String {
+    let mut result = "!Microsoft IME Dictionary Tool\r\n!Format:WORDLIST\r\n\r\n".to_string();
+    for word in words {
+        result += &format!(
+            "{}\t{}\t{}\r\n",
+            word.reading, word.word, word.part_of_speech
+        );
+    }
+    result
+}
+
+/// "読み","単語","品詞"の行を読む。引用符は省略されていてもよい。
+fn parse_csv(content: &str) -> Vec<ImeDictionaryWord> {
+    content
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}'))
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let fields = split_csv_line(line);
+            let mut fields = fields.into_iter();
+            let reading = fields.next()?;
+            let word = fields.next()?;
+            let part_of_speech = fields.next().unwrap_or_default();
+            let comment = fields.next().filter(|comment| !comment.is_empty());
+            Some(ImeDictionaryWord {
+                reading,
+                word,
+                part_of_speech,
+                comment,
+            })
+        })
+        .collect()
+}
+
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut current = String::new();
+    let mut in_quote = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quote => {
+                if chars.peek() == Some(&'"') {
+                    current.push('"');
+                    chars.next();
+                } else {
+                    in_quote = false;
+                }
+            }
+            '"' if current.is_empty() => in_quote = true,
+            ',' if !in_quote => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+fn format_csv(words: &[ImeDictionaryWord]) -> String {
+    let quote = |field: &str| format!("\"{}\"", field.replace('"', "\"\""));
+    let mut result = String::new();
+    for word in words {
+        result += &format!(
+            "{},{},{}\n",
+            quote(&word.reading),
+            quote(&word.word),
+            quote(&word.part_of_speech)
+        );
+    }
+    result
+}
+
+/// plistの&lt;dict&gt;ごとにshortcutを読み、phraseを単語として読む。
+fn parse_plist(content: &str) -> Vec<ImeDictionaryWord> {
+    let mut result = vec![];
+    for dict in content.split("<dict>").skip(1) {
+        let dict = dict.split("</dict>").next().unwrap_or_default();
+        let mut reading = None;
+        let mut word = None;
+        let mut rest = dict;
+        while let Some((key, after_key)) = next_tag_content(rest, "key") {
+            let Some((value, after_value)) = next_tag_content(after_key, "string") else {
+                break;
+            };
+            match key.as_str() {
+                "shortcut" => reading = Some(value),
+                "phrase" => word = Some(value),
+                _ => {}
+            }
+            rest = after_value;
+        }
+        if let (Some(reading), Some(word)) = (reading, word) {
+            result.push(ImeDictionaryWord {
+                reading,
+                word,
+                part_of_speech: "".to_string(),
+                comment: None,
+            });
+        }
+    }
+    result
+}
+
+/// textの最初の&lt;tag&gt;...&lt;/tag&gt;の中身と、その後ろの残りを返す。
+fn next_tag_content<'a>(text: &'a str, tag: &str) -> Option<(String, &'a str)> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = text.find(&open)? + open.len();
+    let end = start + text[start..].find(&close)?;
+    Some((unescape_xml(&text[start..end]), &text[end + close.len()..]))
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn format_plist(words: &[ImeDictionaryWord]) -> String {
+    let mut result = concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\">\n",
+        "<array>\n"
+    )
+    .to_string();
+    for word in words {
+        result += &format!(
+            "\t<dict>\n\t\t<key>phrase</key>\n\t\t<string>{}</string>\n\t\t<key>shortcut</key>\n\t\t<string>{}</string>\n\t</dict>\n",
+            escape_xml(&word.word),
+            escape_xml(&word.reading)
+        );
+    }
+    result += "</array>\n</plist>\n";
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn word(reading: &str, word: &str, part_of_speech: &str) -> ImeDictionaryWord {
+        ImeDictionaryWord {
+            reading: reading.to_string(),
+            word: word.to_string(),
+            part_of_speech: part_of_speech.to_string(),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn words_to_dict_entries() {
+        let words = vec![
+            word("かんがえる", "考える", "動詞一段"),
+            word("たかい", "高い", "形容詞"),
+            word("かんがえる", "勘返る", "動詞ラ行五段"),
+            word("あい", "愛", "名詞"),
+            word("あい", "藍", "名詞"),
+            word("しずか", "静か", "形容動詞"),
+            word("おちる", "落ちる", "動詞一段"),
+            word("もちいる", "用いる", "動詞一段"),
+            word("うつくしい", "美しい", "形容詞"),
+        ];
+        let (okuri_ari, okuri_nashi) = dict_entries_from_words(&words);
+        let okuri_ari = okuri_ari.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        let okuri_nashi = okuri_nashi
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "かんがe /考/[え/考/]/".to_string(),
+                "たかi /高/[い/高/]/".to_string(),
+                "かんがえr /勘返/[る/勘返/]/".to_string(),
+                "おt /落/[ち/落/]/".to_string(),
+                "もちいr /用い/[る/用い/]/".to_string(),
+                "うつくしi /美し/[い/美し/]/".to_string(),
+            ],
+            okuri_ari
+        );
+        assert_eq!(
+            vec!["あい /愛/藍/".to_string(), "しずか /静か/".to_string()],
+            okuri_nashi
+        );
+    }
+
+    #[test]
+    fn dict_entry_to_words() {
+        let dict_entry = DictEntry::from_skkjisyo_line("かんがe /考/[え/考;annotation/]/").unwrap();
+        let mut skipped = vec![];
+        let words = words_from_dict_entry(
+            &dict_entry,
+            true,
+            ImeDictionaryFormat::MozcTsv,
+            &mut skipped,
+        );
+        assert_eq!(
+            vec![ImeDictionaryWord {
+                reading: "かんがえる".to_string(),
+                word: "考える".to_string(),
+                part_of_speech: "動詞一段".to_string(),
+                comment: Some("annotation".to_string()),
+            }],
+            words
+        );
+
+        let dict_entry = DictEntry::from_skkjisyo_line("かk /書/[く/書/]/").unwrap();
+        let words = words_from_dict_entry(
+            &dict_entry,
+            true,
+            ImeDictionaryFormat::MozcTsv,
+            &mut skipped,
+        );
+        assert_eq!(vec![word("かく", "書く", "動詞カ行五段")], words);
+        let words = words_from_dict_entry(
+            &dict_entry,
+            true,
+            ImeDictionaryFormat::MsImeText,
+            &mut skipped,
+        );
+        assert_eq!(vec![word("かく", "書く", "動詞")], words);
+
+        let dict_entry = DictEntry::from_skkjisyo_line("みr /見/[る/見/]/").unwrap();
+        let words = words_from_dict_entry(
+            &dict_entry,
+            true,
+            ImeDictionaryFormat::MozcTsv,
+            &mut skipped,
+        );
+        assert_eq!(vec![word("みる", "見る", "動詞一段")], words);
+
+        let dict_entry = DictEntry::from_skkjisyo_line("おt /落/[ち/落/]/").unwrap();
+        let words = words_from_dict_entry(
+            &dict_entry,
+            true,
+            ImeDictionaryFormat::MozcTsv,
+            &mut skipped,
+        );
+        assert_eq!(vec![word("おちる", "落ちる", "動詞一段")], words);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn skip_okuri_ari_without_dictionary_form() {
+        let dict_entry = DictEntry::from_skkjisyo_line("いk /行/逝/[って/行/]/").unwrap();
+        let mut skipped = vec![];
+        let words = words_from_dict_entry(
+            &dict_entry,
+            true,
+            ImeDictionaryFormat::MozcTsv,
+            &mut skipped,
+        );
+        assert!(words.is_empty());
+        assert_eq!(
+            vec![
+                SkippedCandidate {
+                    midashi: "いk".to_string(),
+                    strict_okuri: "".to_string(),
+                    kouho_text: "逝".to_string(),
+                },
+                SkippedCandidate {
+                    midashi: "いk".to_string(),
+                    strict_okuri: "って".to_string(),
+                    kouho_text: "行".to_string(),
+                },
+            ],
+            skipped
+        );
+    }
+
+    #[test]
+    fn skip_words_with_tab() {
+        let dict_entry = DictEntry::from_skkjisyo_line("あい /愛\t/藍;注\t釈/哀/").unwrap();
+        let mut skipped = vec![];
+        let words = words_from_dict_entry(
+            &dict_entry,
+            false,
+            ImeDictionaryFormat::MozcTsv,
+            &mut skipped,
+        );
+        assert_eq!(vec![word("あい", "哀", "名詞")], words);
+        assert_eq!(
+            vec!["愛\t".to_string(), "藍".to_string()],
+            skipped
+                .iter()
+                .map(|skipped| skipped.kouho_text.to_owned())
+                .collect::<Vec<_>>()
+        );
+
+        // MS-IMEの形式はコメントを書き出さない
+        let mut skipped = vec![];
+        let words = words_from_dict_entry(
+            &dict_entry,
+            false,
+            ImeDictionaryFormat::MsImeText,
+            &mut skipped,
+        );
+        assert_eq!(
+            vec!["藍".to_string(), "哀".to_string()],
+            words
+                .iter()
+                .map(|word| word.word.to_owned())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, skipped.len());
+    }
+
+    #[test]
+    fn words_round_trip() {
+        let words = vec![
+            word("かんがえる", "考える", "動詞一段"),
+            word("おちる", "落ちる", "動詞一段"),
+            word("もちいる", "用いる", "動詞一段"),
+            word("みる", "見る", "動詞一段"),
+            word("かく", "書く", "動詞カ行五段"),
+            word("たかい", "高い", "形容詞"),
+            word("うつくしい", "美しい", "形容詞"),
+        ];
+        let (okuri_ari, _) = dict_entries_from_words(&words);
+        let mut skipped = vec![];
+        let exported = okuri_ari
+            .iter()
+            .flat_map(|dict_entry| {
+                words_from_dict_entry(dict_entry, true, ImeDictionaryFormat::MozcTsv, &mut skipped)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(words, exported);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn split_okuri_needs_stem() {
+        assert_eq!(None, split_okuri("ある", "ある", false));
+        assert_eq!(None, split_okuri("あいする", "愛す", false));
+        assert_eq!(
+            Some(("いっ".to_string(), "行っ".to_string(), 'た')),
+            split_okuri("いった", "行った", false)
+        );
+        assert_eq!(
+            Some(("かんが".to_string(), "考".to_string(), 'え')),
+            split_okuri("かんがえる", "考える", true)
+        );
+        assert_eq!(
+            Some(("み".to_string(), "見".to_string(), 'る')),
+            split_okuri("みる", "見る", true)
+        );
+    }
+
+    #[test]
+    fn csv_line() {
+        assert_eq!(
+            vec!["よみ", "単\"語", "普通名詞"],
+            split_csv_line(r#""よみ","単""語",普通名詞"#)
+        );
+    }
+
+    #[test]
+    fn plist_round_trip() {
+        let words = vec![word("あんど", "&", ""), word("やじるし", "<-", "")];
+        let formatted = format_plist(&words);
+        assert_eq!(words, parse_plist(&formatted));
+    }
+}
@@ -2,9 +2,12 @@ pub(crate) mod candidate;
 pub(crate) mod composite_key;
 pub(crate) mod dictentry;
 mod dictionary_candidate;
+mod dictionary_entry_data;
 mod dictionary_parser;
 pub mod empty_dict;
 pub(crate) mod file_dictionary;
+mod fuzzy_lookup;
+pub mod ime_format;
 pub mod static_dict;
 pub mod user_dictionary;
 
@@ -14,15 +17,23 @@ use crate::form_changer::numeric_form_changer::{
     numeric_to_daiji_as_number, numeric_to_kanji_each, numeric_to_simple_kanji_as_number,
     numeric_to_thousand_separator, numeric_to_zenkaku,
 };
+use crate::form_changer::KanaFormChanger;
 pub(crate) use candidate::Candidate;
 pub(crate) use composite_key::CompositeKey;
 use dictentry::DictEntry;
 pub(in crate::dictionary) use dictionary_candidate::DictionaryCandidate;
+pub use dictionary_entry_data::{
+    DictionaryCandidateData, DictionaryEntryData, DictionaryEntryOrder, DictionaryReading,
+};
 use empty_dict::EmptyDictionary;
+pub use file_dictionary::DictionaryLoadCallback;
+pub(crate) use fuzzy_lookup::get_fuzzy_candidates;
 use log::*;
 use regex::Regex;
 use static_dict::StaticFileDict;
-use std::sync::{Arc, Mutex};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use user_dictionary::UserDictionary;
 
 // C側に出す関係でSizedである必要があり、dyn Traitではなくenumでラップする。
@@ -37,14 +48,31 @@ pub(crate) enum CskkDictionaryType {
 #[derive(Debug)]
 pub struct CskkDictionary {
     is_completable: bool,
-    pub(crate) mutex: Mutex<CskkDictionaryType>,
+    // ddskkのskk-kakutei-jisyoのように、この辞書の候補が先頭に来た時には選択せずに確定する。
+    is_kakutei: bool,
+    // 検索は複数のスレッドから同時に行え、学習などの書き込みは排他的に行う。
+    // 直接触らず、read, writeなどを通して使う。
+    dictionary: RwLock<CskkDictionaryType>,
+    // 別スレッドで読み込み中の可能性がある場合true。読み込んだエントリへの入れ替えが必要かを書き込みロックなしに判断するため。
+    is_loading: AtomicBool,
 }
 
 impl CskkDictionary {
     fn new(dictionary: CskkDictionaryType, is_completable: bool) -> Self {
         Self {
             is_completable,
-            mutex: Mutex::new(dictionary),
+            is_kakutei: false,
+            dictionary: RwLock::new(dictionary),
+            is_loading: AtomicBool::new(false),
+        }
+    }
+
+    fn new_in_background(dictionary: CskkDictionaryType, is_completable: bool) -> Self {
+        Self {
+            is_completable,
+            is_kakutei: false,
+            dictionary: RwLock::new(dictionary),
+            is_loading: AtomicBool::new(true),
         }
     }
 
@@ -78,6 +106,40 @@ impl CskkDictionary {
         ))
     }
 
+    /// new_static_dictと同様だが、辞書ファイルを別スレッドで読み込み、読み込みの終了を待たずに返す。
+    /// 読み込みが終わるまでは空の辞書として検索し、読み込みが終わると読み込んだエントリに入れ替える。
+    /// on_completeは読み込みが終わった時に読み込みスレッドから呼ばれる。
+    /// ファイルを開けない場合はその場でエラーを返す。
+    pub fn new_static_dict_in_background(
+        file_path: &str,
+        encode: &str,
+        is_completable: bool,
+        on_complete: Option<DictionaryLoadCallback>,
+    ) -> Result<CskkDictionary, CskkError> {
+        let dictionary = StaticFileDict::new_in_background(file_path, encode, on_complete)?;
+        Ok(CskkDictionary::new_in_background(
+            CskkDictionaryType::StaticFile(dictionary),
+            is_completable,
+        ))
+    }
+
+    /// new_user_dictと同様だが、辞書ファイルを別スレッドで読み込み、読み込みの終了を待たずに返す。
+    /// 読み込み中に確定した候補は読み込んだエントリと合わせられる。保存は読み込みが終わるのを待ってから行う。
+    /// on_completeは読み込みが終わった時に読み込みスレッドから呼ばれる。
+    /// ファイルを開けない場合はその場でエラーを返す。
+    pub fn new_user_dict_in_background(
+        file_path: &str,
+        encode: &str,
+        is_completable: bool,
+        on_complete: Option<DictionaryLoadCallback>,
+    ) -> Result<CskkDictionary, CskkError> {
+        let dictionary = UserDictionary::new_in_background(file_path, encode, on_complete)?;
+        Ok(CskkDictionary::new_in_background(
+            CskkDictionaryType::UserFile(dictionary),
+            is_completable,
+        ))
+    }
+
     /// Library user interface for creating fallback dictionary.
     /// Dictionary is required to create the context, so this dictionary is useful when no dictionary file is available.
     pub fn new_empty_dict() -> Result<CskkDictionary, CskkError> {
@@ -86,6 +148,324 @@ impl CskkDictionary {
             false,
         ))
     }
+
+    ///
+    /// 辞書の読み込みロックを取る。別スレッドでの読み込みが終わっていれば、読み込んだエントリに入れ替えてから返す。
+    ///
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, CskkDictionaryType> {
+        if self.is_loading.load(Ordering::Acquire) {
+            drop(self.write());
+        }
+        self.dictionary.read().unwrap()
+    }
+
+    ///
+    /// 辞書の読み込みロックを取る。別スレッドで読み込み中の場合、読み込みが終わるのを待ってから返す。
+    /// 辞書全体を扱う操作に使う。
+    ///
+    pub(crate) fn read_fully_loaded(&self) -> RwLockReadGuard<'_, CskkDictionaryType> {
+        if self.is_loading.load(Ordering::Acquire) {
+            drop(self.write_fully_loaded());
+        }
+        self.dictionary.read().unwrap()
+    }
+
+    ///
+    /// 辞書の書き込みロックを取る。別スレッドでの読み込みが終わっていれば、読み込んだエントリに入れ替えてから返す。
+    ///
+    pub(crate) fn write(&self) -> RwLockWriteGuard<'_, CskkDictionaryType> {
+        self.write_inner(false)
+    }
+
+    ///
+    /// 辞書の書き込みロックを取る。別スレッドで読み込み中の場合、読み込みが終わるのを待ってから返す。
+    /// 後から読み込まれるエントリで結果が変わってしまう操作に使う。
+    ///
+    pub(crate) fn write_fully_loaded(&self) -> RwLockWriteGuard<'_, CskkDictionaryType> {
+        self.write_inner(true)
+    }
+
+    fn write_inner(&self, wait: bool) -> RwLockWriteGuard<'_, CskkDictionaryType> {
+        // Dictionaryのメソッドと名前が重なるため、ここでのみFileDictionaryを使う
+        use file_dictionary::FileDictionary;
+        let mut lock = self.dictionary.write().unwrap();
+        if self.is_loading.load(Ordering::Acquire) {
+            let is_ready = match *lock {
+                CskkDictionaryType::StaticFile(ref mut dict) => {
+                    FileDictionary::absorb_loaded_entries(dict, wait);
+                    FileDictionary::is_ready(dict)
+                }
+                CskkDictionaryType::UserFile(ref mut dict) => {
+                    FileDictionary::absorb_loaded_entries(dict, wait);
+                    FileDictionary::is_ready(dict)
+                }
+                CskkDictionaryType::EmptyDict(_) => true,
+            };
+            self.is_loading.store(!is_ready, Ordering::Release);
+        }
+        lock
+    }
+
+    ///
+    /// is_kakuteiがtrueなら、ddskkのskk-kakutei-jisyoにあたる確定辞書にして返す。
+    /// new_static_dict, new_user_dictなどで作った辞書に続けて使う。
+    /// 確定辞書の候補が変換候補の先頭に来た時は、▼モードで選択せずにその候補で確定する。
+    /// 確定した直後にもう一度次候補のキーを押すと、確定を取り消して変換候補の一覧に戻る。
+    ///
+    pub fn with_kakutei(mut self, is_kakutei: bool) -> Self {
+        self.is_kakutei = is_kakutei;
+        self
+    }
+
+    ///
+    /// 確定辞書ならばtrue
+    ///
+    pub fn is_kakutei(&self) -> bool {
+        self.is_kakutei
+    }
+
+    ///
+    /// 辞書の読み込みが終わっていればtrue
+    ///
+    pub fn is_ready(&self) -> bool {
+        use file_dictionary::FileDictionary;
+        match &*self.read() {
+            CskkDictionaryType::StaticFile(dict) => FileDictionary::is_ready(dict),
+            CskkDictionaryType::UserFile(dict) => FileDictionary::is_ready(dict),
+            CskkDictionaryType::EmptyDict(_) => true,
+        }
+    }
+
+    ///
+    /// 辞書ファイルを読み込んだ割合を0.0から1.0で返す。
+    ///
+    pub fn load_progress(&self) -> f64 {
+        use file_dictionary::FileDictionary;
+        match &*self.read() {
+            CskkDictionaryType::StaticFile(dict) => FileDictionary::load_progress(dict),
+            CskkDictionaryType::UserFile(dict) => FileDictionary::load_progress(dict),
+            CskkDictionaryType::EmptyDict(_) => 1.0,
+        }
+    }
+
+    ///
+    /// 辞書の全エントリを返す。送りありエントリ、送りなしエントリの順にそれぞれorderの順で並ぶ。
+    ///
+    pub fn get_entries(&self, order: DictionaryEntryOrder) -> Vec<DictionaryEntryData> {
+        self.collect_entries(order, |_| true)
+    }
+
+    ///
+    /// 見出しがprefixから始まるエントリを見出しの辞書順で返す。
+    ///
+    /// 送りありエントリの見出しは"かんがe"のように送り仮名のローマ字が末尾に付いている。
+    ///
+    pub fn search_entries_by_midashi_prefix(&self, prefix: &str) -> Vec<DictionaryEntryData> {
+        self.collect_entries(DictionaryEntryOrder::Sorted, |dict_entry| {
+            dict_entry.midashi.starts_with(prefix)
+        })
+    }
+
+    ///
+    /// 候補のいずれかにsubstringを含むエントリを見出しの辞書順で返す。
+    ///
+    pub fn search_entries_by_candidate(&self, substring: &str) -> Vec<DictionaryEntryData> {
+        self.collect_entries(DictionaryEntryOrder::Sorted, |dict_entry| {
+            dict_entry.has_candidate_containing(substring)
+        })
+    }
+
+    fn collect_entries<F>(&self, order: DictionaryEntryOrder, filter: F) -> Vec<DictionaryEntryData>
+    where
+        F: Fn(&DictEntry) -> bool,
+    {
+        let lock = self.read_fully_loaded();
+        let dictionary: &dyn Dictionary = match &*lock {
+            CskkDictionaryType::StaticFile(dict) => dict,
+            CskkDictionaryType::UserFile(dict) => dict,
+            CskkDictionaryType::EmptyDict(dict) => dict,
+        };
+        let mut result = vec![];
+        for is_okuri_ari in [true, false] {
+            result.extend(
+                dictionary
+                    .iter_entries(order, is_okuri_ari)
+                    .filter(|dict_entry| filter(dict_entry))
+                    .map(|dict_entry| {
+                        DictionaryEntryData::from_dict_entry(dict_entry, is_okuri_ari)
+                    }),
+            );
+        }
+        result
+    }
+
+    ///
+    /// 見出しmidashiのエントリの先頭に候補を追加する。既に同じ候補がある場合は先頭に移動し、アノテーションを置き換える。
+    ///
+    /// 送りありエントリの場合、midashiは"かんがe"のように送り仮名のローマ字を末尾に付ける。
+    /// strict_okuriを与えた場合、厳密な送り仮名の候補としても追加する。
+    ///
+    /// Returns true if the dictionary is updated. Read-only dictionaries are kindly ignored and returns false.
+    ///
+    pub fn add_candidate(
+        &self,
+        midashi: &str,
+        kouho_text: &str,
+        annotation: Option<&str>,
+        strict_okuri: Option<&str>,
+    ) -> Result<bool, CskkError> {
+        if midashi.is_empty() || kouho_text.is_empty() {
+            return Err(CskkError::Error(
+                "midashi and kouho_text must not be empty".to_string(),
+            ));
+        }
+        let is_okuri_ari = DictEntry::is_okuri_ari_midashi(midashi);
+        if strict_okuri.is_some() && !is_okuri_ari {
+            return Err(CskkError::Error(format!(
+                "strict okuri is given for okuri-nasi midashi {midashi}"
+            )));
+        }
+        let candidate = Candidate::new(
+            midashi.to_string(),
+            strict_okuri.map(|x| x.to_string()),
+            is_okuri_ari,
+            kouho_text.to_string(),
+            annotation.map(|x| x.to_string()),
+            kouho_text.to_string(),
+        );
+        // Using lock in match on purpose, never acquiring lock again.
+        #[allow(clippy::significant_drop_in_scrutinee)]
+        match *self.write() {
+            CskkDictionaryType::StaticFile(ref mut dict) => dict.add_candidate(&candidate),
+            CskkDictionaryType::UserFile(ref mut dict) => dict.add_candidate(&candidate),
+            CskkDictionaryType::EmptyDict(ref mut dict) => dict.add_candidate(&candidate),
+        }
+    }
+
+    ///
+    /// 見出しmidashiのエントリの候補kouho_textをnew_positionの位置に移動する。
+    /// strict_okuriを与えた場合、その厳密な送り仮名の候補の中で移動する。
+    ///
+    /// Returns true if the dictionary is updated.
+    ///
+    pub fn move_candidate(
+        &self,
+        midashi: &str,
+        strict_okuri: Option<&str>,
+        kouho_text: &str,
+        new_position: usize,
+    ) -> Result<bool, CskkError> {
+        // Using lock in match on purpose, never acquiring lock again.
+        #[allow(clippy::significant_drop_in_scrutinee)]
+        match *self.write_fully_loaded() {
+            CskkDictionaryType::StaticFile(ref mut dict) => {
+                dict.move_candidate(midashi, strict_okuri, kouho_text, new_position)
+            }
+            CskkDictionaryType::UserFile(ref mut dict) => {
+                dict.move_candidate(midashi, strict_okuri, kouho_text, new_position)
+            }
+            CskkDictionaryType::EmptyDict(ref mut dict) => {
+                dict.move_candidate(midashi, strict_okuri, kouho_text, new_position)
+            }
+        }
+    }
+
+    ///
+    /// 見出しmidashiのエントリから候補kouho_textを削除する。
+    /// strict_okuriがNoneの場合は厳密な送り仮名の有無にかかわらず削除し、Someの場合はその厳密な送り仮名の候補からのみ削除する。
+    /// 候補がなくなったエントリは削除される。
+    ///
+    /// Returns true if the dictionary is updated.
+    ///
+    pub fn delete_candidate(
+        &self,
+        midashi: &str,
+        strict_okuri: Option<&str>,
+        kouho_text: &str,
+    ) -> Result<bool, CskkError> {
+        // Using lock in match on purpose, never acquiring lock again.
+        #[allow(clippy::significant_drop_in_scrutinee)]
+        match *self.write_fully_loaded() {
+            CskkDictionaryType::StaticFile(ref mut dict) => {
+                dict.delete_candidate(midashi, strict_okuri, kouho_text)
+            }
+            CskkDictionaryType::UserFile(ref mut dict) => {
+                dict.delete_candidate(midashi, strict_okuri, kouho_text)
+            }
+            CskkDictionaryType::EmptyDict(ref mut dict) => {
+                dict.delete_candidate(midashi, strict_okuri, kouho_text)
+            }
+        }
+    }
+
+    ///
+    /// 見出しmidashiのエントリを削除する。
+    ///
+    /// Returns true if the dictionary is updated.
+    ///
+    pub fn delete_entry(&self, midashi: &str) -> Result<bool, CskkError> {
+        // Using lock in match on purpose, never acquiring lock again.
+        #[allow(clippy::significant_drop_in_scrutinee)]
+        match *self.write_fully_loaded() {
+            CskkDictionaryType::StaticFile(ref mut dict) => dict.delete_entry(midashi),
+            CskkDictionaryType::UserFile(ref mut dict) => dict.delete_entry(midashi),
+            CskkDictionaryType::EmptyDict(ref mut dict) => dict.delete_entry(midashi),
+        }
+    }
+
+    ///
+    /// SKK辞書形式のファイルを読み込み、含まれる候補をこの辞書に追加する。
+    /// 既存のエントリにある候補は順序を保ち、ない候補のみ末尾に追加する。
+    ///
+    /// Returns the number of added candidates. Read-only dictionaries are kindly ignored and returns 0.
+    ///
+    pub fn import_entries(&self, file_path: &str, encode: &str) -> Result<usize, CskkError> {
+        // Using lock in match on purpose, never acquiring lock again.
+        #[allow(clippy::significant_drop_in_scrutinee)]
+        match *self.write_fully_loaded() {
+            CskkDictionaryType::StaticFile(ref mut dict) => dict.import_entries(file_path, encode),
+            CskkDictionaryType::UserFile(ref mut dict) => dict.import_entries(file_path, encode),
+            CskkDictionaryType::EmptyDict(ref mut dict) => dict.import_entries(file_path, encode),
+        }
+    }
+
+    ///
+    /// この辞書のエントリをSKK辞書形式でfile_pathに書き出す。
+    /// 読み込み専用の辞書も書き出せる。
+    ///
+    pub fn export_entries(
+        &self,
+        file_path: &str,
+        encode: &str,
+        order: DictionaryEntryOrder,
+    ) -> Result<(), CskkError> {
+        let lock = self.read_fully_loaded();
+        let dictionary: &dyn Dictionary = match &*lock {
+            CskkDictionaryType::StaticFile(dict) => dict,
+            CskkDictionaryType::UserFile(dict) => dict,
+            CskkDictionaryType::EmptyDict(dict) => dict,
+        };
+        file_dictionary::write_dictionary(
+            file_path,
+            encode,
+            dictionary.iter_entries(order, true),
+            dictionary.iter_entries(order, false),
+        )
+    }
+
+    ///
+    /// Save this dictionary if it is writable and has changes.
+    /// Returns true if saved, false if kindly ignored.
+    ///
+    pub fn save(&self) -> Result<bool, CskkError> {
+        // Using lock in match on purpose, never acquiring lock again.
+        #[allow(clippy::significant_drop_in_scrutinee)]
+        match *self.write_fully_loaded() {
+            CskkDictionaryType::StaticFile(ref mut dict) => dict.save_dictionary(),
+            CskkDictionaryType::UserFile(ref mut dict) => dict.save_dictionary(),
+            CskkDictionaryType::EmptyDict(ref mut dict) => dict.save_dictionary(),
+        }
+    }
 }
 
 /// confirm the candidate.
@@ -96,9 +476,9 @@ pub(crate) fn confirm_candidate(
     candidate: &Candidate,
 ) -> Result<bool, CskkError> {
     debug!("confirm: {:?}", candidate);
-    // Using mutex in match on purpose, never acquiring lock again.
+    // Using lock in match on purpose, never acquiring lock again.
     #[allow(clippy::significant_drop_in_scrutinee)]
-    match *dictionary.mutex.lock().unwrap() {
+    match *dictionary.write() {
         CskkDictionaryType::StaticFile(ref mut dict) => dict.select_candidate(candidate),
         CskkDictionaryType::UserFile(ref mut dict) => dict.select_candidate(candidate),
         CskkDictionaryType::EmptyDict(ref mut dict) => dict.select_candidate(candidate),
@@ -113,9 +493,9 @@ pub(crate) fn purge_candidate(
     composite_key: &CompositeKey,
     candidate: &Candidate,
 ) -> Result<bool, CskkError> {
-    // Using mutex in match on purpose, never acquiring lock again.
+    // Using lock in match on purpose, never acquiring lock again.
     #[allow(clippy::significant_drop_in_scrutinee)]
-    match *dictionary.mutex.lock().unwrap() {
+    match *dictionary.write_fully_loaded() {
         CskkDictionaryType::StaticFile(ref mut dict) => {
             dict.purge_candidate(composite_key, candidate)
         }
@@ -128,6 +508,59 @@ pub(crate) fn purge_candidate(
     }
 }
 
+///
+/// dictionariesからwordを候補に持つ見出しを逆引きし、読みと見つかった辞書を辞書の順に返す。
+///
+/// 送りありエントリは厳密な送り仮名の候補も探し、厳密な送り仮名の候補として見つかった場合はその送り仮名を共に返す。
+///
+pub fn get_readings_for(
+    dictionaries: &[Arc<CskkDictionary>],
+    word: &str,
+) -> Vec<DictionaryReading> {
+    let mut result = vec![];
+    if word.is_empty() {
+        return result;
+    }
+
+    for (dictionary_index, cskkdict) in dictionaries.iter().enumerate() {
+        let lock = cskkdict.read();
+        let dict_entries = match &*lock {
+            CskkDictionaryType::StaticFile(dict) => dict.reverse_lookup(word),
+            CskkDictionaryType::UserFile(dict) => dict.reverse_lookup(word),
+            CskkDictionaryType::EmptyDict(dict) => dict.reverse_lookup(word),
+        };
+        for (is_okuri_ari, dict_entry) in dict_entries {
+            let reading = if is_okuri_ari {
+                dict_entry
+                    .midashi
+                    .trim_end_matches(|c: char| c.is_ascii_lowercase())
+                    .to_string()
+            } else {
+                dict_entry.midashi.to_owned()
+            };
+            for (strict_okuri, candidate) in dict_entry.iter_candidates_with_strict_okuri() {
+                if candidate.kouho_text == word {
+                    result.push(DictionaryReading {
+                        midashi: dict_entry.midashi.to_owned(),
+                        reading: reading.to_owned(),
+                        is_okuri_ari,
+                        strict_okuri: if strict_okuri.is_empty() {
+                            None
+                        } else {
+                            Some(strict_okuri.to_string())
+                        },
+                        annotation: candidate.annotation.to_owned(),
+                        dictionary_index,
+                        dictionary: cskkdict.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    result
+}
+
 /// 現在ueno/libskk同様にDedupはkouho_textのみ。
 pub(crate) fn get_all_candidates(
     dictionaries: &[Arc<CskkDictionary>],
@@ -136,20 +569,216 @@ pub(crate) fn get_all_candidates(
     get_all_candidates_inner(dictionaries, composite_key, false)
 }
 
+///
+/// 再変換する文字列の読みと、送り仮名があればその送り仮名を返す。
+///
+/// readingが与えられた場合はその読みを使い、文字列と読みの末尾で共通するひらがなを送り仮名とする。
+/// 与えられない場合は逆引きで送りなしの読みを探し、見つからなければ末尾のひらがなを送り仮名として語幹を送りありエントリから逆引きする。
+/// ひらがなだけの文字列はそれ自体を読みとする。読みが見つからなければNoneを返す。
+///
+pub(crate) fn get_reconversion_reading(
+    dictionaries: &[Arc<CskkDictionary>],
+    text: &str,
+    reading: Option<&str>,
+) -> Option<(String, Option<String>)> {
+    let stem_len = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| ('ぁ'..='ゖ').contains(c))
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+    let (stem, okuri) = text.split_at(stem_len);
+
+    if let Some(reading) = reading {
+        if reading.is_empty() {
+            return None;
+        }
+        if !stem.is_empty() && !okuri.is_empty() && reading.len() > okuri.len() {
+            if let Some(reading_stem) = reading.strip_suffix(okuri) {
+                return Some((reading_stem.to_owned(), Some(okuri.to_owned())));
+            }
+        }
+        return Some((reading.to_owned(), None));
+    }
+
+    if let Some(found) = get_readings_for(dictionaries, text)
+        .into_iter()
+        .find(|found| !found.is_okuri_ari)
+    {
+        return Some((found.reading, None));
+    }
+    if stem.is_empty() {
+        return Some((text.to_owned(), None));
+    }
+    if !okuri.is_empty() {
+        let okuri = okuri.to_owned();
+        if let Some(found) = get_readings_for(dictionaries, stem)
+            .into_iter()
+            .find(|found| {
+                found.is_okuri_ari
+                    && CompositeKey::new(&found.reading, Some(okuri.to_owned())).get_dict_key()
+                        == found.midashi
+            })
+        {
+            return Some((found.reading, Some(okuri)));
+        }
+    }
+    None
+}
+
+///
+/// 確定による学習を取り消すための、学習前のユーザー辞書のエントリ
+///
+pub(crate) struct LearningBackup {
+    dictionary: Arc<CskkDictionary>,
+    okuri: bool,
+    midashi: String,
+    entry: Option<DictEntry>,
+}
+
+///
+/// candidateの確定で学習する前に、ユーザー辞書であれば学習前のエントリを取っておく。
+///
+pub(crate) fn backup_learning(
+    dictionary: &Arc<CskkDictionary>,
+    candidate: &Candidate,
+) -> Option<LearningBackup> {
+    match *dictionary.read() {
+        CskkDictionaryType::UserFile(ref dict) => Some(LearningBackup {
+            dictionary: dictionary.clone(),
+            okuri: candidate.okuri,
+            midashi: candidate.midashi.to_owned(),
+            entry: dict.peek_entry(candidate.okuri, &candidate.midashi),
+        }),
+        _ => None,
+    }
+}
+
+///
+/// 取っておいたエントリに戻して学習を取り消す。
+/// LRUの順序は戻らないので、補完候補の順では学習した見出しが新しいものとして扱われる。
+///
+pub(crate) fn restore_learning(backup: LearningBackup) {
+    // Using lock in match on purpose, never acquiring lock again.
+    #[allow(clippy::significant_drop_in_scrutinee)]
+    if let CskkDictionaryType::UserFile(ref mut dict) = *backup.dictionary.write() {
+        dict.restore_entry(backup.okuri, &backup.midashi, backup.entry);
+    }
+}
+
+///
+/// candidateがcomposite_keyに対する確定辞書の候補ならばtrueを返す。
+///
+pub(crate) fn is_kakutei_candidate(
+    dictionaries: &[Arc<CskkDictionary>],
+    composite_key: &CompositeKey,
+    candidate: &Candidate,
+) -> bool {
+    let kakutei_dictionaries = dictionaries
+        .iter()
+        .filter(|cskkdict| cskkdict.is_kakutei())
+        .cloned()
+        .collect::<Vec<_>>();
+    get_candidates_in_order(&kakutei_dictionaries, composite_key)
+        .iter()
+        .any(|dictionary_candidate| dictionary_candidate.kouho_text == candidate.kouho_text)
+}
+
+///
+/// ddskkのskk-search-sagyo-henkakuに相当する、サ行変格活用の語幹による候補を返す。
+///
+/// composite_keyの送り仮名がさ・し・す・せで始まる場合に、送り仮名を除いた読みで送りなしエントリを引き、
+/// その候補を元の送りありの候補として返す。「べんきょうs」の候補が無くても「べんきょう」の候補から「勉強す」と変換できる。
+/// 候補は送りありの見出しを持つので、確定すると送りありエントリとして学習される。
+///
+pub(crate) fn get_sagyo_henkaku_candidates(
+    dictionaries: &[Arc<CskkDictionary>],
+    composite_key: &CompositeKey,
+) -> Vec<Candidate> {
+    let is_sagyo_okuri = composite_key
+        .get_okuri()
+        .as_ref()
+        .and_then(|okuri| okuri.chars().next())
+        .is_some_and(|first_okuri| matches!(first_okuri, 'さ' | 'し' | 'す' | 'せ'));
+    if !is_sagyo_okuri {
+        return vec![];
+    }
+
+    let stem_key = CompositeKey::new(composite_key.get_to_composite(), None);
+    let stem_candidates = dedup_candidates(get_candidates_in_order(dictionaries, &stem_key));
+    stem_candidates
+        .into_iter()
+        .map(|dictionary_candidate| {
+            Candidate::from_dictionary_candidate(composite_key, &dictionary_candidate)
+        })
+        .collect()
+}
+
+///
+/// ddskkのskk-auto-okuri-processに相当する、送り仮名を指定せずに入力した読みを送りありエントリから引いた候補を返す。
+///
+/// 送りなしのcomposite_keyの読みを語幹と送り仮名に分けられる全ての位置で送りありエントリを引き、
+/// 候補の後ろに送り仮名とそれ以降の読みを付けて返す。語幹の長い分け方の候補から並ぶ。
+/// 「かえる」からは「かえr」の「帰る」、「かe」の「変える」等を返す。
+/// 候補は分けた送り仮名を厳密な送り仮名として持つので、確定すると送りありエントリとして学習される。
+///
+pub(crate) fn get_auto_okuri_candidates(
+    dictionaries: &[Arc<CskkDictionary>],
+    composite_key: &CompositeKey,
+) -> Vec<Candidate> {
+    if composite_key.has_okuri() {
+        return vec![];
+    }
+
+    let reading = composite_key.get_to_composite().chars().collect::<Vec<_>>();
+    let mut result = Vec::new();
+    for stem_len in (1..reading.len()).rev() {
+        if KanaFormChanger::kana_to_okuri_prefix(&reading[stem_len]).is_none() {
+            continue;
+        }
+        // 促音は次のかなと合わせて送り仮名とする。「かっ」+「た」ではなく「か」+「った」。
+        let okuri_len = if reading[stem_len] == 'っ' && stem_len + 1 < reading.len() {
+            2
+        } else {
+            1
+        };
+        let stem = reading[..stem_len].iter().collect::<String>();
+        let okuri = reading[stem_len..stem_len + okuri_len]
+            .iter()
+            .collect::<String>();
+        let rest = reading[stem_len + okuri_len..].iter().collect::<String>();
+        let okuri_key = CompositeKey::new(&stem, Some(okuri.to_owned()));
+        let dictionary_candidates =
+            dedup_candidates(get_candidates_in_order(dictionaries, &okuri_key));
+        for dictionary_candidate in dictionary_candidates {
+            let mut candidate =
+                Candidate::from_dictionary_candidate(&okuri_key, &dictionary_candidate);
+            candidate.output = format!("{}{}{}", candidate.output, okuri, rest);
+            result.push(candidate);
+        }
+    }
+    result
+}
+
 ///
 /// 補完候補となる辞書のエントリ列を返す。
 ///
 /// [Dictionary]の[complete]に準じて、composite_keyが送りなしのみを想定し、先頭一致する送りなし候補を返す。
+/// 送りありエントリからの候補は、厳密な送り仮名をstrict_okuriに持つ送りありの候補として後に続く。
+///
+/// limitがSomeの場合、重複を除いてその件数の候補が見つかった時点で辞書を読むのをやめ、その件数までを返す。
 ///
 pub(crate) fn get_all_complete(
     dictionaries: &[Arc<CskkDictionary>],
     composite_key: &CompositeKey,
+    limit: Option<usize>,
 ) -> Vec<Candidate> {
-    let dict_candidates = get_all_complete_inner(dictionaries, composite_key);
+    let dict_candidates = get_all_complete_inner(dictionaries, composite_key, limit);
     let deduped_completion_candidate = dedup_candidates(dict_candidates);
 
     deduped_completion_candidate
         .into_iter()
+        .take(limit.unwrap_or(usize::MAX))
         .map(|x| Candidate::from_completion_candidate(&x))
         .collect()
 }
@@ -157,43 +786,127 @@ pub(crate) fn get_all_complete(
 /// 補完候補となる辞書のエントリ列を返す。
 ///
 /// [Dictionary]の[complete]に準じて、composite_keyが送りなしのみを想定し、先頭一致する送りなし候補を返す。
+/// 続いて、送り仮名を除いた読みが先頭一致する送りありエントリから、送り仮名の定まる候補を送り仮名付きで返す。
+///
+/// どちらも設定された辞書の順に並べる。ユーザー辞書の中では辞書を作ってから確定した回数の多い順に、同じ回数なら最近使用した順に、
+/// それ以外の辞書の中では見出しの順に並ぶ。SKK-JISYO形式は使用回数を持たないので、回数は辞書ファイルに保存しない。
+///
+/// limitがSomeの場合、重複を除いた候補がその件数見つかった時点で返す。
 ///
 /// 使われない想定ではあるが、composite_keyが送りありだと送り仮名まで一致する候補を返すので先頭一致が完全一致と同じになる。
 ///
 fn get_all_complete_inner(
     dictionaries: &[Arc<CskkDictionary>],
     composite_key: &CompositeKey,
+    limit: Option<usize>,
 ) -> Vec<CompletionCandidate> {
     let mut result = Vec::new();
+    let mut distinct_candidates = HashSet::new();
+    let completes_okuri_ari = if composite_key.has_okuri() {
+        vec![false]
+    } else {
+        vec![false, true]
+    };
 
-    for cskkdict in dictionaries.iter() {
-        if cskkdict.is_completable {
-            let lock = cskkdict.mutex.lock().unwrap();
-            let dict_entries = match &*lock {
-                CskkDictionaryType::StaticFile(dict) => dict.complete(composite_key),
-                CskkDictionaryType::UserFile(dict) => dict.complete(composite_key),
-                CskkDictionaryType::EmptyDict(dict) => dict.complete(composite_key),
-            };
-            for dict_entry in dict_entries {
-                let candidates = dict_entry.get_candidates(composite_key.get_okuri());
-
-                if let Some(candidates) = candidates {
-                    // result.extend(candidates.to_owned())
-                    result.extend(candidates.iter().map(|x| {
-                        CompletionCandidate::from_dictionary_candidate(
-                            &dict_entry.midashi,
-                            composite_key.get_okuri(),
-                            x,
-                        )
-                    }));
+    for okuri_ari in completes_okuri_ari {
+        for cskkdict in dictionaries.iter().filter(|dict| dict.is_completable) {
+            let lock = cskkdict.read();
+            for dict_entry in completion_entries(&lock, composite_key, okuri_ari) {
+                let candidates = if okuri_ari {
+                    complete_okuri_ari_candidates(dict_entry)
+                } else {
+                    dict_entry
+                        .get_candidates(composite_key.get_okuri())
+                        .map(|candidates| {
+                            candidates
+                                .iter()
+                                .map(|x| {
+                                    CompletionCandidate::from_dictionary_candidate(
+                                        &dict_entry.midashi,
+                                        composite_key.get_okuri(),
+                                        x,
+                                    )
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default()
+                };
+                for candidate in candidates {
+                    distinct_candidates.insert((
+                        candidate.kouho_text.to_owned(),
+                        candidate.annotation.to_owned(),
+                    ));
+                    result.push(candidate);
+                }
+                if limit.is_some_and(|limit| distinct_candidates.len() >= limit) {
+                    return result;
                 }
             }
         }
     }
-
     result
 }
 
+///
+/// 辞書から補完に使うエントリを返す。
+/// okuri_ariがfalseならcomposite_keyに先頭一致するエントリを、trueなら送り仮名を除いた読みが先頭一致する送りありエントリを返す。
+/// ユーザー辞書では確定した回数と最近使用した順に、それ以外の辞書では見出しの順に返す。
+///
+fn completion_entries<'a>(
+    dictionary: &'a CskkDictionaryType,
+    composite_key: &'a CompositeKey,
+    okuri_ari: bool,
+) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
+    use file_dictionary::FileDictionary;
+    let reading = composite_key.get_to_composite();
+    match (dictionary, okuri_ari) {
+        (CskkDictionaryType::StaticFile(dict), false) => Dictionary::complete(dict, composite_key),
+        (CskkDictionaryType::StaticFile(dict), true) => {
+            FileDictionary::complete_okuri_ari(dict, reading, false)
+        }
+        (CskkDictionaryType::UserFile(dict), false) => {
+            FileDictionary::complete_by_usage(dict, composite_key)
+        }
+        (CskkDictionaryType::UserFile(dict), true) => {
+            FileDictionary::complete_okuri_ari(dict, reading, true)
+        }
+        (CskkDictionaryType::EmptyDict(dict), false) => dict.complete(composite_key),
+        (CskkDictionaryType::EmptyDict(_), true) => Box::new(std::iter::empty()),
+    }
+}
+
+///
+/// 送りありエントリから、送り仮名が定まる候補を送り仮名付きの補完候補として返す。
+///
+/// 厳密な送り仮名のある候補はその送り仮名で返す。
+/// 厳密な送り仮名のない候補は、"かんがe"のように送り仮名が母音のみで定まる時に限り返す。
+///
+fn complete_okuri_ari_candidates(dict_entry: &DictEntry) -> Vec<CompletionCandidate> {
+    let vowel_okuri = match dict_entry.midashi.chars().last() {
+        Some('a') => Some("あ"),
+        Some('i') => Some("い"),
+        Some('u') => Some("う"),
+        Some('e') => Some("え"),
+        Some('o') => Some("お"),
+        _ => None,
+    };
+    dict_entry
+        .iter_candidates_with_strict_okuri()
+        .filter_map(|(strict_okuri, dictionary_candidate)| {
+            let okuri = if strict_okuri.is_empty() {
+                vowel_okuri?
+            } else {
+                strict_okuri
+            };
+            Some(CompletionCandidate::from_dictionary_candidate(
+                &dict_entry.midashi,
+                &Some(okuri.to_string()),
+                dictionary_candidate,
+            ))
+        })
+        .collect()
+}
+
 ///
 /// First search the exact match, and then replace numerics to # and search the dict for numeric composition.
 /// If numeric-re-lookup, skip the latter don't replace numerics for the "#4" type entries.
@@ -296,7 +1009,7 @@ fn get_candidates_in_order(
     let mut result = Vec::new();
 
     for cskkdict in dictionaries.iter() {
-        let lock = cskkdict.mutex.lock().unwrap();
+        let lock = cskkdict.read();
         if let Some(dict_entry) = match &*lock {
             CskkDictionaryType::StaticFile(dict) => dict.lookup(composite_key),
             CskkDictionaryType::UserFile(dict) => dict.lookup(composite_key),
@@ -555,6 +1268,58 @@ pub(crate) trait Dictionary {
     fn reload(&mut self) -> Result<(), CskkError> {
         Ok(())
     }
+
+    /// is_okuri_ariに合わせて送りありエントリか送りなしエントリの全エントリを返す。
+    fn iter_entries<'a>(
+        &'a self,
+        order: DictionaryEntryOrder,
+        is_okuri_ari: bool,
+    ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a>;
+
+    /// Add the candidate to the top of the entry, or move it to the top and replace its annotation when it already exists.
+    /// Unlike select_candidate, this is for the dictionary editing.
+    /// Safe to call to read_only dictionary.
+    fn add_candidate(&mut self, _candidate: &Candidate) -> Result<bool, CskkError> {
+        Ok(false)
+    }
+
+    /// Move the candidate in the entry to the new position.
+    /// Safe to call to read_only dictionary.
+    fn move_candidate(
+        &mut self,
+        _midashi: &str,
+        _strict_okuri: Option<&str>,
+        _kouho_text: &str,
+        _new_position: usize,
+    ) -> Result<bool, CskkError> {
+        Ok(false)
+    }
+
+    /// Delete the candidate from the entry.
+    /// Safe to call to read_only dictionary.
+    fn delete_candidate(
+        &mut self,
+        _midashi: &str,
+        _strict_okuri: Option<&str>,
+        _kouho_text: &str,
+    ) -> Result<bool, CskkError> {
+        Ok(false)
+    }
+
+    /// Delete the whole entry.
+    /// Safe to call to read_only dictionary.
+    fn delete_entry(&mut self, _midashi: &str) -> Result<bool, CskkError> {
+        Ok(false)
+    }
+
+    /// kouho_textを候補に持つエントリを、送りありエントリかどうかと共に返す。
+    fn reverse_lookup(&self, kouho_text: &str) -> Vec<(bool, &DictEntry)>;
+
+    /// Merge the entries in the dictionary file. Returns the number of added candidates.
+    /// Safe to call to read_only dictionary.
+    fn import_entries(&mut self, _file_path: &str, _encode: &str) -> Result<usize, CskkError> {
+        Ok(0)
+    }
 }
 
 #[cfg(test)]
@@ -580,6 +1345,96 @@ mod test {
         assert_eq!(result[0].kouho_text, "愛");
     }
 
+    #[test]
+    fn get_sagyo_henkaku_candidates_basic() {
+        let test_dictionary =
+            CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+                .unwrap();
+        let dictionaries = vec![Arc::new(test_dictionary)];
+        let key = CompositeKey::new("べんきょう", Some("し".to_string()));
+        let result = get_sagyo_henkaku_candidates(&dictionaries, &key);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].kouho_text, "勉強");
+        assert_eq!(result[0].midashi, "べんきょうs");
+        assert_eq!(result[0].strict_okuri, Some("し".to_string()));
+        assert!(result[0].okuri);
+
+        let key = CompositeKey::new("べんきょう", Some("か".to_string()));
+        assert!(get_sagyo_henkaku_candidates(&dictionaries, &key).is_empty());
+        let key = CompositeKey::new("べんきょう", None);
+        assert!(get_sagyo_henkaku_candidates(&dictionaries, &key).is_empty());
+    }
+
+    #[test]
+    fn get_auto_okuri_candidates_basic() {
+        let test_dictionary =
+            CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+                .unwrap();
+        let dictionaries = vec![Arc::new(test_dictionary)];
+        let key = CompositeKey::new("かえる", None);
+        let result = get_auto_okuri_candidates(&dictionaries, &key);
+
+        let outputs = result
+            .iter()
+            .map(|candidate| candidate.output.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outputs,
+            vec!["帰る", "返る", "変える", "替える", "代える", "換える"]
+        );
+        assert_eq!(result[0].midashi, "かえr");
+        assert_eq!(result[0].kouho_text, "帰");
+        assert_eq!(result[0].strict_okuri, Some("る".to_string()));
+        assert_eq!(result[2].midashi, "かe");
+        assert_eq!(result[2].strict_okuri, Some("え".to_string()));
+
+        let key = CompositeKey::new("かえ", Some("る".to_string()));
+        assert!(get_auto_okuri_candidates(&dictionaries, &key).is_empty());
+    }
+
+    #[test]
+    fn get_reconversion_reading_basic() {
+        let test_dictionary =
+            CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+                .unwrap();
+        let dictionaries = vec![Arc::new(test_dictionary)];
+        assert_eq!(
+            Some(("かんじ".to_string(), None)),
+            get_reconversion_reading(&dictionaries, "漢字", None)
+        );
+        assert_eq!(
+            Some(("かんが".to_string(), Some("える".to_string()))),
+            get_reconversion_reading(&dictionaries, "考える", None)
+        );
+        assert_eq!(
+            Some(("か".to_string(), Some("える".to_string()))),
+            get_reconversion_reading(&dictionaries, "変える", Some("かえる"))
+        );
+        assert_eq!(
+            Some(("ひらがな".to_string(), None)),
+            get_reconversion_reading(&dictionaries, "ひらがな", None)
+        );
+        assert_eq!(
+            None,
+            get_reconversion_reading(&dictionaries, "未登録語", None)
+        );
+    }
+
+    #[test]
+    fn get_auto_okuri_candidates_sokuon() {
+        let test_dictionary =
+            CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+                .unwrap();
+        let dictionaries = vec![Arc::new(test_dictionary)];
+        let key = CompositeKey::new("かった", None);
+        let result = get_auto_okuri_candidates(&dictionaries, &key);
+
+        assert_eq!(result[0].midashi, "かt");
+        assert_eq!(result[0].strict_okuri, Some("った".to_string()));
+        assert_eq!(result[0].output, "勝った");
+    }
+
     #[test]
     fn get_all_candidates_numeric_match() {
         let test_dictionary = CskkDictionary::new_static_dict(
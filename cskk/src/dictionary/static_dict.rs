@@ -1,7 +1,14 @@
-use crate::dictionary::file_dictionary::{load_dictionary, DictionaryEntries, FileDictionary};
-use crate::dictionary::{CompositeKey, DictEntry, Dictionary};
+use crate::dictionary::file_dictionary::{
+    load_dictionary, BackgroundLoad, DictionaryEntries, DictionaryLoadCallback, FileDictionary,
+};
+use crate::dictionary::{CompositeKey, DictEntry, Dictionary, DictionaryEntryOrder};
 use crate::CskkError;
 use lru_ordered_map::LruOrderedMap;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// kouho_text -> (送りありエントリか, midashi) の列
+type ReverseIndex = HashMap<String, Vec<(bool, String)>>;
 
 #[derive(Debug)]
 pub(crate) struct StaticFileDict {
@@ -10,6 +17,10 @@ pub(crate) struct StaticFileDict {
     // Midashi -> DictEntry map
     okuri_ari_dictionary: LruOrderedMap<String, DictEntry>,
     okuri_nashi_dictionary: LruOrderedMap<String, DictEntry>,
+    // 逆引き用の索引。大きな辞書で毎回線形探索しないよう、初めて逆引きする時に作る。
+    reverse_index: OnceLock<ReverseIndex>,
+    // 別スレッドで読み込み中の場合のみSome
+    background_load: Option<BackgroundLoad>,
 }
 
 impl StaticFileDict {
@@ -23,8 +34,51 @@ impl StaticFileDict {
             encode: encode.to_string(),
             okuri_ari_dictionary: dictionary.okuri_ari,
             okuri_nashi_dictionary: dictionary.okuri_nashi,
+            reverse_index: OnceLock::new(),
+            background_load: None,
+        })
+    }
+
+    /// 別スレッドで読み込む辞書を作る。読み込みが終わるまでは空の辞書になる。
+    /// on_completeは読み込みが終わった時に読み込みスレッドから呼ばれる。
+    pub(crate) fn new_in_background(
+        file_path: &str,
+        encode: &str,
+        on_complete: Option<DictionaryLoadCallback>,
+    ) -> Result<Self, CskkError> {
+        let background_load = BackgroundLoad::start(file_path, encode, on_complete)?;
+
+        Ok(StaticFileDict {
+            file_path: String::from(file_path),
+            encode: encode.to_string(),
+            okuri_ari_dictionary: LruOrderedMap::new(),
+            okuri_nashi_dictionary: LruOrderedMap::new(),
+            reverse_index: OnceLock::new(),
+            background_load: Some(background_load),
         })
     }
+
+    fn build_reverse_index(&self) -> ReverseIndex {
+        let mut reverse_index: ReverseIndex = HashMap::new();
+        for is_okuri_ari in [true, false] {
+            for dict_entry in
+                FileDictionary::iter_entries(self, DictionaryEntryOrder::Sorted, is_okuri_ari)
+            {
+                for (_, candidate) in dict_entry.iter_candidates_with_strict_okuri() {
+                    let midashis = reverse_index
+                        .entry(candidate.kouho_text.to_owned())
+                        .or_default();
+                    // 厳密な送り仮名の候補と重複しているものは一度だけ
+                    if !midashis.iter().any(|(okuri_ari, midashi)| {
+                        *okuri_ari == is_okuri_ari && *midashi == dict_entry.midashi
+                    }) {
+                        midashis.push((is_okuri_ari, dict_entry.midashi.to_owned()));
+                    }
+                }
+            }
+        }
+        reverse_index
+    }
 }
 
 impl Dictionary for StaticFileDict {
@@ -50,6 +104,34 @@ impl Dictionary for StaticFileDict {
     fn reload(&mut self) -> Result<(), CskkError> {
         FileDictionary::reload(self)
     }
+
+    fn reverse_lookup(&self, kouho_text: &str) -> Vec<(bool, &DictEntry)> {
+        let reverse_index = self
+            .reverse_index
+            .get_or_init(|| self.build_reverse_index());
+        let mut result = vec![];
+        if let Some(midashis) = reverse_index.get(kouho_text) {
+            for (is_okuri_ari, midashi) in midashis {
+                let dictionary = if *is_okuri_ari {
+                    &self.okuri_ari_dictionary
+                } else {
+                    &self.okuri_nashi_dictionary
+                };
+                if let Some(dict_entry) = dictionary.peek(midashi) {
+                    result.push((*is_okuri_ari, dict_entry));
+                }
+            }
+        }
+        result
+    }
+
+    fn iter_entries<'a>(
+        &'a self,
+        order: DictionaryEntryOrder,
+        is_okuri_ari: bool,
+    ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
+        FileDictionary::iter_entries(self, order, is_okuri_ari)
+    }
 }
 
 impl FileDictionary for StaticFileDict {
@@ -64,6 +146,27 @@ impl FileDictionary for StaticFileDict {
     fn set_dictionary(&mut self, dictionary: DictionaryEntries) {
         self.okuri_ari_dictionary = dictionary.okuri_ari;
         self.okuri_nashi_dictionary = dictionary.okuri_nashi;
+        self.reverse_index = OnceLock::new();
+        self.background_load = None;
+    }
+
+    fn background_load(&self) -> Option<&BackgroundLoad> {
+        self.background_load.as_ref()
+    }
+
+    fn absorb_loaded_entries(&mut self, wait: bool) {
+        if let Some(background_load) = &self.background_load {
+            let finished = background_load.absorb(
+                &mut self.okuri_ari_dictionary,
+                &mut self.okuri_nashi_dictionary,
+                wait,
+            );
+            if finished {
+                // 読み込みが終わる前のエントリで作った索引は作り直す
+                self.reverse_index = OnceLock::new();
+                self.background_load = None;
+            }
+        }
     }
 
     fn get_okuri_nashi_dictionary(&self) -> &LruOrderedMap<String, DictEntry> {
@@ -125,4 +228,20 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    pub fn reverse_lookup() -> Result<(), CskkError> {
+        let static_dict = StaticFileDict::new("tests/data/dictionaries/strict_okuri.dat", "utf-8")?;
+        let result = Dictionary::reverse_lookup(&static_dict, "干");
+        assert_eq!(1, result.len());
+        assert!(result[0].0);
+        assert_eq!("ほs", result[0].1.midashi);
+        assert!(Dictionary::reverse_lookup(&static_dict, "存在しない").is_empty());
+        // 線形探索と同じ結果
+        assert_eq!(
+            FileDictionary::reverse_lookup(&static_dict, "贈").len(),
+            Dictionary::reverse_lookup(&static_dict, "贈").len()
+        );
+        Ok(())
+    }
 }
@@ -1,12 +1,13 @@
 use crate::dictionary::candidate::Candidate;
-use crate::dictionary::file_dictionary::{load_dictionary, DictionaryEntries, FileDictionary};
-use crate::dictionary::{CompositeKey, DictEntry, Dictionary};
+use crate::dictionary::file_dictionary::{
+    load_dictionary, write_dictionary, BackgroundLoad, DictionaryEntries, DictionaryLoadCallback,
+    FileDictionary,
+};
+use crate::dictionary::{CompositeKey, DictEntry, Dictionary, DictionaryEntryOrder};
 use crate::error::CskkError;
-use crate::error::CskkError::Error;
-use encoding_rs::{Encoder, EncoderResult, Encoding};
 use lru_ordered_map::LruOrderedMap;
-use std::fs::{rename, File};
-use std::io::{BufWriter, Write};
+use std::collections::HashMap;
+use std::fs::rename;
 
 ///
 /// User dictionary that can load from file and save entries to file.
@@ -19,12 +20,15 @@ pub(crate) struct UserDictionary {
     // Midashi -> DictEntry map
     okuri_ari_dictionary: LruOrderedMap<String, DictEntry>,
     okuri_nashi_dictionary: LruOrderedMap<String, DictEntry>,
+    // 補完候補の順に使う見出しごとの確定回数。辞書ファイルには保存しない。
+    okuri_ari_use_counts: HashMap<String, u32>,
+    okuri_nashi_use_counts: HashMap<String, u32>,
     // Just bool, because we know this is under mutex.
     has_change: bool,
+    // 別スレッドで読み込み中の場合のみSome
+    background_load: Option<BackgroundLoad>,
 }
 
-const BUF_SIZE: usize = 1024;
-
 impl UserDictionary {
     pub(crate) fn new(file_path: &str, encode: &str) -> Result<Self, CskkError> {
         let dictionary = load_dictionary(file_path, encode.as_bytes())?;
@@ -34,7 +38,65 @@ impl UserDictionary {
             encode: encode.to_string(),
             okuri_ari_dictionary: dictionary.okuri_ari,
             okuri_nashi_dictionary: dictionary.okuri_nashi,
+            okuri_ari_use_counts: HashMap::new(),
+            okuri_nashi_use_counts: HashMap::new(),
+            has_change: false,
+            background_load: None,
+        })
+    }
+
+    /// 見出しのエントリの複製を返す。学習を取り消すために学習前のエントリを取っておく用途。
+    pub(crate) fn peek_entry(&self, okuri: bool, midashi: &str) -> Option<DictEntry> {
+        let midashi = midashi.to_owned();
+        if okuri {
+            self.okuri_ari_dictionary.peek(&midashi).cloned()
+        } else {
+            self.okuri_nashi_dictionary.peek(&midashi).cloned()
+        }
+    }
+
+    /// 見出しのエントリをentryに戻す。entryがNoneならエントリを消す。
+    pub(crate) fn restore_entry(&mut self, okuri: bool, midashi: &str, entry: Option<DictEntry>) {
+        let dictionary = if okuri {
+            &mut self.okuri_ari_dictionary
+        } else {
+            &mut self.okuri_nashi_dictionary
+        };
+        match entry {
+            Some(entry) => dictionary.push(midashi.to_owned(), entry),
+            None => {
+                dictionary.remove(midashi.to_owned());
+            }
+        }
+        let use_counts = if okuri {
+            &mut self.okuri_ari_use_counts
+        } else {
+            &mut self.okuri_nashi_use_counts
+        };
+        if let Some(use_count) = use_counts.get_mut(midashi) {
+            *use_count = use_count.saturating_sub(1);
+        }
+        self.has_change = true;
+    }
+
+    /// 別スレッドで読み込む辞書を作る。読み込みが終わるまでは読み込み中に確定したエントリのみを持つ。
+    /// on_completeは読み込みが終わった時に読み込みスレッドから呼ばれる。
+    pub(crate) fn new_in_background(
+        file_path: &str,
+        encode: &str,
+        on_complete: Option<DictionaryLoadCallback>,
+    ) -> Result<Self, CskkError> {
+        let background_load = BackgroundLoad::start(file_path, encode, on_complete)?;
+
+        Ok(UserDictionary {
+            file_path: String::from(file_path),
+            encode: encode.to_string(),
+            okuri_ari_dictionary: LruOrderedMap::new(),
+            okuri_nashi_dictionary: LruOrderedMap::new(),
+            okuri_ari_use_counts: HashMap::new(),
+            okuri_nashi_use_counts: HashMap::new(),
             has_change: false,
+            background_load: Some(background_load),
         })
     }
 }
@@ -65,49 +127,18 @@ impl Dictionary for UserDictionary {
     /// 辞書ファイルのフォーマットは SKK 16.2 user manual 5.10.7 辞書の書式 に依る
     /// userdictなので送りありエントリも送りなしエントリも最近使用した順に並ぶ。
     fn save_dictionary(&mut self) -> Result<bool, CskkError> {
+        // 読み込み途中の辞書で上書きしないよう、全て読み込んでから保存する
+        self.absorb_loaded_entries(true);
         if self.has_change {
             rename(&self.file_path, format!("{}.BAK", self.file_path))?;
-            let dict_file = File::create(&self.file_path)?;
-
-            let mut stream = BufWriter::new(dict_file);
-            let mut enc = Encoding::for_label(self.encode.as_bytes())
-                .expect("It should be same as encoding name succeeded when loading file.")
-                .new_encoder();
-
-            // Not using. Can't compile on mac.
-            // let encoded = encode_string(
-            //     &mut enc,
-            //     &format!(
-            //         ";; Save on {} \n",
-            //         chrono::offset::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
-            //     ),
-            // )?;
-            // stream.write_all(encoded.as_slice())?;
-
-            let encoded = encode_string(&mut enc, ";; okuri-ari entries.\n")?;
-            stream.write_all(encoded.as_slice())?;
-            for (_, dictentry) in self.okuri_ari_dictionary.iter_lru() {
-                // midashi is ignored here because dictentry holds the same.
-                if let Some(dict_entry) = dictentry {
-                    let mut dict_entry_string = dict_entry.to_string();
-                    dict_entry_string += "\n";
-                    if let Ok(encoded) = encode_string(&mut enc, dict_entry_string.as_mut_str()) {
-                        stream.write_all(encoded.as_slice())?;
-                    }
-                }
-            }
-            let encoded = encode_string(&mut enc, ";; okuri-nasi entries.\n")?;
-            stream.write_all(encoded.as_slice())?;
-            for (_, dictentry) in self.okuri_nashi_dictionary.iter_lru() {
-                if let Some(dict_entry) = dictentry {
-                    let mut dict_entry_string = dict_entry.to_string();
-                    dict_entry_string += "\n";
-                    if let Ok(encoded) = encode_string(&mut enc, dict_entry_string.as_mut_str()) {
-                        stream.write_all(encoded.as_slice())?;
-                    }
-                }
-            }
-            stream.flush()?;
+            write_dictionary(
+                &self.file_path,
+                &self.encode,
+                self.okuri_ari_dictionary.iter_lru().filter_map(|(_k, v)| v),
+                self.okuri_nashi_dictionary
+                    .iter_lru()
+                    .filter_map(|(_k, v)| v),
+            )?;
             self.has_change = false;
             Ok(true)
         } else {
@@ -136,6 +167,12 @@ impl Dictionary for UserDictionary {
                 );
             }
         }
+        let use_counts = if candidate.okuri {
+            &mut self.okuri_ari_use_counts
+        } else {
+            &mut self.okuri_nashi_use_counts
+        };
+        *use_counts.entry(candidate.midashi.to_owned()).or_insert(0) += 1;
         self.has_change = true;
         Ok(true)
     }
@@ -162,6 +199,124 @@ impl Dictionary for UserDictionary {
     fn reload(&mut self) -> Result<(), CskkError> {
         FileDictionary::reload(self)
     }
+
+    fn iter_entries<'a>(
+        &'a self,
+        order: DictionaryEntryOrder,
+        is_okuri_ari: bool,
+    ) -> Box<dyn Iterator<Item = &'a DictEntry> + 'a> {
+        FileDictionary::iter_entries(self, order, is_okuri_ari)
+    }
+
+    fn reverse_lookup(&self, kouho_text: &str) -> Vec<(bool, &DictEntry)> {
+        FileDictionary::reverse_lookup(self, kouho_text)
+    }
+
+    fn add_candidate(&mut self, candidate: &Candidate) -> Result<bool, CskkError> {
+        let dictionary = if candidate.okuri {
+            &mut self.okuri_ari_dictionary
+        } else {
+            &mut self.okuri_nashi_dictionary
+        };
+
+        match dictionary.get_mut(&candidate.midashi) {
+            Some(dict_entry) => {
+                dict_entry.add_candidate(candidate);
+            }
+            None => {
+                dictionary.push(
+                    candidate.midashi.to_owned(),
+                    DictEntry::new(&candidate.midashi, candidate),
+                );
+            }
+        }
+        self.has_change = true;
+        Ok(true)
+    }
+
+    fn move_candidate(
+        &mut self,
+        midashi: &str,
+        strict_okuri: Option<&str>,
+        kouho_text: &str,
+        new_position: usize,
+    ) -> Result<bool, CskkError> {
+        let dictionary = if DictEntry::is_okuri_ari_midashi(midashi) {
+            &mut self.okuri_ari_dictionary
+        } else {
+            &mut self.okuri_nashi_dictionary
+        };
+        let mut moved = false;
+        if let Some(dict_entry) = dictionary.get_mut(&midashi.to_owned()) {
+            moved = dict_entry.move_candidate(strict_okuri.unwrap_or(""), kouho_text, new_position);
+        }
+        self.has_change |= moved;
+        Ok(moved)
+    }
+
+    fn delete_candidate(
+        &mut self,
+        midashi: &str,
+        strict_okuri: Option<&str>,
+        kouho_text: &str,
+    ) -> Result<bool, CskkError> {
+        let dictionary = if DictEntry::is_okuri_ari_midashi(midashi) {
+            &mut self.okuri_ari_dictionary
+        } else {
+            &mut self.okuri_nashi_dictionary
+        };
+        let mut deleted = false;
+        let mut is_empty = false;
+        if let Some(dict_entry) = dictionary.get_mut(&midashi.to_owned()) {
+            deleted = dict_entry.delete_candidate(strict_okuri, kouho_text);
+            is_empty = dict_entry.is_empty();
+        }
+        if is_empty {
+            // 候補のないエントリは辞書に残さない。
+            dictionary.remove(midashi.to_owned());
+        }
+        self.has_change |= deleted;
+        Ok(deleted)
+    }
+
+    fn delete_entry(&mut self, midashi: &str) -> Result<bool, CskkError> {
+        let dictionary = if DictEntry::is_okuri_ari_midashi(midashi) {
+            &mut self.okuri_ari_dictionary
+        } else {
+            &mut self.okuri_nashi_dictionary
+        };
+        let deleted = dictionary.remove(midashi.to_owned()).is_some();
+        self.has_change |= deleted;
+        Ok(deleted)
+    }
+
+    /// 既存のエントリには含まれない候補のみ末尾に追加し、新しいエントリは最近使われたものとして追加する。
+    fn import_entries(&mut self, file_path: &str, encode: &str) -> Result<usize, CskkError> {
+        let imported = load_dictionary(file_path, encode.as_bytes())?;
+        let mut added = 0;
+        for (imported_map, dictionary) in [
+            (imported.okuri_ari, &mut self.okuri_ari_dictionary),
+            (imported.okuri_nashi, &mut self.okuri_nashi_dictionary),
+        ] {
+            // 読み込み元のLRU順を保つため古いものから入れる。
+            for (_, imported_entry) in imported_map.iter_lru().rev() {
+                if let Some(imported_entry) = imported_entry {
+                    match dictionary.get_mut(&imported_entry.midashi) {
+                        Some(dict_entry) => {
+                            added += dict_entry.merge(imported_entry.to_owned());
+                        }
+                        None => {
+                            added += imported_entry.iter_candidates_with_strict_okuri().count();
+                            dictionary
+                                .push(imported_entry.midashi.to_owned(), imported_entry.to_owned());
+                        }
+                    }
+                }
+            }
+        }
+        self.has_change |= added > 0;
+        Ok(added)
+    }
 }
 
 impl FileDictionary for UserDictionary {
@@ -176,6 +331,23 @@ impl FileDictionary for UserDictionary {
     fn set_dictionary(&mut self, dictionary: DictionaryEntries) {
         self.okuri_ari_dictionary = dictionary.okuri_ari;
         self.okuri_nashi_dictionary = dictionary.okuri_nashi;
+        self.background_load = None;
+    }
+
+    fn background_load(&self) -> Option<&BackgroundLoad> {
+        self.background_load.as_ref()
+    }
+
+    fn absorb_loaded_entries(&mut self, wait: bool) {
+        if let Some(background_load) = &self.background_load {
+            if background_load.absorb(
+                &mut self.okuri_ari_dictionary,
+                &mut self.okuri_nashi_dictionary,
+                wait,
+            ) {
+                self.background_load = None;
+            }
+        }
     }
 
     fn get_okuri_nashi_dictionary(&self) -> &LruOrderedMap<String, DictEntry> {
@@ -185,42 +357,24 @@ impl FileDictionary for UserDictionary {
     fn get_okuri_ari_dictionary(&self) -> &LruOrderedMap<String, DictEntry> {
         &self.okuri_ari_dictionary
     }
-}
 
-fn encode_string(encoder: &mut Encoder, to_encode: &str) -> Result<Vec<u8>, CskkError> {
-    let mut encoded_vec = Vec::with_capacity(BUF_SIZE);
-    let mut source = to_encode;
-    let mut tmp_buf = Vec::with_capacity(BUF_SIZE);
-    loop {
-        let (result, read) =
-            encoder.encode_from_utf8_to_vec_without_replacement(source, &mut tmp_buf, true);
-        if read == 0 {
-            return Err(Error(
-                "Cannot read on encoding. Give up whole string.".to_string(),
-            ));
-        }
-        match result {
-            EncoderResult::Unmappable(_char) => {
-                return Err(Error("Encoding failed. Give up whole string.".to_string()));
-            }
-            EncoderResult::InputEmpty => {
-                encoded_vec.append(&mut tmp_buf);
-                break;
-            }
-            EncoderResult::OutputFull => {
-                encoded_vec.append(&mut tmp_buf);
-                source = &source[read..];
-            }
-        }
+    fn use_count(&self, is_okuri_ari: bool, midashi: &str) -> u32 {
+        let use_counts = if is_okuri_ari {
+            &self.okuri_ari_use_counts
+        } else {
+            &self.okuri_nashi_use_counts
+        };
+        use_counts.get(midashi).copied().unwrap_or(0)
     }
-    Ok(encoded_vec)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use encoding_rs::Encoding;
     use encoding_rs_io::DecodeReaderBytesBuilder;
-    use std::io::{BufRead, BufReader};
+    use std::fs::File;
+    use std::io::{BufRead, BufReader, Write};
     use tempfile::NamedTempFile;
 
     #[test]
@@ -325,4 +479,71 @@ mod test {
         user_dictionary.save_dictionary()?;
         Ok(())
     }
+
+    #[test]
+    fn edit_entries() -> Result<(), CskkError> {
+        let file = NamedTempFile::new()?;
+        let filename = file.path().to_str().unwrap();
+        let mut user_dictionary = UserDictionary::new(filename, "utf-8")?;
+        let candidate = Candidate::new(
+            "かんがe".to_string(),
+            Some("え".to_string()),
+            true,
+            "考".to_string(),
+            Some("think".to_string()),
+            "考".to_string(),
+        );
+        user_dictionary.add_candidate(&candidate)?;
+        let candidate = Candidate::new(
+            "かんがe".to_string(),
+            None,
+            true,
+            "勘".to_string(),
+            None,
+            "勘".to_string(),
+        );
+        user_dictionary.add_candidate(&candidate)?;
+        assert!(user_dictionary.move_candidate("かんがe", None, "勘", 1)?);
+        let entry = Dictionary::iter_entries(&user_dictionary, DictionaryEntryOrder::Lru, true)
+            .next()
+            .unwrap();
+        assert_eq!("かんがe /考;think/勘/[え/考;think/]/", entry.to_string());
+
+        assert!(user_dictionary.delete_candidate("かんがe", None, "考")?);
+        assert!(user_dictionary.delete_candidate("かんがe", None, "勘")?);
+        assert_eq!(
+            0,
+            Dictionary::iter_entries(&user_dictionary, DictionaryEntryOrder::Lru, true).count()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn import_entries() -> Result<(), CskkError> {
+        let file = NamedTempFile::new()?;
+        let filename = file.path().to_str().unwrap();
+        let mut user_dictionary = UserDictionary::new(filename, "utf-8")?;
+        let candidate = Candidate::new(
+            "あい".to_string(),
+            None,
+            false,
+            "愛".to_string(),
+            None,
+            "愛".to_string(),
+        );
+        user_dictionary.select_candidate(&candidate)?;
+
+        let mut import_file = NamedTempFile::new()?;
+        writeln!(import_file, ";; okuri-nasi entries.")?;
+        writeln!(import_file, "あい /相/愛/")?;
+        writeln!(import_file, "うえ /上/")?;
+        let import_filename = import_file.path().to_str().unwrap();
+        assert_eq!(2, user_dictionary.import_entries(import_filename, "utf-8")?);
+        let entries =
+            Dictionary::iter_entries(&user_dictionary, DictionaryEntryOrder::Sorted, false)
+                .map(|entry| entry.to_string())
+                .collect::<Vec<String>>();
+        assert_eq!(vec!["あい /愛/相/", "うえ /上/"], entries);
+        Ok(())
+    }
 }
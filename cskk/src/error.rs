@@ -20,6 +20,11 @@ pub enum CskkError {
         source: toml::de::Error,
     },
     #[error(transparent)]
+    TomlSerializeError {
+        #[from]
+        source: toml::ser::Error,
+    },
+    #[error(transparent)]
     IoError {
         #[from]
         source: std::io::Error,
@@ -0,0 +1,71 @@
+use crate::dictionary::candidate::Candidate;
+use crate::skk_modes::{CompositionMode, InputMode};
+
+///
+/// ホスト側に知らせるイベント。[crate::CskkContext::poll_events]で取り出す。
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CskkEvent {
+    /// 確定した文字列。ホスト側ではこれを入力する。
+    Commit(String),
+    /// 既に確定した文字列のうち、ホスト側でカーソルの直前から消すべき文字数。同時に返すCommitの前に消す。
+    DeleteSurrounding(usize),
+    /// キー入力で入力モードかCompositionModeが変わった。変わった後のモードを持つ。
+    ModeChanged {
+        input_mode: InputMode,
+        composition_mode: CompositionMode,
+    },
+    /// キー入力で変換候補の一覧か、その中で選択している候補が変わった。
+    CandidatesChanged,
+    /// 辞書が更新された。
+    DictionaryUpdated {
+        kind: DictionaryUpdateKind,
+        midashi: String,
+        kouho_text: String,
+    },
+    /// 辞書登録の入れ子が深すぎる場合など、操作を受け付けられなかったので警告音等で知らせる。
+    Beep,
+}
+
+///
+/// 辞書の更新の種類
+///
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DictionaryUpdateKind {
+    /// 登録モードで新しく登録した
+    Registered,
+    /// 確定した候補を学習した
+    Learned,
+    /// 候補を削除した
+    Purged,
+}
+
+impl CskkEvent {
+    pub(crate) fn dictionary_updated(kind: DictionaryUpdateKind, candidate: &Candidate) -> Self {
+        CskkEvent::DictionaryUpdated {
+            kind,
+            midashi: candidate.midashi.to_owned(),
+            kouho_text: candidate.kouho_text.to_owned(),
+        }
+    }
+}
+
+///
+/// [crate::CskkContext::add_observer]で登録したobserverに知らせる変化の種類
+///
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CskkChange {
+    /// 入力モードが変わった
+    InputModeChanged,
+    /// CompositionModeが変わった
+    CompositionModeChanged,
+    /// 変換候補の一覧か、その中で選択している候補が変わった
+    CandidatesChanged,
+    /// 入力中の表示が変わった
+    PreeditChanged,
+}
+
+/// キー入力の処理で状態が変わった時に、変化の種類ごとに呼ばれる。
+pub type CskkObserver = Box<dyn FnMut(CskkChange) + Send + 'static>;
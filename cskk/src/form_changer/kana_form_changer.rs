@@ -245,6 +245,26 @@ impl KanaFormChanger {
     pub(crate) fn kana_to_okuri_prefix(kana: &char) -> Option<char> {
         KANA_ROM_MAP.get(kana).copied()
     }
+
+    ///
+    /// textの末尾から続く、input_modeで入力されたかなを最大limit文字まで、ひらがなの読みにして返す。
+    /// Hiraganaならひらがなを、Katakanaならカタカナを対象とし、長音記号はどちらでも含める。
+    ///
+    pub(crate) fn trailing_reading_kana(text: &str, input_mode: InputMode, limit: usize) -> String {
+        let mut reading = text
+            .chars()
+            .rev()
+            .map_while(|c| match (input_mode, c) {
+                (_, 'ー') => Some(c),
+                (InputMode::Hiragana, 'ぁ'..='ゖ') => Some(c),
+                (InputMode::Katakana, 'ァ'..='ヶ') => char::from_u32(c as u32 - 0x60),
+                _ => None,
+            })
+            .take(limit)
+            .collect::<Vec<char>>();
+        reading.reverse();
+        reading.into_iter().collect()
+    }
 }
 
 #[cfg(test)]
@@ -272,6 +292,26 @@ impl KanaFormChanger {
 mod tests {
     use super::*;
 
+    #[test]
+    fn trailing_reading_kana() {
+        assert_eq!(
+            "かんじ",
+            KanaFormChanger::trailing_reading_kana("漢字かんじ", InputMode::Hiragana, usize::MAX)
+        );
+        assert_eq!(
+            "じ",
+            KanaFormChanger::trailing_reading_kana("かんじ", InputMode::Hiragana, 1)
+        );
+        assert_eq!(
+            "こーひー",
+            KanaFormChanger::trailing_reading_kana("あコーヒー", InputMode::Katakana, usize::MAX)
+        );
+        assert_eq!(
+            "",
+            KanaFormChanger::trailing_reading_kana("カタカナ", InputMode::Hiragana, usize::MAX)
+        );
+    }
+
     #[test]
     fn sanity_check() {
         let changer = KanaFormChanger::test_kana_form_changer();
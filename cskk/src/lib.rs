@@ -12,22 +12,27 @@ extern crate xkbcommon;
 use crate::command_handler::ConfigurableCommandHandler;
 use crate::command_handler::Instruction;
 use crate::config::CskkConfig;
-use crate::cskkstate::{CskkState, CskkStateInfo};
+use crate::cskkstate::{CandidatePage, CompletionSuggestion, CskkState, CskkStateInfo};
 use crate::dictionary::{
-    confirm_candidate, get_all_candidates, numeric_entry_count, numeric_string_count,
-    purge_candidate, replace_numeric_string, to_composite_to_numeric_dict_key, CskkDictionary,
-    CskkDictionaryType, Dictionary,
+    backup_learning, confirm_candidate, get_all_candidates, get_auto_okuri_candidates,
+    is_kakutei_candidate, numeric_entry_count, numeric_string_count, purge_candidate,
+    replace_numeric_string, restore_learning, to_composite_to_numeric_dict_key, CskkDictionary,
+    CskkDictionaryType, Dictionary, LearningBackup,
+};
+use crate::dictionary::{
+    get_all_complete, get_fuzzy_candidates, get_readings_for, get_reconversion_reading,
+    get_sagyo_henkaku_candidates, Candidate, CompositeKey, DictionaryReading,
 };
-use crate::dictionary::{get_all_complete, Candidate};
 use crate::error::CskkError;
+use crate::event::{CskkChange, CskkEvent, CskkObserver, DictionaryUpdateKind};
 use crate::kana_builder::KanaBuilder;
 use crate::keyevent::KeyEventSeq;
 use crate::keyevent::{CskkKeyEvent, SkkKeyModifier};
 use crate::rule::{CskkRule, CskkRuleMetadata, CskkRuleMetadataEntry};
 use crate::skk_modes::{has_rom2kana_conversion, CompositionMode};
-use crate::skk_modes::{CommaStyle, InputMode, PeriodStyle};
+use crate::skk_modes::{CommaStyle, FocusOutPolicy, InputMode, PeriodStyle};
 use form_changer::{AsciiFormChanger, KanaFormChanger};
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -43,6 +48,7 @@ pub mod cskkstate;
 pub mod dictionary;
 mod env;
 pub mod error;
+pub mod event;
 mod form_changer;
 mod kana_builder;
 pub mod keyevent;
@@ -53,6 +59,14 @@ mod testhelper;
 
 // 実用上5でも問題なかった。安全率2で。
 const REGISTER_MODE_STACK_MAX: usize = 10;
+// UndoPreeditで戻れるキー入力の数。
+const PREEDIT_HISTORY_MAX: usize = 100;
+// UndoPreeditの履歴に持つ変換候補の数の合計の上限。変換候補の一覧は大きくなりうるので、キー入力の数とは別に抑える。
+const PREEDIT_HISTORY_CANDIDATES_MAX: usize = 1000;
+// poll_eventsで取り出されずに溜めておくイベントの数。poll_outputのみを使うホストでも溢れないよう古いものから捨てる。
+const EVENT_QUEUE_MAX: usize = 256;
+// snapshotの形式のバージョン。形式を変えたら上げる。
+const SNAPSHOT_VERSION: u32 = 1;
 
 ///
 /// CSKK のメインの構造体。このcontextをに対しキー入力を行って変換された状態を得る。
@@ -71,9 +85,75 @@ pub struct CskkContext {
     dictionaries: Vec<Arc<CskkDictionary>>,
     config: CskkConfig,
     composition_triggers: HashSet<Keysym>,
+    // 処理中のキー入力で変換候補を確定した場合、確定を取り消して変換候補の一覧に戻るための記録を持つ。
+    confirmation_record: Option<ConfirmationRecord>,
+    // 直前のキー入力で確定した場合の記録。そのキー入力の次のキー入力の処理中のみ持つ。
+    last_confirmation_record: Option<ConfirmationRecord>,
+    // 既にpollされた確定済み文字列のうち、ホスト側でカーソルの前から消すべき文字数。
+    pending_delete_surrounding: usize,
+    // 再変換中のみ、確定した時にホスト側で置き換える再変換元の文字列の文字数を持つ。
+    reconversion_target_len: Option<usize>,
+    // ホスト側から渡された、既にpollされてカーソルの直前にある文字列。次のキー入力の処理でのみ使う。
+    surrounding_text: String,
+    // UndoPreeditで戻すための、入力中の状態が変わったキー入力の前のstate_stack。確定すると捨てる。
+    preedit_undo_history: VecDeque<Vec<CskkState>>,
+    // RedoPreeditでやり直すための、UndoPreeditで戻す前のstate_stack。
+    preedit_redo_history: Vec<Vec<CskkState>>,
+    // 現在のキー入力でUndoPreeditかRedoPreeditをしたかどうか。その場合は履歴に積まない。
+    preedit_history_moved: bool,
+    // poll_eventsで返すイベントのうち、確定文字列と消すべき文字数以外のもの。
+    events: VecDeque<CskkEvent>,
+    // add_observerで登録されたobserverとそのid。
+    observers: Vec<(usize, CskkObserver)>,
+    next_observer_id: usize,
+    // FocusOutKeepでfocus_outした時に保存した入力中の状態。focus_inで戻す。
+    focus_out_state_stack: Option<Vec<CskkState>>,
     //rule: CskkRuleMetadataEntry,
 }
 
+///
+/// 変換候補を確定した時の、確定を取り消すための記録
+///
+struct ConfirmationRecord {
+    // 確定する前の▼モードの状態
+    state: CskkState,
+    // 確定前のCompositionMode。確定辞書での確定では、次候補のキーかどうかはこのモードのルールで判断する。
+    composition_mode: CompositionMode,
+    // 確定した文字列
+    output: String,
+    // 確定した時のstate_stackの深さ
+    stack_depth: usize,
+    // 確定辞書の候補で確定したか
+    is_kakutei: bool,
+    // 確定で学習する前のユーザー辞書のエントリ
+    learning_backups: Vec<LearningBackup>,
+}
+
+///
+/// 状態の変化を知らせるために、変える前の状態から比べる部分だけを持っておくもの
+///
+struct StateChangeBase {
+    input_mode: InputMode,
+    composition_mode: CompositionMode,
+    selection_pointer: usize,
+    candidate_outputs: Vec<String>,
+}
+
+///
+/// snapshotで保存する入力中の状態。辞書は含めず、restoreしたcontextの辞書をそのまま使う。
+///
+#[derive(Serialize, Deserialize)]
+struct ContextSnapshot {
+    version: u32,
+    state_stack: Vec<CskkState>,
+}
+
+// 形式の異なるsnapshotを読もうとしていないか、先にバージョンだけ読んで確かめる。
+#[derive(Deserialize)]
+struct ContextSnapshotVersion {
+    version: u32,
+}
+
 /// Test purpose only.
 #[deprecated(
     since = "2.0.0",
@@ -135,7 +215,7 @@ pub fn skk_context_get_composition_mode_rs(context: &CskkContext) -> Composition
 /// 将来的にはcontextのmethodに置き換える。
 ///
 pub fn skk_context_reset_rs(context: &mut CskkContext) {
-    context.reset_state_stack();
+    context.with_change_notification(|context| context.reset_state_stack());
 }
 
 /// テスト用途
@@ -144,12 +224,12 @@ pub fn skk_context_set_composition_mode(
     context: &mut CskkContext,
     composition_mode: CompositionMode,
 ) {
-    context.set_composition_mode(composition_mode)
+    context.with_change_notification(|context| context.set_composition_mode(composition_mode))
 }
 
 /// 他のステートとの整合性は無視される。
 pub fn skk_context_set_input_mode_rs(context: &mut CskkContext, input_mode: InputMode) {
-    context.set_input_mode(input_mode)
+    context.with_change_notification(|context| context.set_input_mode(input_mode))
 }
 
 ///
@@ -268,6 +348,10 @@ pub fn skk_context_get_current_candidate_cursor_position_rs(
 //     note = "Not proper for Rust interface. See rustdoc for details."
 // )]
 pub fn skk_context_select_candidate_at_rs(context: &mut CskkContext, i: i32) -> bool {
+    context.with_change_notification(|context| select_candidate_at(context, i))
+}
+
+fn select_candidate_at(context: &mut CskkContext, i: i32) -> bool {
     let len = context
         .current_state_ref()
         .get_candidate_list()
@@ -303,11 +387,14 @@ pub fn skk_context_select_candidate_at_rs(context: &mut CskkContext, i: i32) ->
 //     note = "Not proper for Rust interface. See rustdoc for details."
 // )]
 pub fn skk_context_confirm_candidate_at_rs(context: &mut CskkContext, i: usize) -> bool {
-    if context.current_state().set_candidate_pointer_index(i) {
-        context.confirm_current_composition_candidate();
-        return true;
-    }
-    false
+    context.with_change_notification(|context| {
+        if context.current_state().set_candidate_pointer_index(i) {
+            context.clear_preedit_history();
+            context.confirm_current_composition_candidate();
+            return true;
+        }
+        false
+    })
 }
 
 pub fn skk_context_set_auto_start_henkan_keywords_rs(
@@ -355,6 +442,68 @@ impl CskkContext {
         self.retrieve_output(true)
     }
 
+    ///
+    /// 既に[poll_output]で返した確定済み文字列のうち、取り消されたのでホスト側でカーソルの直前から消すべき文字数を返し、0に戻す。
+    ///
+    /// 確定辞書で確定した直後やUndoKakuteiで確定を取り消した時、BackwardSetHenkanPointで確定済みのかなを読みにした時などに使われる。消すべきものがなければ0を返す。
+    ///
+    pub fn poll_delete_surrounding(&mut self) -> usize {
+        std::mem::take(&mut self.pending_delete_surrounding)
+    }
+
+    ///
+    /// キー入力の処理で入力モード、CompositionMode、変換候補の一覧やその中で選択している候補、入力中の表示が変わった時に呼ばれるobserverを登録する。
+    ///
+    /// observerは変わったものごとに[CskkChange]を引数として、キー入力の処理の後に呼ばれる。
+    /// [skk_context_reset_rs]や[start_reconversion], [restore], [focus_out]などキー入力以外で状態を変えるメソッドの後にも同様に呼ばれる。
+    /// observerはこのcontextの処理の途中で呼ばれるので、observerの中から同じcontextのメソッドを呼んではならない。
+    /// [remove_observer]で登録を解除するためのidを返す。
+    ///
+    pub fn add_observer(&mut self, observer: CskkObserver) -> usize {
+        let id = self.next_observer_id;
+        self.next_observer_id += 1;
+        self.observers.push((id, observer));
+        id
+    }
+
+    ///
+    /// [add_observer]で登録したobserverの登録を解除する。該当するobserverがなければfalseを返す。
+    ///
+    pub fn remove_observer(&mut self, id: usize) -> bool {
+        let count = self.observers.len();
+        self.observers.retain(|(observer_id, _)| *observer_id != id);
+        count != self.observers.len()
+    }
+
+    ///
+    /// 前回からのイベントを古いものから順に取り出す。
+    ///
+    /// [poll_delete_surrounding]の文字数と[poll_output]の確定文字列も、それぞれDeleteSurroundingとCommitとしてこの順で最後に含める。
+    /// そのためこれを使う場合は[poll_output]と[poll_delete_surrounding]を呼ぶ必要はない。
+    ///
+    pub fn poll_events(&mut self) -> Vec<CskkEvent> {
+        let mut events = self.events.drain(..).collect::<Vec<_>>();
+        let delete_surrounding = self.poll_delete_surrounding();
+        if delete_surrounding > 0 {
+            events.push(CskkEvent::DeleteSurrounding(delete_surrounding));
+        }
+        if let Some(output) = self.poll_output() {
+            events.push(CskkEvent::Commit(output));
+        }
+        events
+    }
+
+    ///
+    /// 既に[poll_output]で返してホスト側でカーソルの直前にある文字列を渡す。
+    ///
+    /// BackwardSetHenkanPointなどで、pollされていない確定済み文字列より前まで遡るために使う。
+    /// 渡した文字列は次の[process_key_event]の処理でのみ使われるので、ホスト側ではキー入力を渡す直前に毎回渡す。
+    /// 遡って読みにした文字数は[poll_delete_surrounding]で返す。
+    ///
+    pub fn set_surrounding_text(&mut self, text_before_cursor: &str) {
+        self.surrounding_text = text_before_cursor.to_owned();
+    }
+
     ///
     /// pollされていない入力を状態に応じて修飾して返す。
     ///
@@ -386,7 +535,13 @@ impl CskkContext {
     pub fn get_preedit_detail(&self) -> Vec<CskkStateInfo> {
         let mut result = Vec::new();
         for state in &self.state_stack {
-            result.push(state.preedit_detail(&self.kana_form_changer, state.input_mode))
+            let mut state_info = state.preedit_detail(&self.kana_form_changer, state.input_mode);
+            if let CskkStateInfo::CompositionSelection(ref mut composition_selection_data) =
+                state_info
+            {
+                composition_selection_data.page = self.candidate_page(state);
+            }
+            result.push(state_info)
         }
 
         result
@@ -463,22 +618,130 @@ impl CskkContext {
 
     ///
     /// 現在のraw_to_compositeから変換候補をリストにして、変換候補を指すポインタを0に戻す。
+    /// 設定があればサ行変格活用の語幹による候補、送り仮名を推定した送りありエントリの候補を続ける。
+    /// 辞書に候補が無ければ打ち間違いを訂正した読みの候補を、設定があれば末尾に読みのかな変換を加える。
     ///
     fn update_candidate_list(&mut self) {
         let composite_key = self.current_state_ref().get_composite_key();
-        let candidates = get_all_candidates(&self.dictionaries, &composite_key);
+        let mut candidates = get_all_candidates(&self.dictionaries, &composite_key);
+        if self.config.sagyo_henkaku_search {
+            for candidate in get_sagyo_henkaku_candidates(&self.dictionaries, &composite_key) {
+                if !candidates
+                    .iter()
+                    .any(|existing| existing.kouho_text == candidate.kouho_text)
+                {
+                    candidates.push(candidate);
+                }
+            }
+        }
+        if self.config.auto_okuri_process && self.is_converting_kana_reading() {
+            for candidate in get_auto_okuri_candidates(&self.dictionaries, &composite_key) {
+                if !candidates
+                    .iter()
+                    .any(|existing| existing.output == candidate.output)
+                {
+                    candidates.push(candidate);
+                }
+            }
+        }
+        if candidates.is_empty() {
+            candidates = self.corrected_candidates(&composite_key);
+        }
+        let kana_form_candidates = self.kana_form_candidates(&composite_key, &candidates);
+        candidates.extend(kana_form_candidates);
         self.current_state().set_new_candidate_list(candidates);
     }
 
+    ///
+    /// かなの読みの変換中かどうか。Abbreviationやその他のモードではfalse。
+    ///
+    fn is_converting_kana_reading(&self) -> bool {
+        matches!(
+            self.current_state_ref().composition_mode,
+            CompositionMode::PreComposition | CompositionMode::PreCompositionOkurigana
+        )
+    }
+
+    ///
+    /// 打ち間違いを訂正した読みから変換候補を探す。
+    /// 設定で無効な場合や、かなの読みの変換でない場合は空。
+    ///
+    fn corrected_candidates(&self, composite_key: &CompositeKey) -> Vec<Candidate> {
+        let max_cost = self.config.fuzzy_fallback_max_cost;
+        if max_cost == 0 || !self.is_converting_kana_reading() {
+            return vec![];
+        }
+        get_fuzzy_candidates(&self.dictionaries, composite_key, max_cost)
+    }
+
+    ///
+    /// 読みを設定された入力モードのかなに変換した候補を返す。既にcandidatesにある出力は除く。
+    /// かなの読みの変換でない場合は空。
+    ///
+    fn kana_form_candidates(
+        &self,
+        composite_key: &CompositeKey,
+        candidates: &[Candidate],
+    ) -> Vec<Candidate> {
+        let mut result: Vec<Candidate> = vec![];
+        if !self.is_converting_kana_reading() {
+            return result;
+        }
+        for input_mode in &self.config.kana_form_candidates {
+            if !matches!(
+                input_mode,
+                InputMode::Hiragana | InputMode::Katakana | InputMode::HankakuKatakana
+            ) {
+                continue;
+            }
+            let kana_form = self
+                .kana_form_changer
+                .adjust_kana_string(*input_mode, composite_key.get_to_composite());
+            if candidates
+                .iter()
+                .chain(result.iter())
+                .any(|candidate| candidate.output == kana_form)
+            {
+                continue;
+            }
+            result.push(Candidate::from_kana_form(composite_key, kana_form));
+        }
+        result
+    }
+
     ///
     /// 現在のraw_to_compositeから補完候補をリストにして、候補ポインタを0にする。
     ///
     fn update_completion_list(&mut self) {
         let composite_key = self.current_state_ref().get_composite_key();
-        let candidates = get_all_complete(&self.dictionaries, &composite_key);
+        let candidates = get_all_complete(&self.dictionaries, &composite_key, None);
         self.current_state().set_new_candidate_list(candidates);
     }
 
+    ///
+    /// 現在の読みから補完候補を設定した件数だけ探し、入力中の補完候補とする。
+    /// PreComposition(Abbreviationを含む)の時以外は補完候補を消す。
+    ///
+    fn update_completion_suggestions(&mut self) {
+        for state in self.state_stack.iter_mut() {
+            state.set_completion_suggestions(vec![]);
+        }
+        let count = self.config.dynamic_completion_count;
+        let current_state = self.current_state_ref();
+        if count == 0
+            || !matches!(
+                current_state.composition_mode,
+                CompositionMode::PreComposition | CompositionMode::Abbreviation
+            )
+            || current_state.get_to_composite_string().is_empty()
+        {
+            return;
+        }
+        let composite_key = current_state.get_composite_key();
+        let suggestions = get_all_complete(&self.dictionaries, &composite_key, Some(count));
+        self.current_state().set_completion_suggestions(suggestions);
+    }
+
     #[allow(unused_must_use)]
     fn purge_current_composition_candidate(&mut self) {
         if let Ok(current_candidate) = self
@@ -492,42 +755,86 @@ impl CskkContext {
                 .get_candidate_list()
                 .get_current_to_composite()
                 .to_owned();
+            let mut updated = false;
             for cskkdict in self.dictionaries.iter_mut() {
-                purge_candidate(cskkdict, &composite_key, &current_candidate);
+                updated |=
+                    purge_candidate(cskkdict, &composite_key, &current_candidate).unwrap_or(false);
+            }
+            if updated {
+                self.push_event(CskkEvent::dictionary_updated(
+                    DictionaryUpdateKind::Purged,
+                    &current_candidate,
+                ));
             }
         } else {
             log::warn!(
                 "Tried to purge candidate when current candidate is not available. Skipping."
-            )
+            );
+            self.push_event(CskkEvent::Beep);
         }
 
         self.current_state().clear_all();
     }
 
     // TODO: make this internal to cskkstate state内でfieldの齟齬が起きないようにcskkstate内の関数にする
-    #[allow(unused_must_use)]
     fn confirm_current_composition_candidate(&mut self) {
+        self.confirm_current_composition_candidate_inner(Some(DictionaryUpdateKind::Learned));
+    }
+
+    // learningがNoneの時は辞書に学習せずに確定する。
+    // 学習で辞書が更新された時は、learningの種類のDictionaryUpdatedイベントを積む。
+    fn confirm_current_composition_candidate_inner(
+        &mut self,
+        learning: Option<DictionaryUpdateKind>,
+    ) {
         if let Ok(current_candidate) = self
             .current_state_ref()
             .get_candidate_list()
             .get_current_candidate()
         {
             let current_candidate = current_candidate.to_owned();
-
-            for cskkdict in self.dictionaries.iter_mut() {
-                confirm_candidate(cskkdict, &current_candidate);
+            let state_before_confirmation = self.current_state_ref().clone();
+            let confirmed_len = self.current_state_ref().get_confirmed_string().len();
+
+            let mut learning_backups = vec![];
+            if let Some(kind) = learning {
+                let mut updated = false;
+                for cskkdict in self.dictionaries.iter_mut() {
+                    learning_backups.extend(backup_learning(cskkdict, &current_candidate));
+                    updated |= confirm_candidate(cskkdict, &current_candidate).unwrap_or(false);
+                }
+                if updated {
+                    self.push_event(CskkEvent::dictionary_updated(kind, &current_candidate));
+                }
             }
 
-            let composited_okuri = self.kana_form_changer.adjust_kana_string(
-                self.current_state_ref().input_mode,
-                self.current_state_ref().get_okuri_string(),
-            );
+            let okuri = self
+                .current_state_ref()
+                .get_completion_okuri()
+                .unwrap_or_else(|| self.current_state_ref().get_okuri_string());
+            let composited_okuri = self
+                .kana_form_changer
+                .adjust_kana_string(self.current_state_ref().input_mode, okuri);
             let composited_kanji_and_okuri = current_candidate.output + &composited_okuri;
 
+            self.replace_reconversion_target();
             let current_state = self.current_state();
             current_state.push_string(&composited_kanji_and_okuri);
             current_state.clear_unconfirmed();
             current_state.reset_to_default_composition_mode();
+
+            if state_before_confirmation.composition_mode == CompositionMode::CompositionSelection {
+                let output =
+                    self.current_state_ref().get_confirmed_string()[confirmed_len..].to_owned();
+                self.confirmation_record = Some(ConfirmationRecord {
+                    state: state_before_confirmation,
+                    composition_mode: CompositionMode::CompositionSelection,
+                    output,
+                    stack_depth: self.state_stack.len(),
+                    is_kakutei: false,
+                    learning_backups,
+                });
+            }
         } else {
             log::warn!(
                 "Tried to confirm candidate when current candidate is not available. Skipping."
@@ -545,6 +852,7 @@ impl CskkContext {
         } else {
             self.convert_kana_in_input_mode(&kana_like, temporary_input_mode)
         };
+        self.replace_reconversion_target();
         self.current_state()
             .push_string_for_composition_mode(&adjusted, CompositionMode::Direct);
         self.current_state().clear_unconfirmed();
@@ -568,12 +876,19 @@ impl CskkContext {
             );
             self.state_stack
                 .push(CskkState::new(InputMode::Hiragana, CompositionMode::Direct))
+        } else {
+            self.push_event(CskkEvent::Beep);
         }
     }
 
     /// Reset the state stack.
     /// The top compositionmode is reset to default, but inputmode remains.
     fn reset_state_stack(&mut self) {
+        self.confirmation_record = None;
+        self.last_confirmation_record = None;
+        self.reconversion_target_len = None;
+        self.surrounding_text.clear();
+        self.clear_preedit_history();
         while self.state_stack.len() > 1 {
             self.state_stack.pop();
         }
@@ -655,7 +970,9 @@ impl CskkContext {
                         .add_new_candidates_for_existing_string_to_composite(candidates);
                 }
 
-                self.confirm_current_composition_candidate();
+                self.confirm_current_composition_candidate_inner(Some(
+                    DictionaryUpdateKind::Registered,
+                ));
             }
         }
     }
@@ -729,14 +1046,52 @@ impl CskkContext {
             // TODO: from ueno/libskk's comment, returning false for all release might need to be reconsidered on dictionary editing.
             return false;
         }
-        self.process_key_event_inner_v2(key_event)
+        self.last_confirmation_record = self.confirmation_record.take();
+        let change_base = self.state_change_base();
+        let history_before = self.preedit_history_entry();
+        // 入力中の表示の比較は、observerか履歴で必要な時だけ行う
+        let preedit_before = if self.observers.is_empty() && history_before.is_none() {
+            None
+        } else {
+            Some(self.get_preedit_detail())
+        };
+        if self.try_reopen_kakutei(key_event) {
+            self.last_confirmation_record = None;
+            self.clear_preedit_history();
+            self.update_completion_suggestions();
+            let preedit_changed = preedit_before
+                .is_some_and(|preedit_before| preedit_before != self.get_preedit_detail());
+            self.notify_state_changes(change_base, preedit_changed);
+            return true;
+        }
+        let confirmed_len_before = self.state_stack[0].get_confirmed_string().len();
+        let delete_surrounding_before = self.pending_delete_surrounding;
+        let processed = self.process_key_event_inner_v2(key_event);
+        self.last_confirmation_record = None;
+        self.discard_stale_confirmation_record();
+        self.discard_aborted_reconversion();
+        self.surrounding_text.clear();
+        self.update_completion_suggestions();
+        let preedit_changed = preedit_before
+            .is_some_and(|preedit_before| preedit_before != self.get_preedit_detail());
+        self.notify_state_changes(change_base, preedit_changed);
+        if delete_surrounding_before != self.pending_delete_surrounding
+            || confirmed_len_before != self.state_stack[0].get_confirmed_string().len()
+        {
+            // 確定した場合は確定済みの文字列を戻せないので、履歴を全て捨てる。
+            self.preedit_history_moved = false;
+            self.clear_preedit_history();
+        } else {
+            self.record_preedit_history(history_before, preedit_changed);
+        }
+        processed
     }
 
     pub fn save_dictionary(&mut self) {
         for cskkdict in &self.dictionaries {
-            // Using mutex in match on purpose, never acquiring lock again.
+            // Using lock in match on purpose, never acquiring lock again.
             #[allow(clippy::significant_drop_in_scrutinee)]
-            let result = match *cskkdict.mutex.lock().unwrap() {
+            let result = match *cskkdict.write_fully_loaded() {
                 CskkDictionaryType::StaticFile(ref mut dictionary) => dictionary.save_dictionary(),
                 CskkDictionaryType::UserFile(ref mut dictionary) => dictionary.save_dictionary(),
                 CskkDictionaryType::EmptyDict(ref mut dictionary) => dictionary.save_dictionary(),
@@ -752,9 +1107,9 @@ impl CskkContext {
 
     pub fn reload_dictionary(&mut self) {
         for cskkdict in &self.dictionaries {
-            // Using mutex in match on purpose, never acquiring lock again.
+            // Using lock in match on purpose, never acquiring lock again.
             #[allow(clippy::significant_drop_in_scrutinee)]
-            let result = match *cskkdict.mutex.lock().unwrap() {
+            let result = match *cskkdict.write() {
                 CskkDictionaryType::StaticFile(ref mut dictionary) => dictionary.reload(),
                 CskkDictionaryType::UserFile(ref mut dictionary) => dictionary.reload(),
                 CskkDictionaryType::EmptyDict(_) => Ok(()),
@@ -766,10 +1121,319 @@ impl CskkContext {
                 }
             }
         }
+        self.update_completion_suggestions();
     }
 
     pub fn set_dictionaries(&mut self, dicts: Vec<Arc<CskkDictionary>>) {
         self.dictionaries = dicts;
+        self.update_completion_suggestions();
+    }
+
+    ///
+    /// PreCompositionの入力中に補完候補として探す件数を設定する。0ならば探さない。
+    ///
+    /// ddskkのskk-dcompのように、CompositionModeを変えずにキー入力ごとに補完候補を更新する。
+    /// 補完候補は[get_completion_suggestions]や[get_preedit_detail]で取得でき、表示はIME側で行う。
+    ///
+    pub fn set_dynamic_completion_count(&mut self, count: usize) {
+        self.config.set_dynamic_completion_count(count);
+        self.update_completion_suggestions();
+    }
+
+    ///
+    /// 現在の入力中の補完候補を返す。補完候補がなければ空。
+    ///
+    pub fn get_completion_suggestions(&self) -> Vec<CompletionSuggestion> {
+        self.current_state_ref()
+            .get_completion_suggestions()
+            .iter()
+            .map(CompletionSuggestion::from_candidate)
+            .collect()
+    }
+
+    ///
+    /// index番目の補完候補で確定する。補完候補として選ばれた候補は辞書に学習される。
+    ///
+    /// 該当する補完候補がなければ何もせずfalseを返す。
+    ///
+    pub fn accept_completion_suggestion(&mut self, index: usize) -> bool {
+        self.with_change_notification(|context| context.accept_completion_suggestion_inner(index))
+    }
+
+    #[allow(unused_must_use)]
+    fn accept_completion_suggestion_inner(&mut self, index: usize) -> bool {
+        let suggestion = if let Some(suggestion) = self
+            .current_state_ref()
+            .get_completion_suggestions()
+            .get(index)
+        {
+            suggestion.to_owned()
+        } else {
+            return false;
+        };
+
+        let mut updated = false;
+        for cskkdict in self.dictionaries.iter() {
+            updated |= confirm_candidate(cskkdict, &suggestion).unwrap_or(false);
+        }
+        if updated {
+            self.push_event(CskkEvent::dictionary_updated(
+                DictionaryUpdateKind::Learned,
+                &suggestion,
+            ));
+        }
+
+        let mut output = suggestion.output.to_owned();
+        if suggestion.okuri {
+            if let Some(okuri) = &suggestion.strict_okuri {
+                output.push_str(
+                    &self
+                        .kana_form_changer
+                        .adjust_kana_string(self.current_state_ref().input_mode, okuri),
+                );
+            }
+        }
+        let current_state = self.current_state();
+        current_state.push_string_for_composition_mode(&output, CompositionMode::Direct);
+        current_state.clear_unconfirmed();
+        current_state.reset_to_default_composition_mode();
+        self.update_completion_suggestions();
+        true
+    }
+
+    ///
+    /// 変換候補が見つからない時、登録モードに入る前に打ち間違いを訂正した読みで辞書を引くようにする。
+    ///
+    /// max_costは訂正する編集のコストの上限で、0ならば訂正しない。
+    /// 促音・長音・小書き・母音・撥音の挿入と削除、濁点の有無や小書きと並字の違い、ローマ字で隣のキーとの押し間違いにあたるかなの置換はコスト1、
+    /// その他のかな一文字の挿入・削除・置換はコスト2とする。
+    /// 辞書の大きさによらず速く引けるよう、コストの上限は2までに制限する。
+    ///
+    /// 訂正した読みで見つかった候補は[cskkstate::CompositionSelectionData::corrected_reading]に訂正した読みを持つ。
+    ///
+    pub fn set_fuzzy_fallback_max_cost(&mut self, max_cost: usize) {
+        self.config.set_fuzzy_fallback_max_cost(max_cost);
+    }
+
+    ///
+    /// 変換候補の末尾に、読みそのものを指定した入力モードのかなに変換した候補を加えるようにする。
+    ///
+    /// ddskkのように辞書に無い外来語等を読みのカタカナで確定できるよう、例えば`vec![InputMode::Katakana, InputMode::Hiragana]`を指定する。
+    /// 指定できるのはHiragana, Katakana, HankakuKatakanaで、他の入力モードは無視される。空ならば加えない。
+    /// 辞書の候補と同じものは加えない。選ばれた候補は辞書の候補と同様に学習される。
+    ///
+    pub fn set_kana_form_candidates(&mut self, input_modes: Vec<InputMode>) {
+        self.config.set_kana_form_candidates(input_modes);
+    }
+
+    ///
+    /// ddskkのskk-search-sagyo-henkakuのように、送り仮名がさ・し・す・せの送りあり変換で送りなしの語幹も引くようにする。
+    ///
+    /// 「べんきょうs」のエントリが無くても「べんきょう」の候補から「勉強す」「勉強し」のように変換できる。
+    /// 語幹による候補は送りありエントリの候補の後に並び、確定すると送りありエントリとして学習される。
+    ///
+    pub fn set_sagyo_henkaku_search(&mut self, enabled: bool) {
+        self.config.set_sagyo_henkaku_search(enabled);
+    }
+
+    ///
+    /// ddskkのskk-auto-okuri-processのように、送り仮名を指定せずに入力した読みを送りありエントリからも引くようにする。
+    ///
+    /// 「Kaeru」のように送り仮名の位置を大文字で指定しなかった場合にも、読みを語幹と送り仮名に分けて送りありエントリを引き、
+    /// 「帰る」「変える」のような候補を送りなしエントリの候補の後に並べる。
+    /// 選んだ候補は分けた送り仮名で送りありエントリとして学習される。
+    ///
+    pub fn set_auto_okuri_process(&mut self, enabled: bool) {
+        self.config.set_auto_okuri_process(enabled);
+    }
+
+    ///
+    /// ddskkのように、変換候補の選択で一定数の候補を示した後は候補の一覧をページ単位で送り、選択キーで候補を選ぶようにする。
+    ///
+    /// 一覧を出すまでの候補数、1ページの候補数、選択キーはルールファイルの[composition_selection.candidate_selection]で指定する。
+    /// 一覧を表示している間は[get_preedit_detail]の[cskkstate::CompositionSelectionData::page]に現在のページが入る。
+    /// 無効の場合は従来通り、選択キーの処理はIME側で行う。
+    ///
+    pub fn set_builtin_candidate_selection(&mut self, enabled: bool) {
+        self.config.set_builtin_candidate_selection(enabled);
+    }
+
+    ///
+    /// 確定済みの文字列や選択中の文字列textを再変換する。
+    ///
+    /// readingを与えればその読みで、Noneならば辞書の逆引きで読みを求め、textを最初の候補とした▼モードに入る。
+    /// 「考える」のように末尾がひらがなの場合は送り仮名として扱い、送りありエントリの候補を並べる。
+    /// 確定すると[poll_delete_surrounding]がtextの文字数を返すので、ホスト側ではカーソルの直前にあるtextをその文字数分消してから[poll_output]の文字列を入れる。
+    /// 選択中の文字列の場合は、ホスト側でカーソルを選択範囲の末尾に置いて呼ぶ。
+    /// 確定せずに中止した場合は何も消さない。
+    ///
+    /// 変換中の入力がある場合や、読みが見つからない場合は何もせずにfalseを返す。
+    ///
+    pub fn start_reconversion(&mut self, text: &str, reading: Option<&str>) -> bool {
+        self.with_change_notification(|context| context.start_reconversion_inner(text, reading))
+    }
+
+    fn start_reconversion_inner(&mut self, text: &str, reading: Option<&str>) -> bool {
+        if text.is_empty()
+            || self.state_stack.len() > 1
+            || self.current_state_ref().composition_mode != CompositionMode::Direct
+            || !self.current_state_ref().pre_conversion.is_empty()
+        {
+            return false;
+        }
+        let (reading, okuri) = match get_reconversion_reading(&self.dictionaries, text, reading) {
+            Some(found) => found,
+            None => return false,
+        };
+        let target = text[..text.len() - okuri.as_ref().map_or(0, |okuri| okuri.len())].to_owned();
+
+        self.current_state()
+            .push_string_for_composition_mode(&reading, CompositionMode::PreComposition);
+        self.set_composition_mode(CompositionMode::PreComposition);
+        if let Some(okuri) = okuri {
+            self.current_state()
+                .push_string_for_composition_mode(&okuri, CompositionMode::PreCompositionOkurigana);
+            self.set_composition_mode(CompositionMode::PreCompositionOkurigana);
+        }
+        self.update_candidate_list();
+        let composite_key = self.current_state_ref().get_composite_key();
+        let mut candidates = self
+            .current_state_ref()
+            .get_candidate_list()
+            .get_all_candidates()
+            .to_owned();
+        let target_candidate = match candidates
+            .iter()
+            .position(|candidate| candidate.output == target)
+        {
+            Some(index) => candidates.remove(index),
+            None => Candidate::from_reconversion_target(&composite_key, target),
+        };
+        candidates.insert(0, target_candidate);
+        self.current_state().set_new_candidate_list(candidates);
+        self.set_composition_mode(CompositionMode::CompositionSelection);
+        self.reconversion_target_len = Some(text.chars().count());
+        self.clear_preedit_history();
+        true
+    }
+
+    ///
+    /// 現在の辞書群からwordを候補に持つ読みを逆引きする。
+    /// 読みは辞書の順に並び、dictionary_indexは現在の辞書群の中での位置を示す。
+    ///
+    pub fn get_readings_for(&self, word: &str) -> Vec<DictionaryReading> {
+        get_readings_for(&self.dictionaries, word)
+    }
+
+    ///
+    /// 登録モードの入れ子や変換候補の一覧を含む入力中の状態を、バージョン付きのTOML文字列として保存する。
+    /// 辞書の内容は含めないので、[restore]したcontextの辞書で続きの変換を行う。
+    /// pollされていない確定済み文字列は含めない。
+    ///
+    pub fn snapshot(&self) -> Result<String, CskkError> {
+        let mut state_stack = self.state_stack.clone();
+        state_stack[0].flush_confirmed_string();
+        let snapshot = ContextSnapshot {
+            version: SNAPSHOT_VERSION,
+            state_stack,
+        };
+        Ok(toml::to_string(&snapshot)?)
+    }
+
+    ///
+    /// [snapshot]で保存した入力中の状態に戻す。pollされていない確定済み文字列はそのまま残る。
+    /// 確定の取り消しや入力中の状態の履歴など、キー入力をまたいで持つ記録は捨てる。
+    ///
+    /// 読めない文字列やバージョンの異なるsnapshot、候補の選択位置や読みの中のカーソル位置が範囲外であるなど
+    /// 辻褄の合わない状態を含むsnapshotの場合は状態を変えずにエラーを返す。
+    ///
+    pub fn restore(&mut self, snapshot: &str) -> Result<(), CskkError> {
+        let version = toml::from_str::<ContextSnapshotVersion>(snapshot)?.version;
+        if version != SNAPSHOT_VERSION {
+            return Err(CskkError::Error(format!(
+                "Unsupported snapshot version: {}",
+                version
+            )));
+        }
+        let snapshot = toml::from_str::<ContextSnapshot>(snapshot)?;
+        if snapshot.state_stack.is_empty() || snapshot.state_stack.len() > REGISTER_MODE_STACK_MAX {
+            return Err(CskkError::Error(format!(
+                "Invalid snapshot state stack depth: {}",
+                snapshot.state_stack.len()
+            )));
+        }
+        for state in &snapshot.state_stack {
+            state.validate()?;
+        }
+        // 登録モードに入る時は元の状態を登録モードにしてから新しい状態を積むので、一番上以外は登録モードになっている。
+        let (_top, registering) = snapshot.state_stack.split_last().unwrap();
+        if let Some(state) = registering
+            .iter()
+            .find(|state| state.composition_mode != CompositionMode::Register)
+        {
+            return Err(CskkError::Error(format!(
+                "Non register mode state {:?} under another state",
+                state.composition_mode
+            )));
+        }
+
+        self.with_change_notification(|context| context.replace_state_stack(snapshot.state_stack));
+        Ok(())
+    }
+
+    ///
+    /// ホスト側の入力欄からフォーカスが外れた時に呼び、policyに従って入力中の状態を片付ける。
+    ///
+    /// FocusOutCommitでは▼モードなら選択中の候補を、▽モードなら表示している読みをかなのまま確定する。かな変換前の入力は捨てる。
+    /// 登録モードの場合は登録せずに抜け、登録しようとしていた読みをかなのまま確定する。
+    /// FocusOutDiscardでは入力中のものを登録モードも含めて全て捨てる。
+    /// FocusOutKeepでは入力中の状態を保存して表示から消し、[focus_in]で戻せるようにする。
+    ///
+    /// いずれの場合もpollされていない確定済み文字列はそのまま残るので、この後にpollする。
+    ///
+    pub fn focus_out(&mut self, policy: FocusOutPolicy) {
+        self.with_change_notification(|context| context.focus_out_inner(policy))
+    }
+
+    fn focus_out_inner(&mut self, policy: FocusOutPolicy) {
+        let confirmed = self.state_stack[0].get_confirmed_string().to_owned();
+        self.focus_out_state_stack = None;
+        match policy {
+            FocusOutPolicy::FocusOutCommit => {
+                self.state_stack.truncate(1);
+                self.commit_shown_composition();
+                let confirmed = self.state_stack[0].get_confirmed_string().to_owned();
+                self.reset_state_stack();
+                self.current_state()
+                    .push_string_for_composition_mode(&confirmed, CompositionMode::Direct);
+            }
+            FocusOutPolicy::FocusOutDiscard => {
+                self.reset_state_stack();
+                self.current_state()
+                    .push_string_for_composition_mode(&confirmed, CompositionMode::Direct);
+            }
+            FocusOutPolicy::FocusOutKeep => {
+                let mut saved = self.state_stack.clone();
+                saved[0].flush_confirmed_string();
+                self.focus_out_state_stack = Some(saved);
+                self.reset_state_stack();
+                self.current_state()
+                    .push_string_for_composition_mode(&confirmed, CompositionMode::Direct);
+            }
+        }
+    }
+
+    ///
+    /// ホスト側の入力欄にフォーカスが戻った時に呼び、FocusOutKeepで[focus_out]した時の入力中の状態に戻す。
+    /// 戻した場合はtrue、保存した状態がなければ何もせずにfalseを返す。
+    ///
+    pub fn focus_in(&mut self) -> bool {
+        if let Some(state_stack) = self.focus_out_state_stack.take() {
+            self.with_change_notification(|context| context.replace_state_stack(state_stack));
+            true
+        } else {
+            false
+        }
     }
 
     /// 大文字であり、かつコマンドではないキー入力をした時のモード変更を行う。
@@ -858,11 +1522,12 @@ impl CskkContext {
                 {
                     // この部分TryNextCandidateと似ているが、事前条件が違うので共通にできなかった。
                     self.update_candidate_list();
+                    let prev_composition_mode = self.current_state_ref().composition_mode;
                     if self.current_state_ref().get_candidate_list().is_empty() {
-                        let prev_composition_mode = self.current_state_ref().composition_mode;
                         self.enter_register_mode(prev_composition_mode);
                     } else {
                         self.set_composition_mode(CompositionMode::CompositionSelection);
+                        self.confirm_if_kakutei(prev_composition_mode);
                     }
                 }
             }
@@ -880,6 +1545,12 @@ impl CskkContext {
     /// 1. は"z l"から"→"の変換のように、コマンドでもひらがな変換に使うことがあるための処置。即rom_kana以外ではコマンド優先。
     fn process_key_event_inner_v2(&mut self, key_event: &CskkKeyEvent) -> bool {
         log::debug!("Keyevent: {:?}", key_event);
+        if self.current_state_ref().is_hint_active() {
+            return self.process_hint_key_event(key_event);
+        }
+        if self.try_select_candidate_on_page(key_event) {
+            return true;
+        }
         let initial_composition_mode = self.current_state_ref().composition_mode;
         let initial_input_mode = self.current_state_ref().input_mode;
 
@@ -915,7 +1586,7 @@ impl CskkContext {
             }
         }
 
-        // CompositionSelectionの選択キーをlibcskkではなく外部のUI所持側が持つ想定なので、ここで処理しない。
+        // 組み込みの候補選択を使わない場合、CompositionSelectionの選択キーをlibcskkではなく外部のUI所持側が持つ想定なので、ここで処理しない。
 
         if !key_event.is_modifierless_input() {
             return false;
@@ -1282,6 +1953,8 @@ impl CskkContext {
                         log::debug!(
                             "Trying previous candidate on not composition selection mode. Ignore."
                         )
+                    } else if let Some(previous) = self.previous_candidate_page_pointer() {
+                        self.current_state().set_candidate_pointer_index(previous);
                     } else if !self.current_state_ref().get_candidate_list().has_previous() {
                         self.current_state().consolidate_converted_to_to_composite();
                         self.current_state().clear_candidate_list();
@@ -1302,6 +1975,38 @@ impl CskkContext {
                         self.current_state().backward_candidate();
                     } // Do nothing when no previous
                 }
+                Instruction::UndoKakutei => {
+                    return self.undo_kakutei();
+                }
+                Instruction::BackwardSetHenkanPoint => {
+                    return self.backward_set_henkan_point(true);
+                }
+                Instruction::BackwardSetHenkanPointChar => {
+                    return self.backward_set_henkan_point(false);
+                }
+                Instruction::UndoPreedit => {
+                    return self.undo_preedit();
+                }
+                Instruction::RedoPreedit => {
+                    return self.redo_preedit();
+                }
+                Instruction::MoveCursorLeft
+                | Instruction::MoveCursorRight
+                | Instruction::MoveCursorHome
+                | Instruction::MoveCursorEnd => {
+                    self.move_composite_cursor(instruction);
+                }
+                Instruction::StartHint => {
+                    if self.current_state_ref().composition_mode
+                        != CompositionMode::CompositionSelection
+                    {
+                        log::debug!(
+                            "Trying to start hint on not composition selection mode. Ignore."
+                        )
+                    } else {
+                        self.current_state().start_hint();
+                    }
+                }
                 #[allow(unreachable_patterns)]
                 _ => {
                     log::debug!("unimplemented instruction: {:?}", &instruction);
@@ -1312,6 +2017,92 @@ impl CskkContext {
         !instructions.is_empty()
     }
 
+    // ヒントの入力中のキー入力を処理する。
+    // かな入力はヒントの読みとし、次候補・確定のキーでヒントによって候補を絞り込む。
+    fn process_hint_key_event(&mut self, key_event: &CskkKeyEvent) -> bool {
+        let input_mode = self.current_state_ref().input_mode;
+        let pre_conversion = self.current_state_ref().pre_conversion.clone();
+        if key_event.is_modifierless_input() {
+            let combined = KanaBuilder::combine_lower(key_event, &pre_conversion);
+            if let Some((converted, carry_over)) =
+                self.kana_converter.convert_non_partial(&combined)
+            {
+                let converted = converted.to_owned();
+                let carry_over = carry_over.to_owned();
+                self.current_state().push_hint(&converted);
+                self.set_carry_over(&carry_over);
+                return true;
+            }
+            if self.kana_converter.can_continue(key_event, &pre_conversion) {
+                self.set_carry_over(&combined);
+                return true;
+            }
+        }
+
+        let instructions = self
+            .get_handler_v2()
+            .get_instruction(
+                key_event,
+                &input_mode,
+                &CompositionMode::CompositionSelection,
+            )
+            .unwrap_or_default();
+        // BackSpaceのように確定と削除を兼ねるキーはヒントでは削除として扱う
+        if instructions.contains(&Instruction::Delete) {
+            self.current_state().delete_hint();
+        } else if instructions.contains(&Instruction::Abort) {
+            self.current_state().take_hint();
+        } else if instructions.contains(&Instruction::TryNextCandidate)
+            || instructions.contains(&Instruction::ConfirmComposition)
+        {
+            self.narrow_candidates_by_hint();
+        } else if key_event.is_modifierless_input() {
+            log::debug!("Ignored non kana input in hint: {:?}", key_event);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // ヒントの入力を終え、ヒントの読みの変換候補と共通する文字を持つ変換候補だけに絞り込む。
+    // 絞り込むと候補が無くなる場合は変換候補を変えない。
+    fn narrow_candidates_by_hint(&mut self) {
+        let pre_conversion = self.current_state_ref().pre_conversion.clone();
+        if let Some((converted, _)) = self.kana_converter.convert_greedy(&pre_conversion) {
+            let converted = converted.to_owned();
+            self.current_state().push_hint(&converted);
+        }
+        let hint_reading = match self.current_state().take_hint() {
+            Some(hint_reading) if !hint_reading.is_empty() => hint_reading,
+            _ => return,
+        };
+
+        let hint_candidates =
+            get_all_candidates(&self.dictionaries, &CompositeKey::new(&hint_reading, None));
+        let hint_chars = hint_candidates
+            .iter()
+            .flat_map(|candidate| candidate.kouho_text.chars())
+            .collect::<HashSet<_>>();
+        let narrowed = self
+            .current_state_ref()
+            .get_candidate_list()
+            .get_all_candidates()
+            .iter()
+            .filter(|candidate| {
+                candidate
+                    .kouho_text
+                    .chars()
+                    .any(|kouho_char| hint_chars.contains(&kouho_char))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if narrowed.is_empty() {
+            log::debug!("No candidate matched hint {}", hint_reading);
+        } else {
+            self.current_state().set_new_candidate_list(narrowed);
+        }
+    }
+
     // if has candidate and has next, pointer to the next candidate and set to composition selection mode.
     // if not, go to registeration mode.
     fn try_next_candidate(
@@ -1331,6 +2122,13 @@ impl CskkContext {
                 self.enter_register_mode(initial_composition_mode);
             } else if !self.current_state_ref().get_candidate_list().is_empty() {
                 self.set_composition_mode(CompositionMode::CompositionSelection);
+                self.confirm_if_kakutei(initial_composition_mode);
+            }
+        } else if let Some(next) = self.next_candidate_page_pointer() {
+            if next < self.current_state_ref().get_candidate_list().len() {
+                self.current_state().set_candidate_pointer_index(next);
+            } else {
+                self.enter_register_mode(initial_composition_mode);
             }
         } else if !self.current_state_ref().get_candidate_list().has_next() {
             self.enter_register_mode(initial_composition_mode);
@@ -1339,6 +2137,519 @@ impl CskkContext {
         }
     }
 
+    // 組み込みの候補選択で候補の一覧を表示していれば、そのページの候補の範囲を返す。
+    fn candidate_page_range(&self, state: &CskkState) -> Option<(usize, usize)> {
+        if !self.config.builtin_candidate_selection
+            || state.composition_mode != CompositionMode::CompositionSelection
+        {
+            return None;
+        }
+        let selection_rule = self.get_handler_v2().get_candidate_selection_rule();
+        let candidate_list = state.get_candidate_list();
+        let pointer = candidate_list.get_selection_pointer();
+        if candidate_list.is_empty() || pointer < selection_rule.show_after {
+            return None;
+        }
+        let page_size = selection_rule.effective_page_size();
+        let start = selection_rule.show_after
+            + (pointer - selection_rule.show_after) / page_size * page_size;
+        let end = (start + page_size).min(candidate_list.len());
+        Some((start, end))
+    }
+
+    fn candidate_page(&self, state: &CskkState) -> Option<CandidatePage> {
+        let (start, end) = self.candidate_page_range(state)?;
+        let selection_rule = self.get_handler_v2().get_candidate_selection_rule();
+        let page_size = selection_rule.effective_page_size();
+        let candidates = state.get_candidate_list().get_all_candidates()[start..end]
+            .iter()
+            .map(|candidate| candidate.output.to_owned())
+            .collect::<Vec<_>>();
+        let labels = selection_rule
+            .selection_keys
+            .iter()
+            .take(candidates.len())
+            .map(|key| {
+                key.get_symbol_char()
+                    .map(|symbol| symbol.to_ascii_uppercase().to_string())
+                    .unwrap_or_else(|| key.to_string())
+            })
+            .collect();
+        let listed_count = state.get_candidate_list().len() - selection_rule.show_after;
+        Some(CandidatePage {
+            candidates,
+            labels,
+            page_index: (start - selection_rule.show_after) / page_size,
+            page_count: (listed_count + page_size - 1) / page_size,
+        })
+    }
+
+    // 組み込みの候補選択での次候補の位置を返す。一覧を出すまでは一つずつ、一覧ではページ単位で進む。
+    // 候補の数以上の位置を返した場合は候補が尽きている。
+    fn next_candidate_page_pointer(&self) -> Option<usize> {
+        if !self.config.builtin_candidate_selection {
+            return None;
+        }
+        let show_after = self
+            .get_handler_v2()
+            .get_candidate_selection_rule()
+            .show_after;
+        let pointer = self
+            .current_state_ref()
+            .get_candidate_list()
+            .get_selection_pointer();
+        match self.candidate_page_range(self.current_state_ref()) {
+            Some((start, _)) => Some(
+                start
+                    + self
+                        .get_handler_v2()
+                        .get_candidate_selection_rule()
+                        .effective_page_size(),
+            ),
+            None if pointer < show_after => Some(pointer + 1),
+            None => None,
+        }
+    }
+
+    // 組み込みの候補選択で一覧を表示している時の前候補の位置を返す。
+    // 最初のページからは一覧を出す前の最後の候補に戻る。
+    fn previous_candidate_page_pointer(&self) -> Option<usize> {
+        let (start, _) = self.candidate_page_range(self.current_state_ref())?;
+        let selection_rule = self.get_handler_v2().get_candidate_selection_rule();
+        if start > selection_rule.show_after {
+            Some(start - selection_rule.effective_page_size())
+        } else if selection_rule.show_after > 0 {
+            Some(selection_rule.show_after - 1)
+        } else {
+            None
+        }
+    }
+
+    // 組み込みの候補選択で一覧を表示している時、選択キーであればその候補で確定する。
+    // 選択キーを処理した場合はtrueを返す。
+    fn try_select_candidate_on_page(&mut self, key_event: &CskkKeyEvent) -> bool {
+        let (start, end) = match self.candidate_page_range(self.current_state_ref()) {
+            Some(range) => range,
+            None => return false,
+        };
+        let maybe_key_index = self
+            .get_handler_v2()
+            .get_candidate_selection_rule()
+            .selection_keys
+            .iter()
+            .position(|selection_key| selection_key == key_event);
+        if let Some(key_index) = maybe_key_index {
+            // ページ内に候補が無い選択キーは何もせずに消費する
+            if start + key_index < end {
+                self.current_state()
+                    .set_candidate_pointer_index(start + key_index);
+                self.confirm_current_composition_candidate();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    // 変換候補を選び始めた時、現在の候補が確定辞書の候補であれば学習せずに確定し、確定を取り消すための記録を残す。
+    fn confirm_if_kakutei(&mut self, initial_composition_mode: CompositionMode) {
+        let composite_key = self.current_state_ref().get_composite_key();
+        let is_kakutei = match self
+            .current_state_ref()
+            .get_candidate_list()
+            .get_current_candidate()
+        {
+            Ok(candidate) => is_kakutei_candidate(&self.dictionaries, &composite_key, candidate),
+            Err(_) => false,
+        };
+        if !is_kakutei {
+            return;
+        }
+
+        self.confirm_current_composition_candidate_inner(None);
+        if let Some(record) = self.confirmation_record.as_mut() {
+            record.composition_mode = initial_composition_mode;
+            record.is_kakutei = true;
+        }
+    }
+
+    // 確定辞書で確定した直後の次候補のキーであれば、確定を取り消して次の変換候補を選ぶ。
+    // 取り消した場合はtrueを返す。
+    fn try_reopen_kakutei(&mut self, key_event: &CskkKeyEvent) -> bool {
+        let is_next_candidate_key = match &self.last_confirmation_record {
+            Some(record) if record.is_kakutei => self
+                .get_handler_v2()
+                .get_instruction(
+                    key_event,
+                    &record.state.input_mode,
+                    &record.composition_mode,
+                )
+                .is_some_and(|instructions| instructions.contains(&Instruction::TryNextCandidate)),
+            _ => false,
+        };
+        is_next_candidate_key && self.undo_kakutei()
+    }
+
+    // 直前のキー入力での確定を取り消し、確定前の変換候補の一覧に戻って次の変換候補を選ぶ。
+    // 確定による学習も取り消す。取り消した場合はtrueを返す。
+    fn undo_kakutei(&mut self) -> bool {
+        let record = match self.last_confirmation_record.take() {
+            Some(record) => record,
+            None => return false,
+        };
+        if record.stack_depth != self.state_stack.len() {
+            return false;
+        }
+
+        if !self
+            .current_state()
+            .retract_confirmed_string(&record.output)
+        {
+            if self.state_stack.len() > 1 {
+                // 登録モード中の確定済み文字列はpollされないので、ここには来ない。
+                return false;
+            }
+            self.pending_delete_surrounding += record.output.chars().count();
+        }
+        for learning_backup in record.learning_backups.into_iter().rev() {
+            restore_learning(learning_backup);
+        }
+        let confirmed = self.current_state_ref().get_confirmed_string().to_owned();
+        let mut restored = record.state;
+        restored.flush_confirmed_string();
+        restored.push_string_for_composition_mode(&confirmed, CompositionMode::Direct);
+        let input_mode = restored.input_mode;
+        *self.current_state() = restored;
+        self.try_next_candidate(CompositionMode::CompositionSelection, input_mode);
+        true
+    }
+
+    // 確定済みのかなを遡って読みにし、PreCompositionモードで変換開始位置を置き直す。
+    // whole_runならば末尾から続くかなを全て、そうでなければ1文字だけ読みの先頭に加える。
+    // 遡れるかなが無ければ何もせずにfalseを返す。
+    fn backward_set_henkan_point(&mut self, whole_run: bool) -> bool {
+        let current_state = self.current_state_ref();
+        let input_mode = current_state.input_mode;
+        let composition_mode = current_state.composition_mode;
+        if !current_state.pre_conversion.is_empty()
+            || !matches!(
+                composition_mode,
+                CompositionMode::Direct | CompositionMode::PreComposition
+            )
+            || !matches!(input_mode, InputMode::Hiragana | InputMode::Katakana)
+        {
+            return false;
+        }
+        let limit = if whole_run { usize::MAX } else { 1 };
+
+        let confirmed = current_state.get_confirmed_string();
+        let from_confirmed = KanaFormChanger::trailing_reading_kana(confirmed, input_mode, limit);
+        let retracted = confirmed
+            .chars()
+            .rev()
+            .take(from_confirmed.chars().count())
+            .collect::<Vec<char>>()
+            .into_iter()
+            .rev()
+            .collect::<String>();
+        let reached_start = retracted.len() == confirmed.len();
+        self.current_state().retract_confirmed_string(&retracted);
+
+        let mut reading = from_confirmed;
+        let remaining = limit.saturating_sub(reading.chars().count());
+        if remaining > 0 && reached_start && self.state_stack.len() == 1 {
+            let from_surrounding = KanaFormChanger::trailing_reading_kana(
+                &self.surrounding_text,
+                input_mode,
+                remaining,
+            );
+            let surrounding_len = from_surrounding.chars().count();
+            for _ in 0..surrounding_len {
+                self.surrounding_text.pop();
+            }
+            self.pending_delete_surrounding += surrounding_len;
+            reading.insert_str(0, &from_surrounding);
+        }
+        if reading.is_empty() {
+            return false;
+        }
+
+        if composition_mode == CompositionMode::Direct {
+            self.set_composition_mode(CompositionMode::PreComposition);
+        }
+        self.current_state().prepend_kana_to_composite(&reading);
+        true
+    }
+
+    fn push_event(&mut self, event: CskkEvent) {
+        self.events.push_back(event);
+        if self.events.len() > EVENT_QUEUE_MAX {
+            self.events.pop_front();
+        }
+    }
+
+    fn state_change_base(&self) -> StateChangeBase {
+        let current_state = self.current_state_ref();
+        let candidate_list = current_state.get_candidate_list();
+        StateChangeBase {
+            input_mode: current_state.input_mode,
+            composition_mode: current_state.composition_mode,
+            selection_pointer: candidate_list.get_selection_pointer(),
+            candidate_outputs: candidate_list
+                .get_all_candidates()
+                .iter()
+                .map(|candidate| candidate.output.to_owned())
+                .collect(),
+        }
+    }
+
+    // 公開APIでの状態の変更をchangeで行い、キー入力の処理と同様に入力中の補完候補を探し直して、変わったものをイベントとobserverに知らせる。
+    fn with_change_notification<T>(&mut self, change: impl FnOnce(&mut Self) -> T) -> T {
+        let change_base = self.state_change_base();
+        let preedit_before = if self.observers.is_empty() {
+            None
+        } else {
+            Some(self.get_preedit_detail())
+        };
+        let result = change(self);
+        self.update_completion_suggestions();
+        let preedit_changed = preedit_before
+            .is_some_and(|preedit_before| preedit_before != self.get_preedit_detail());
+        self.notify_state_changes(change_base, preedit_changed);
+        result
+    }
+
+    // 変更の前後でモードや変換候補の一覧が変わっていればイベントを積み、observerに知らせる。
+    // 登録モードの出入りでstate_stackの深さが変わった場合も、前後の一番上の状態のモードや変換候補を比べる。
+    fn notify_state_changes(&mut self, before: StateChangeBase, preedit_changed: bool) {
+        let after = self.current_state_ref();
+        let input_mode = after.input_mode;
+        let composition_mode = after.composition_mode;
+        let input_mode_changed = before.input_mode != input_mode;
+        let composition_mode_changed = before.composition_mode != composition_mode;
+        let after_list = after.get_candidate_list();
+        let candidates_changed = before.selection_pointer != after_list.get_selection_pointer()
+            || before.candidate_outputs.len() != after_list.len()
+            || before
+                .candidate_outputs
+                .iter()
+                .zip(after_list.get_all_candidates())
+                .any(|(before, after)| *before != after.output);
+        if input_mode_changed || composition_mode_changed {
+            self.push_event(CskkEvent::ModeChanged {
+                input_mode,
+                composition_mode,
+            });
+        }
+        if candidates_changed {
+            self.push_event(CskkEvent::CandidatesChanged);
+        }
+
+        if self.observers.is_empty() {
+            return;
+        }
+        let changes = [
+            (input_mode_changed, CskkChange::InputModeChanged),
+            (composition_mode_changed, CskkChange::CompositionModeChanged),
+            (candidates_changed, CskkChange::CandidatesChanged),
+            (preedit_changed, CskkChange::PreeditChanged),
+        ];
+        for (_, observer) in self.observers.iter_mut() {
+            for (changed, change) in changes {
+                if changed {
+                    observer(change);
+                }
+            }
+        }
+    }
+
+    // キー入力の前に、UndoPreeditの履歴に積むための現在のstate_stackを返す。
+    // ルールでUndoPreeditかRedoPreeditが使われていない場合や、読みのない直接入力の状態では履歴を作らずNoneを返す。
+    fn preedit_history_entry(&self) -> Option<Vec<CskkState>> {
+        if !self.get_handler_v2().uses_preedit_history() {
+            return None;
+        }
+        let current_state = self.current_state_ref();
+        if self.state_stack.len() == 1
+            && current_state.composition_mode == CompositionMode::Direct
+            && current_state.pre_conversion.is_empty()
+        {
+            return None;
+        }
+        let mut state_stack = self.state_stack.clone();
+        state_stack[0].flush_confirmed_string();
+        Some(state_stack)
+    }
+
+    // キー入力の前後で入力中の状態が変わっていれば、前の状態を履歴に積んでやり直しの履歴を捨てる。
+    // 履歴はキー入力の数と、持っている変換候補の数の合計の両方で制限する。
+    fn record_preedit_history(
+        &mut self,
+        history_before: Option<Vec<CskkState>>,
+        preedit_changed: bool,
+    ) {
+        if std::mem::take(&mut self.preedit_history_moved) || !preedit_changed {
+            return;
+        }
+        self.preedit_redo_history.clear();
+        if let Some(state_stack_before) = history_before {
+            self.preedit_undo_history.push_back(state_stack_before);
+            while self.preedit_undo_history.len() > PREEDIT_HISTORY_MAX
+                || self
+                    .preedit_undo_history
+                    .iter()
+                    .map(|state_stack| Self::count_candidates(state_stack))
+                    .sum::<usize>()
+                    > PREEDIT_HISTORY_CANDIDATES_MAX
+            {
+                self.preedit_undo_history.pop_front();
+            }
+        }
+    }
+
+    fn count_candidates(state_stack: &[CskkState]) -> usize {
+        state_stack
+            .iter()
+            .map(|state| state.get_candidate_list().len())
+            .sum()
+    }
+
+    // pollされていない確定済み文字列を残したまま、state_stackを入れ替える。キー入力をまたいだ記録は捨てる。
+    fn replace_state_stack(&mut self, state_stack: Vec<CskkState>) {
+        let confirmed = self.state_stack[0].get_confirmed_string().to_owned();
+        self.state_stack = state_stack;
+        self.state_stack[0].flush_confirmed_string();
+        self.state_stack[0].push_string_for_composition_mode(&confirmed, CompositionMode::Direct);
+        self.confirmation_record = None;
+        self.last_confirmation_record = None;
+        self.reconversion_target_len = None;
+        self.surrounding_text.clear();
+        self.clear_preedit_history();
+    }
+
+    // 登録モードを抜けた後の状態で、表示している変換候補か読みを確定済み文字列に加える。
+    fn commit_shown_composition(&mut self) {
+        match self.current_state_ref().composition_mode {
+            CompositionMode::CompositionSelection | CompositionMode::Completion => {
+                self.confirm_current_composition_candidate();
+            }
+            CompositionMode::PreComposition
+            | CompositionMode::PreCompositionOkurigana
+            | CompositionMode::Register => {
+                self.current_state().clear_preconverted_kanainputs();
+                self.current_state().consolidate_converted_to_to_composite();
+                let input_mode = self.current_state_ref().input_mode;
+                self.confirm_current_kana_to_composite(input_mode);
+            }
+            CompositionMode::Abbreviation => {
+                let abbreviation = self
+                    .current_state_ref()
+                    .get_to_composite_string()
+                    .to_owned();
+                self.replace_reconversion_target();
+                self.current_state()
+                    .push_string_for_composition_mode(&abbreviation, CompositionMode::Direct);
+            }
+            CompositionMode::Direct => {}
+        }
+    }
+
+    fn clear_preedit_history(&mut self) {
+        self.preedit_undo_history.clear();
+        self.preedit_redo_history.clear();
+    }
+
+    // 入力中の状態を履歴の1つ前に戻す。戻せる履歴がなければfalseを返す。
+    fn undo_preedit(&mut self) -> bool {
+        if let Some(state_stack) = self.preedit_undo_history.pop_back() {
+            let current = self.restore_preedit_history(state_stack);
+            self.preedit_redo_history.push(current);
+            true
+        } else {
+            false
+        }
+    }
+
+    // UndoPreeditで戻した状態をやり直す。やり直せる履歴がなければfalseを返す。
+    fn redo_preedit(&mut self) -> bool {
+        if let Some(state_stack) = self.preedit_redo_history.pop() {
+            let current = self.restore_preedit_history(state_stack);
+            self.preedit_undo_history.push_back(current);
+            true
+        } else {
+            false
+        }
+    }
+
+    // state_stackを履歴のものに置き換えて、置き換える前のものを返す。
+    // 確定済みの文字列は履歴に積んだ後にpollされているかもしれないので、現在のものを引き継ぐ。
+    fn restore_preedit_history(&mut self, state_stack: Vec<CskkState>) -> Vec<CskkState> {
+        let confirmed = self.state_stack[0].get_confirmed_string().to_owned();
+        let current = std::mem::replace(&mut self.state_stack, state_stack);
+        self.state_stack[0].flush_confirmed_string();
+        self.state_stack[0].push_string_for_composition_mode(&confirmed, CompositionMode::Direct);
+        self.preedit_history_moved = true;
+        current
+    }
+
+    // ▽モードの読みの中でカーソルを動かす。かな変換前の入力は動かす前の位置のものなので捨てる。
+    fn move_composite_cursor(&mut self, instruction: &Instruction) {
+        if !matches!(
+            self.current_state_ref().composition_mode,
+            CompositionMode::PreComposition | CompositionMode::Abbreviation
+        ) {
+            log::debug!("Trying to move cursor on not pre composition mode. Ignore.");
+            return;
+        }
+        let current_state = self.current_state();
+        current_state.clear_preconverted_kanainputs();
+        let cursor = current_state.get_composite_cursor();
+        let new_cursor = match instruction {
+            Instruction::MoveCursorLeft => cursor.saturating_sub(1),
+            Instruction::MoveCursorRight => cursor + 1,
+            Instruction::MoveCursorHome => 0,
+            _ => usize::MAX,
+        };
+        current_state.set_composite_cursor(new_cursor);
+    }
+
+    // 再変換中に確定する時、再変換元の文字列をホスト側で消させる。
+    fn replace_reconversion_target(&mut self) {
+        if self.state_stack.len() == 1 {
+            if let Some(target_len) = self.reconversion_target_len.take() {
+                self.pending_delete_surrounding += target_len;
+            }
+        }
+    }
+
+    // 確定せずにDirectモードに戻った場合は再変換を中止したとみなす。
+    fn discard_aborted_reconversion(&mut self) {
+        if self.state_stack.len() == 1
+            && self.current_state_ref().composition_mode == CompositionMode::Direct
+        {
+            self.reconversion_target_len = None;
+        }
+    }
+
+    // 確定した後に同じキー入力で更に入力が続いた場合は、確定した文字列だけを取り消せないので記録を捨てる。
+    fn discard_stale_confirmation_record(&mut self) {
+        let is_stale = match &self.confirmation_record {
+            Some(record) => {
+                record.stack_depth != self.state_stack.len()
+                    || self.current_state_ref().composition_mode != CompositionMode::Direct
+                    || !self.current_state_ref().pre_conversion.is_empty()
+                    || !self
+                        .current_state_ref()
+                        .get_confirmed_string()
+                        .ends_with(&record.output)
+            }
+            None => false,
+        };
+        if is_stale {
+            self.confirmation_record = None;
+        }
+    }
+
     // 次の補完候補があれば指す、そうでなければ何もしない。
     fn try_next_completion(
         &mut self,
@@ -1380,6 +2691,10 @@ impl CskkContext {
         let mut auto_start_henkan_keyword_matched = false;
         let composite_key = self.current_state_ref().get_composite_key();
         let raw_to_composite = composite_key.get_to_composite();
+        // 読みの途中に入力した時は末尾のキーワードを入力したわけではない。deleteもカーソルの前を消してしまう。
+        if self.current_state_ref().get_composite_cursor() != raw_to_composite.chars().count() {
+            return;
+        }
         for suffix in &self.config.auto_start_henkan_keywords.clone() {
             if !auto_start_henkan_keyword_matched
                 && !raw_to_composite.eq(suffix)
@@ -1512,6 +2827,7 @@ impl CskkContext {
         self.kana_converter = new_kana_converter;
         self.command_handler = new_command_handler;
         self.composition_triggers = new_rule.get_henkan_trigger_keysyms();
+        self.clear_preedit_history();
         Ok(())
     }
 
@@ -1540,6 +2856,18 @@ impl CskkContext {
             dictionaries,
             config: CskkConfig::default(),
             composition_triggers,
+            confirmation_record: None,
+            last_confirmation_record: None,
+            pending_delete_surrounding: 0,
+            reconversion_target_len: None,
+            surrounding_text: "".to_string(),
+            preedit_undo_history: VecDeque::new(),
+            preedit_redo_history: vec![],
+            preedit_history_moved: false,
+            events: VecDeque::new(),
+            observers: vec![],
+            next_observer_id: 0,
+            focus_out_state_stack: None,
         })
     }
 
@@ -1582,6 +2910,18 @@ impl CskkContext {
             dictionaries,
             config: CskkConfig::default(),
             composition_triggers: HashSet::new(),
+            confirmation_record: None,
+            last_confirmation_record: None,
+            pending_delete_surrounding: 0,
+            reconversion_target_len: None,
+            surrounding_text: "".to_string(),
+            preedit_undo_history: VecDeque::new(),
+            preedit_redo_history: vec![],
+            preedit_history_moved: false,
+            events: VecDeque::new(),
+            observers: vec![],
+            next_observer_id: 0,
+            focus_out_state_stack: None,
         }
     }
 
@@ -1611,6 +2951,18 @@ impl CskkContext {
             dictionaries,
             config: CskkConfig::default(),
             composition_triggers,
+            confirmation_record: None,
+            last_confirmation_record: None,
+            pending_delete_surrounding: 0,
+            reconversion_target_len: None,
+            surrounding_text: "".to_string(),
+            preedit_undo_history: VecDeque::new(),
+            preedit_redo_history: vec![],
+            preedit_history_moved: false,
+            events: VecDeque::new(),
+            observers: vec![],
+            next_observer_id: 0,
+            focus_out_state_stack: None,
         }
     }
 
@@ -1690,6 +3042,18 @@ mod unit_tests {
             dictionaries,
             config: CskkConfig::default(),
             composition_triggers,
+            confirmation_record: None,
+            last_confirmation_record: None,
+            pending_delete_surrounding: 0,
+            reconversion_target_len: None,
+            surrounding_text: "".to_string(),
+            preedit_undo_history: VecDeque::new(),
+            preedit_redo_history: vec![],
+            preedit_history_moved: false,
+            events: VecDeque::new(),
+            observers: vec![],
+            next_observer_id: 0,
+            focus_out_state_stack: None,
             //rule_metadata,
         }
     }
@@ -1816,8 +3180,10 @@ mod unit_tests {
             CskkStateInfo::PreComposition(PreCompositionData {
                 confirmed: "".to_string(),
                 kana_to_composite: "あ".to_string(),
+                cursor: 1,
                 okuri: None,
-                unconverted: None
+                unconverted: None,
+                completion_suggestions: vec![]
             })
         )
     }
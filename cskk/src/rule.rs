@@ -45,7 +45,7 @@ pub(crate) struct CskkCommandRule {
     #[serde(default)]
     pre_composition_okurigana: CskkCommandRuleInner,
     #[serde(default)]
-    composition_selection: CskkCommandRuleInner,
+    composition_selection: CompositionSelectionRule,
     #[serde(default)]
     abbreviation: CskkCommandRuleInner,
     #[serde(default)]
@@ -58,12 +58,30 @@ impl CskkCommandRule {
             direct: CskkCommandRuleInner::new_empty(),
             pre_composition: CskkCommandRuleInner::new_empty(),
             pre_composition_okurigana: CskkCommandRuleInner::new_empty(),
-            composition_selection: CskkCommandRuleInner::new_empty(),
+            composition_selection: CompositionSelectionRule::new_empty(),
             abbreviation: CskkCommandRuleInner::new_empty(),
             completion: CskkCommandRuleInner::new_empty(),
         }
     }
 
+    pub(crate) fn get_candidate_selection_rule(&self) -> &CandidateSelectionRule {
+        &self.composition_selection.candidate_selection
+    }
+
+    /// いずれかのモードでinstructionがキーに割り当てられていればtrue
+    pub(crate) fn contains_instruction(&self, instruction: &Instruction) -> bool {
+        [
+            &self.direct,
+            &self.pre_composition,
+            &self.pre_composition_okurigana,
+            &self.composition_selection.commands,
+            &self.abbreviation,
+            &self.completion,
+        ]
+        .iter()
+        .any(|inner| inner.contains_instruction(instruction))
+    }
+
     pub(crate) fn get_inner_ruleset(
         &self,
         composition_mode: &CompositionMode,
@@ -72,7 +90,7 @@ impl CskkCommandRule {
             CompositionMode::Direct => Some(&self.direct),
             CompositionMode::PreComposition => Some(&self.pre_composition),
             CompositionMode::PreCompositionOkurigana => Some(&self.pre_composition_okurigana),
-            CompositionMode::CompositionSelection => Some(&self.composition_selection),
+            CompositionMode::CompositionSelection => Some(&self.composition_selection.commands),
             CompositionMode::Abbreviation => Some(&self.abbreviation),
             CompositionMode::Completion => Some(&self.completion),
             _ => {
@@ -110,6 +128,19 @@ impl CskkCommandRuleInner {
         }
     }
 
+    fn contains_instruction(&self, instruction: &Instruction) -> bool {
+        [
+            &self.hiragana,
+            &self.katakana,
+            &self.hankakukatakana,
+            &self.zenkaku,
+            &self.ascii,
+        ]
+        .iter()
+        .flat_map(|command_map| command_map.values())
+        .any(|instructions| instructions.contains(instruction))
+    }
+
     pub(crate) fn get_command_map(
         &self,
         input_mode: &InputMode,
@@ -124,6 +155,61 @@ impl CskkCommandRuleInner {
     }
 }
 
+///
+/// composition_selectionのルール。他のモードと同じ入力モードごとのコマンドに加えて、候補一覧の設定を持つ。
+///
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub(crate) struct CompositionSelectionRule {
+    #[serde(flatten)]
+    commands: CskkCommandRuleInner,
+    #[serde(default)]
+    candidate_selection: CandidateSelectionRule,
+}
+
+impl CompositionSelectionRule {
+    pub(crate) fn new_empty() -> Self {
+        Self {
+            commands: CskkCommandRuleInner::new_empty(),
+            candidate_selection: CandidateSelectionRule::default(),
+        }
+    }
+}
+
+///
+/// 組み込みの候補選択を使う時の候補一覧の設定。
+/// [composition_selection.candidate_selection] で指定する。
+///
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub(crate) struct CandidateSelectionRule {
+    // 一覧を出すまでに一つずつ示す候補の数
+    pub(crate) show_after: usize,
+    // 一覧の1ページあたりの候補数。選択キーの数より多くはならない。
+    pub(crate) page_size: usize,
+    // 一覧の候補を選ぶキー
+    pub(crate) selection_keys: Vec<CskkKeyEvent>,
+}
+
+impl Default for CandidateSelectionRule {
+    fn default() -> Self {
+        Self {
+            show_after: 4,
+            page_size: 7,
+            selection_keys: ["a", "s", "d", "f", "j", "k", "l"]
+                .iter()
+                .filter_map(|key| CskkKeyEvent::from_str(key).ok())
+                .collect(),
+        }
+    }
+}
+
+impl CandidateSelectionRule {
+    pub(crate) fn effective_page_size(&self) -> usize {
+        self.page_size.min(self.selection_keys.len()).max(1)
+    }
+}
+
 impl CskkRule {
     #[allow(dead_code)]
     pub(crate) fn load_default_rule_file() -> Result<Self, CskkError> {
@@ -274,4 +360,34 @@ mod tests {
         println!("{result:?}");
         println!("{:?}", result.command.direct);
     }
+
+    #[test]
+    fn read_candidate_selection_rule() {
+        let str = r#"
+        [composition_selection.hiragana]
+        "space" = ["TryNextCandidate"]
+        [composition_selection.candidate_selection]
+        show_after = 2
+        selection_keys = ["1", "2", "3"]
+        "#;
+        let result = toml::from_str::<CommandOnly>(str).unwrap();
+        let candidate_selection = result.command.get_candidate_selection_rule();
+        assert_eq!(2, candidate_selection.show_after);
+        assert_eq!(7, candidate_selection.page_size);
+        assert_eq!(3, candidate_selection.effective_page_size());
+        assert_eq!(
+            CskkKeyEvent::from_str("2").unwrap(),
+            candidate_selection.selection_keys[1]
+        );
+        let hiragana_commands = result
+            .command
+            .get_inner_ruleset(&CompositionMode::CompositionSelection)
+            .unwrap()
+            .get_command_map(&InputMode::Hiragana)
+            .unwrap();
+        assert_eq!(
+            Some(&vec![Instruction::TryNextCandidate]),
+            hiragana_commands.get(&CskkKeyEvent::from_str("space").unwrap())
+        );
+    }
 }
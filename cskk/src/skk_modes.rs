@@ -109,6 +109,21 @@ pub enum CommaStyle {
     CommaEn,
 }
 
+///
+/// ホスト側の入力欄からフォーカスが外れた時に、入力中の状態をどう扱うか
+///
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(C)]
+pub enum FocusOutPolicy {
+    // Dumb naming for C FFI as C-style enum
+    /// 表示している変換候補かかなを確定する
+    FocusOutCommit,
+    /// 入力中のものを捨てる
+    FocusOutDiscard,
+    /// 入力中の状態を保存して表示から消し、focus_inで戻す
+    FocusOutKeep,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,101 @@
+use crate::utils::{
+    default_test_context, init_test_logger, static_and_empty_user_dictionary,
+    test_context_with_dictionaries, transition_check,
+};
+use cskk::skk_context_reset_rs;
+use cskk::skk_modes::{CompositionMode, InputMode};
+
+mod utils;
+
+#[test]
+fn auto_okuri_process_disabled_by_default() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "H a s i r u space",
+        "▼はしる【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn auto_okuri_process() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_auto_okuri_process(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "H a s i r u space Return",
+        "",
+        "走る",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    // 送りなしエントリの候補の後に続く
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a e r u space",
+        "▼蛙",
+        "",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "space",
+        "▼帰る",
+        "",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "space space Return",
+        "",
+        "変える",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn auto_okuri_candidate_is_learned_as_okuri_ari() {
+    init_test_logger();
+    let (static_dict, user_dict) = static_and_empty_user_dictionary(true);
+    let mut context = test_context_with_dictionaries(vec![user_dict.clone(), static_dict]);
+    context.set_auto_okuri_process(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a e r u space space space space Return",
+        "",
+        "変える",
+        InputMode::Hiragana,
+    );
+    let entries = user_dict.search_entries_by_midashi_prefix("か");
+    assert_eq!(1, entries.len());
+    assert_eq!("かe", entries[0].midashi);
+    assert!(entries[0].is_okuri_ari);
+    assert_eq!("変", entries[0].candidates[0].kouho_text);
+
+    // 学習した送りありエントリは送り仮名を指定した変換で先に出る
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a E r u",
+        "",
+        "変える",
+        InputMode::Hiragana,
+    );
+}
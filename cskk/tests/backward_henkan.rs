@@ -0,0 +1,116 @@
+use crate::utils::{default_test_context, init_test_logger, transition_check};
+use cskk::keyevent::CskkKeyEvent;
+use cskk::skk_modes::{CompositionMode, InputMode};
+use cskk::{skk_context_set_input_mode_rs, CskkContext};
+use std::str::FromStr;
+
+mod utils;
+
+fn backward_henkan_test_context() -> CskkContext {
+    let mut context = default_test_context();
+    context
+        .set_rule_from_directory("backward_henkan", "tests/data/rules")
+        .unwrap();
+    context
+}
+
+#[test]
+fn backward_set_henkan_point_whole_run() {
+    init_test_logger();
+    let mut context = backward_henkan_test_context();
+    context.process_key_events_string("K a n n j i space Return k a n n j i C-q");
+    assert_eq!("漢字▽かんじ", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::PreComposition,
+        context.get_current_composition_mode()
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::PreComposition,
+        InputMode::Hiragana,
+        "space Return",
+        "",
+        "漢字漢字",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn backward_set_henkan_point_one_char_at_a_time() {
+    init_test_logger();
+    let mut context = backward_henkan_test_context();
+    context.process_key_events_string("k a n n j i C-b");
+    assert_eq!("かん▽じ", context.get_preedit().unwrap());
+    context.process_key_events_string("C-b");
+    assert_eq!("か▽んじ", context.get_preedit().unwrap());
+    context.process_key_events_string("C-b");
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-b").unwrap()));
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    transition_check(
+        &mut context,
+        CompositionMode::PreComposition,
+        InputMode::Hiragana,
+        "space",
+        "▼漢字",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn backward_set_henkan_point_surrounding_text() {
+    init_test_logger();
+    let mut context = backward_henkan_test_context();
+    context.process_key_events_string("k a n n j i");
+    assert_eq!("かんじ", context.poll_output().unwrap());
+    context.set_surrounding_text("漢字かんじ");
+    context.process_key_events_string("C-q");
+    assert_eq!(3, context.poll_delete_surrounding());
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    transition_check(
+        &mut context,
+        CompositionMode::PreComposition,
+        InputMode::Hiragana,
+        "space Return",
+        "",
+        "漢字",
+        InputMode::Hiragana,
+    );
+
+    // 渡した文字列は次のキー入力の処理でのみ使われる
+    context.process_key_events_string("k a n n j i");
+    assert_eq!("かんじ", context.poll_output().unwrap());
+    context.set_surrounding_text("かんじ");
+    context.process_key_events_string("C-b");
+    assert_eq!(1, context.poll_delete_surrounding());
+    assert_eq!("▽じ", context.get_preedit().unwrap());
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-b").unwrap()));
+    context.set_surrounding_text("かん");
+    context.process_key_events_string("C-b");
+    assert_eq!(1, context.poll_delete_surrounding());
+    assert_eq!("▽んじ", context.get_preedit().unwrap());
+}
+
+#[test]
+fn backward_set_henkan_point_katakana() {
+    init_test_logger();
+    let mut context = backward_henkan_test_context();
+    skk_context_set_input_mode_rs(&mut context, InputMode::Katakana);
+    context.process_key_events_string("a k o u C-q");
+    assert_eq!("▽アコウ", context.get_preedit().unwrap());
+}
+
+#[test]
+fn backward_set_henkan_point_without_kana() {
+    init_test_logger();
+    let mut context = backward_henkan_test_context();
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-q").unwrap()));
+    context.process_key_events_string("K a n n j i space Return");
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-q").unwrap()));
+    assert_eq!("漢字", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::Direct,
+        context.get_current_composition_mode()
+    );
+}
@@ -0,0 +1,174 @@
+use crate::utils::{default_test_context, init_test_logger, transition_check};
+use cskk::cskkstate::{CandidatePage, CskkStateInfo};
+use cskk::skk_context_reset_rs;
+use cskk::skk_modes::{CompositionMode, InputMode};
+use cskk::CskkContext;
+
+mod utils;
+
+fn current_page(context: &CskkContext) -> Option<CandidatePage> {
+    match context.get_preedit_detail().pop() {
+        Some(CskkStateInfo::CompositionSelection(composition_selection_data)) => {
+            composition_selection_data.page
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn builtin_candidate_selection_disabled_by_default() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space space space space space",
+        "▼公",
+        "",
+        InputMode::Hiragana,
+    );
+    assert_eq!(None, current_page(&context));
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "a",
+        "",
+        "公あ",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn builtin_candidate_selection_paging() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_builtin_candidate_selection(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space space space space",
+        "▼工",
+        "",
+        InputMode::Hiragana,
+    );
+    assert_eq!(None, current_page(&context));
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "space",
+        "▼公",
+        "",
+        InputMode::Hiragana,
+    );
+    assert_eq!(
+        Some(CandidatePage {
+            candidates: ["公", "向", "効", "校", "交", "考", "項"]
+                .iter()
+                .map(|candidate| candidate.to_string())
+                .collect(),
+            labels: ["A", "S", "D", "F", "J", "K", "L"]
+                .iter()
+                .map(|label| label.to_string())
+                .collect(),
+            page_index: 0,
+            page_count: 12,
+        }),
+        current_page(&context)
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "space",
+        "▼稿",
+        "",
+        InputMode::Hiragana,
+    );
+    assert_eq!(1, current_page(&context).unwrap().page_index);
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "x",
+        "▼公",
+        "",
+        InputMode::Hiragana,
+    );
+    // 最初のページからは一覧を出す前の最後の候補に戻る
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "x",
+        "▼工",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn builtin_candidate_selection_select_by_key() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_builtin_candidate_selection(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space space space space space d",
+        "",
+        "効",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    // 一覧の表示中はコマンドより選択キーを優先する
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space space space space space l",
+        "",
+        "項",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    // 一覧を出す前の選択キーは通常の入力
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space a",
+        "",
+        "行あ",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn builtin_candidate_selection_register_after_last_page() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_builtin_candidate_selection(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K i k a i space space",
+        "▼機会",
+        "",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "space",
+        "▼きかい【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
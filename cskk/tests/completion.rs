@@ -1,9 +1,12 @@
 use crate::utils::{
-    default_test_context, init_test_logger, test_context_with_dictionaries, transition_check,
+    default_test_context, init_test_logger, make_temp_file, static_and_empty_user_dictionary,
+    test_context_with_dictionaries, transition_check, transition_test,
 };
-use cskk::dictionary::CskkDictionary;
+use cskk::cskkstate::{CompleteData, CompletionSuggestion, CskkStateInfo, PreCompositionData};
+use cskk::dictionary::{CskkDictionary, DictionaryEntryOrder};
 use cskk::skk_context_reset_rs;
 use cskk::skk_modes::{CompositionMode, InputMode};
+use std::fs::File;
 use std::sync::Arc;
 
 mod utils;
@@ -244,3 +247,454 @@ pub fn completion_mode_abort_to_abbreviation() {
         InputMode::Hiragana,
     );
 }
+
+#[test]
+pub fn dynamic_completion_suggestions() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_dynamic_completion_count(3);
+    transition_test(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "H o k k a i",
+        CompositionMode::PreComposition,
+        InputMode::Hiragana,
+        CskkStateInfo::PreComposition(PreCompositionData {
+            confirmed: "".to_string(),
+            kana_to_composite: "ほっかい".to_string(),
+            cursor: 4,
+            okuri: None,
+            unconverted: None,
+            completion_suggestions: vec![CompletionSuggestion {
+                midashi: "ほっかいどう".to_string(),
+                output: "北海道".to_string(),
+                okuri: None,
+                annotation: None,
+            }],
+        }),
+    );
+}
+
+#[test]
+pub fn dynamic_completion_suggestions_limited_and_updated() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_dynamic_completion_count(2);
+    context.process_key_events_string("M a k u");
+    let suggestions = context.get_completion_suggestions();
+    assert_eq!(2, suggestions.len());
+    assert!(suggestions
+        .iter()
+        .all(|suggestion| suggestion.midashi.starts_with("まく")));
+
+    context.process_key_events_string("r a");
+    let suggestions = context.get_completion_suggestions();
+    assert_eq!("まくら", suggestions[0].midashi);
+    assert_eq!("枕", suggestions[0].output);
+    assert_eq!(
+        CompositionMode::PreComposition,
+        context.get_current_composition_mode()
+    );
+
+    // 補完候補は見出し語入力中のみ
+    context.process_key_events_string("space");
+    assert!(context.get_completion_suggestions().is_empty());
+}
+
+#[test]
+pub fn dynamic_completion_disabled_by_default() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("H o k k a i");
+    assert!(context.get_completion_suggestions().is_empty());
+    assert!(!context.accept_completion_suggestion(0));
+}
+
+#[test]
+pub fn accept_completion_suggestion() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_dynamic_completion_count(3);
+    context.process_key_events_string("H o k k a i");
+    assert!(!context.accept_completion_suggestion(3));
+    assert!(context.accept_completion_suggestion(0));
+    assert_eq!("北海道", context.poll_output().unwrap());
+    assert_eq!("", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::Direct,
+        context.get_current_composition_mode()
+    );
+    assert!(context.get_completion_suggestions().is_empty());
+}
+
+///
+/// 空のユーザー辞書とSKK-JISYO.Sのcontextを作る。ユーザー辞書の位置はuser_dict_firstで決める。
+///
+fn context_with_empty_user_dictionary(user_dict_first: bool) -> cskk::CskkContext {
+    let (static_dict, user_dict) = static_and_empty_user_dictionary(true);
+    if user_dict_first {
+        test_context_with_dictionaries(vec![user_dict, static_dict])
+    } else {
+        test_context_with_dictionaries(vec![static_dict, user_dict])
+    }
+}
+
+#[test]
+pub fn completion_ranked_by_recent_use() {
+    init_test_logger();
+    let mut context = context_with_empty_user_dictionary(true);
+    // 辞書順では「まく」が「まくら」より先だが、最後に使った「まくら」を先に補完する。
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M a k u space space Return M a k u r a space Return",
+        "",
+        "幕枕",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M a k u Tab",
+        "■枕",
+        "",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M a k u Tab Tab",
+        "■幕",
+        "",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    // 重複する候補は除かれ、使っていない候補が後に続く。
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M a k u Tab Tab Tab",
+        "■膜",
+        "",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+
+    // 使い直すと順位が上がる。学習済みなので「幕」が最初の変換候補。
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M a k u space Return M a k u Tab",
+        "■幕",
+        "幕",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+pub fn completion_ranked_by_use_count() {
+    init_test_logger();
+    let mut context = context_with_empty_user_dictionary(true);
+    context.set_dynamic_completion_count(2);
+    // 「まく」を2回使った後に「まくら」を使っても、回数の多い「まく」を先に補完する。
+    context.process_key_events_string(
+        "M a k u space space Return M a k u space Return M a k u r a space Return",
+    );
+    assert_eq!("幕幕枕", context.poll_output().unwrap());
+    context.process_key_events_string("M a k u");
+    let suggestions = context
+        .get_completion_suggestions()
+        .into_iter()
+        .map(|suggestion| suggestion.output)
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["幕", "枕"], suggestions);
+
+    // 回数が並ぶと最近使用した順になる。
+    context.process_key_events_string("C-g M a k u r a space Return");
+    assert_eq!("枕", context.poll_output().unwrap());
+    context.process_key_events_string("M a k u");
+    let suggestions = context
+        .get_completion_suggestions()
+        .into_iter()
+        .map(|suggestion| suggestion.output)
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["枕", "幕"], suggestions);
+}
+
+#[test]
+pub fn completion_ranked_by_recent_use_after_reloading_user_dictionary() {
+    init_test_logger();
+    let static_dict = Arc::new(
+        CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", true)
+            .unwrap(),
+    );
+    let temppath = make_temp_file().unwrap();
+    File::create(&temppath).unwrap();
+    let user_dict =
+        Arc::new(CskkDictionary::new_user_dict(temppath.to_str().unwrap(), "utf-8", true).unwrap());
+    let mut context = test_context_with_dictionaries(vec![user_dict.clone(), static_dict.clone()]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M a k u space space Return M a k u r a space Return",
+        "",
+        "幕枕",
+        InputMode::Hiragana,
+    );
+    assert!(user_dict.save().unwrap());
+
+    // 保存したファイルから読み直しても、最後に使った「まくら」を先に補完する。
+    let reloaded_user_dict =
+        Arc::new(CskkDictionary::new_user_dict(temppath.to_str().unwrap(), "utf-8", true).unwrap());
+    let mut context = test_context_with_dictionaries(vec![reloaded_user_dict, static_dict]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M a k u Tab",
+        "■枕",
+        "",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M a k u Tab Tab",
+        "■幕",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+pub fn completion_keeps_dictionary_order_when_user_dictionary_is_last() {
+    init_test_logger();
+    let mut context = context_with_empty_user_dictionary(false);
+    context.set_dynamic_completion_count(5);
+    context.process_key_events_string("S h u u");
+    let before = context
+        .get_completion_suggestions()
+        .into_iter()
+        .map(|suggestion| suggestion.output)
+        .collect::<Vec<_>>();
+    assert!(before.len() > 1);
+    assert_ne!("週末", before[0]);
+    context.process_key_events_string("C-g");
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "S h u u m a t s u space Return",
+        "",
+        "週末",
+        InputMode::Hiragana,
+    );
+    // ユーザー辞書は静的辞書の後にあるので、最近使用した候補も静的辞書の候補より先には並ばない。
+    context.process_key_events_string("S h u u");
+    let after = context
+        .get_completion_suggestions()
+        .into_iter()
+        .map(|suggestion| suggestion.output)
+        .collect::<Vec<_>>();
+    assert_eq!(before, after);
+}
+
+#[test]
+pub fn dynamic_completion_suggestions_stop_at_count() {
+    init_test_logger();
+    let mut context = context_with_empty_user_dictionary(true);
+    context.set_dynamic_completion_count(100);
+    context.process_key_events_string("M a k u");
+    let all = context.get_completion_suggestions();
+    assert!(all.len() > 2);
+    context.set_dynamic_completion_count(2);
+    context.process_key_events_string("C-g M a k u");
+    let limited = context.get_completion_suggestions();
+    assert_eq!(2, limited.len());
+    assert_eq!(all[0].output, limited[0].output);
+    assert_eq!(all[1].output, limited[1].output);
+}
+
+#[test]
+pub fn dynamic_completion_suggestions_ranked_by_recent_use() {
+    init_test_logger();
+    let mut context = context_with_empty_user_dictionary(true);
+    context.set_dynamic_completion_count(2);
+    context.process_key_events_string("M a k u r a space Return");
+    context.poll_output();
+    context.process_key_events_string("M a k u");
+    let suggestions = context.get_completion_suggestions();
+    assert_eq!("枕", suggestions[0].output);
+    assert_eq!("膜", suggestions[1].output);
+}
+
+#[test]
+pub fn dynamic_completion_suggestions_follow_api_changes() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_dynamic_completion_count(2);
+    context.process_key_events_string("M a k u");
+    let suggestions = context.get_completion_suggestions();
+    assert_eq!(2, suggestions.len());
+
+    // 辞書を変えると探し直す。
+    let static_dict = Arc::new(
+        CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", true)
+            .unwrap(),
+    );
+    context.set_dictionaries(vec![Arc::new(CskkDictionary::new_empty_dict().unwrap())]);
+    assert!(context.get_completion_suggestions().is_empty());
+    context.set_dictionaries(vec![static_dict]);
+    assert_eq!(suggestions, context.get_completion_suggestions());
+
+    // 状態を変えるAPIの後も探し直す。
+    let snapshot = context.snapshot().unwrap();
+    skk_context_reset_rs(&mut context);
+    assert!(context.get_completion_suggestions().is_empty());
+    context.restore(&snapshot).unwrap();
+    assert_eq!(suggestions, context.get_completion_suggestions());
+}
+
+#[test]
+pub fn okuri_ari_completion() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_test(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n g a Tab",
+        CompositionMode::Completion,
+        InputMode::Hiragana,
+        CskkStateInfo::Complete(CompleteData {
+            confirmed: "".to_string(),
+            complete_origin: "かんが".to_string(),
+            completed_midashi: "かんがe".to_string(),
+            completed: "考".to_string(),
+            okuri: Some("え".to_string()),
+            annotation: None,
+        }),
+    );
+    skk_context_reset_rs(&mut context);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n g a Tab Return",
+        "",
+        "考え",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Katakana,
+        "K a n g a Tab Return",
+        "",
+        "考エ",
+        InputMode::Katakana,
+    );
+}
+
+#[test]
+pub fn okuri_ari_completion_with_strict_okuri() {
+    init_test_logger();
+    let dict =
+        CskkDictionary::new_static_dict("tests/data/dictionaries/strict_okuri.dat", "utf-8", true)
+            .unwrap();
+    let mut context = test_context_with_dictionaries(vec![Arc::new(dict)]);
+    // 送り仮名の定まらない厳密な送り仮名のない候補は補完しない
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "O k u Tab",
+        "■贈って",
+        "",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::Completion,
+        InputMode::Hiragana,
+        "Tab Return",
+        "",
+        "贈って",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+pub fn okuri_ari_completion_learns_as_okuri_ari_conversion() {
+    init_test_logger();
+    let static_dict = Arc::new(
+        CskkDictionary::new_static_dict("tests/data/dictionaries/strict_okuri.dat", "utf-8", true)
+            .unwrap(),
+    );
+    let temppath = make_temp_file().unwrap();
+    File::create(&temppath).unwrap();
+    let user_dict =
+        Arc::new(CskkDictionary::new_user_dict(temppath.to_str().unwrap(), "utf-8", true).unwrap());
+    let mut context = test_context_with_dictionaries(vec![user_dict.clone(), static_dict]);
+    // 補さ、保さ、欲す の順
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "H o Tab Tab Tab Return",
+        "",
+        "欲す",
+        InputMode::Hiragana,
+    );
+
+    let entries = user_dict.get_entries(DictionaryEntryOrder::Sorted);
+    assert_eq!(1, entries.len());
+    assert_eq!("ほs", entries[0].midashi);
+    assert!(entries[0].is_okuri_ari);
+    assert!(entries[0]
+        .candidates
+        .iter()
+        .any(|candidate| candidate.kouho_text == "欲"
+            && candidate.strict_okuri == Some("す".to_string())));
+
+    // 学習した候補が通常の送りあり変換で先頭に来る
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "H o S u",
+        "▼欲す",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+pub fn accept_okuri_ari_completion_suggestion() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_dynamic_completion_count(3);
+    context.process_key_events_string("K a n g a");
+    let suggestions = context.get_completion_suggestions();
+    assert_eq!(
+        vec![CompletionSuggestion {
+            midashi: "かんがe".to_string(),
+            output: "考".to_string(),
+            okuri: Some("え".to_string()),
+            annotation: None,
+        }],
+        suggestions
+    );
+    assert!(context.accept_completion_suggestion(0));
+    assert_eq!("考え", context.poll_output().unwrap());
+}
@@ -0,0 +1,88 @@
+mod utils;
+
+use crate::utils::{make_temp_file, test_context_with_dictionaries, transition_check};
+use cskk::dictionary::{CskkDictionary, DictionaryEntryOrder};
+use cskk::skk_modes::{CompositionMode, InputMode};
+use std::fs::copy;
+use std::sync::Arc;
+use std::thread;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn dictionary_is_send_and_sync() {
+    assert_send_sync::<CskkDictionary>();
+}
+
+///
+/// 複数のスレッドのcontextから同じ辞書を同時に検索し、学習で書き込んでも辞書が壊れないこと。
+///
+#[test]
+fn lookup_and_learn_from_many_threads() {
+    let static_dict = Arc::new(
+        CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", true)
+            .unwrap(),
+    );
+    let temppath = make_temp_file().unwrap();
+    let temppath = temppath.to_str().unwrap().to_string();
+    copy("tests/data/dictionaries/userdict.dat", &temppath).unwrap();
+    let user_dict = Arc::new(CskkDictionary::new_user_dict(&temppath, "utf-8", true).unwrap());
+
+    let mut handles = vec![];
+    for thread_index in 0..8 {
+        let static_dict = static_dict.clone();
+        let user_dict = user_dict.clone();
+        handles.push(thread::spawn(move || {
+            let mut context = test_context_with_dictionaries(vec![user_dict.clone(), static_dict]);
+            for i in 0..50 {
+                transition_check(
+                    &mut context,
+                    CompositionMode::Direct,
+                    InputMode::Hiragana,
+                    "K a n j i space Return",
+                    "",
+                    "漢字",
+                    InputMode::Hiragana,
+                );
+                transition_check(
+                    &mut context,
+                    CompositionMode::Direct,
+                    InputMode::Hiragana,
+                    "A i space Return",
+                    "",
+                    "愛",
+                    InputMode::Hiragana,
+                );
+                assert!(!context.get_readings_for("変換").is_empty());
+                // 学習以外の書き込みも同時に行う
+                user_dict
+                    .add_candidate(
+                        &format!("すれっど{thread_index}"),
+                        &format!("{i}"),
+                        None,
+                        None,
+                    )
+                    .unwrap();
+            }
+        }));
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let entries = user_dict.get_entries(DictionaryEntryOrder::Sorted);
+    for thread_index in 0..8 {
+        let entry = entries
+            .iter()
+            .find(|entry| entry.midashi == format!("すれっど{thread_index}"))
+            .unwrap();
+        assert_eq!(50, entry.candidates.len());
+        // 最後に追加したものが先頭
+        assert_eq!("49", entry.candidates[0].kouho_text);
+    }
+    let kanji = entries
+        .iter()
+        .find(|entry| entry.midashi == "かんじ")
+        .unwrap();
+    assert_eq!("漢字", kanji.candidates[0].kouho_text);
+}
@@ -1,4 +1,6 @@
-use crate::utils::{init_test_logger, test_context_with_dictionaries, transition_test};
+use crate::utils::{
+    init_test_logger, make_temp_file, test_context_with_dictionaries, transition_test,
+};
 use cskk::cskkstate::{CompositionSelectionData, CskkStateInfo};
 use cskk::dictionary::CskkDictionary;
 use cskk::skk_context_reset_rs;
@@ -39,6 +41,10 @@ pub fn dictionary_order_should_be_preserved() {
             composited: "腸".to_string(),
             okuri: None,
             annotation: None,
+            corrected_reading: None,
+            hint: None,
+            hint_unconverted: None,
+            page: None,
         }),
     );
     skk_context_reset_rs(&mut context);
@@ -54,6 +60,10 @@ pub fn dictionary_order_should_be_preserved() {
             composited: "超".to_string(),
             okuri: None,
             annotation: None,
+            corrected_reading: None,
+            hint: None,
+            hint_unconverted: None,
+            page: None,
         }),
     );
     skk_context_reset_rs(&mut context);
@@ -69,6 +79,10 @@ pub fn dictionary_order_should_be_preserved() {
             composited: "帳".to_string(),
             okuri: None,
             annotation: None,
+            corrected_reading: None,
+            hint: None,
+            hint_unconverted: None,
+            page: None,
         }),
     );
     skk_context_reset_rs(&mut context);
@@ -84,6 +98,37 @@ pub fn dictionary_order_should_be_preserved() {
             composited: "長".to_string(),
             okuri: None,
             annotation: Some("人名".to_string()),
+            corrected_reading: None,
+            hint: None,
+            hint_unconverted: None,
+            page: None,
         }),
     );
 }
+
+#[test]
+pub fn reverse_lookup_readings() {
+    init_test_logger();
+    let static_dict =
+        CskkDictionary::new_static_dict("tests/data/dictionaries/strict_okuri.dat", "utf-8", false)
+            .unwrap();
+    let temppath = make_temp_file().unwrap();
+    let user_dict =
+        CskkDictionary::new_user_dict(temppath.to_str().unwrap(), "utf-8", false).unwrap();
+    user_dict.add_candidate("ほし", "干", None, None).unwrap();
+    let context = test_context_with_dictionaries(vec![Arc::new(static_dict), Arc::new(user_dict)]);
+
+    let readings = context.get_readings_for("干");
+    assert_eq!(3, readings.len());
+    assert_eq!("ほs", readings[0].midashi);
+    assert_eq!("ほ", readings[0].reading);
+    assert!(readings[0].is_okuri_ari);
+    assert_eq!(None, readings[0].strict_okuri);
+    assert_eq!(Some("せ".to_string()), readings[1].strict_okuri);
+    assert_eq!(0, readings[1].dictionary_index);
+    assert_eq!("ほし", readings[2].reading);
+    assert!(!readings[2].is_okuri_ari);
+    assert_eq!(1, readings[2].dictionary_index);
+
+    assert!(context.get_readings_for("存在しない").is_empty());
+}
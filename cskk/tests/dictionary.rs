@@ -1,10 +1,16 @@
 mod utils;
 
 use crate::utils::{make_temp_file, test_context_with_dictionaries, transition_check};
-use cskk::dictionary::CskkDictionary;
+use cskk::dictionary::ime_format::{
+    convert_ime_to_skk_jisyo, convert_skk_jisyo_to_ime, read_ime_dictionary, ImeDictionaryFormat,
+};
+use cskk::dictionary::{CskkDictionary, DictionaryEntryOrder};
 use cskk::skk_modes::{CompositionMode, InputMode};
 use cskk::{skk_context_reload_dictionary, skk_context_reset_rs, skk_context_save_dictionaries_rs};
+use std::fs::{copy, write};
+use std::sync::mpsc::channel;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[test]
 fn save_dict() {
@@ -136,3 +142,294 @@ fn concat_dict_entry_read() {
         InputMode::Hiragana,
     );
 }
+
+#[test]
+fn edit_user_dictionary() {
+    let temppath = make_temp_file().unwrap();
+    let user_dict_path = temppath.to_str().unwrap();
+    let user_dict =
+        Arc::new(CskkDictionary::new_user_dict(user_dict_path, "utf-8", false).unwrap());
+    assert!(user_dict.add_candidate("ほげ", "保下", None, None).unwrap());
+    assert!(user_dict
+        .add_candidate("ほげ", "歩毛", Some("annotation"), None)
+        .unwrap());
+    assert!(user_dict
+        .add_candidate("ふがs", "府賀", None, Some("す"))
+        .unwrap());
+    assert!(user_dict.move_candidate("ほげ", None, "歩毛", 1).unwrap());
+
+    let entries = user_dict.get_entries(DictionaryEntryOrder::Sorted);
+    assert_eq!(2, entries.len());
+    assert_eq!("ふがs", entries[0].midashi);
+    assert!(entries[0].is_okuri_ari);
+    assert_eq!(
+        Some("す".to_string()),
+        entries[0].candidates[1].strict_okuri
+    );
+    assert_eq!("ほげ", entries[1].midashi);
+    assert_eq!("保下", entries[1].candidates[0].kouho_text);
+    assert_eq!(
+        Some("annotation".to_string()),
+        entries[1].candidates[1].annotation
+    );
+
+    assert_eq!(1, user_dict.search_entries_by_midashi_prefix("ほ").len());
+    assert_eq!(1, user_dict.search_entries_by_candidate("賀").len());
+
+    let mut context = test_context_with_dictionaries(vec![user_dict.clone()]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "H o g e space",
+        "▼保下",
+        "",
+        InputMode::Hiragana,
+    );
+
+    assert!(user_dict.delete_entry("ほげ").unwrap());
+    assert!(user_dict.delete_candidate("ふがs", None, "府賀").unwrap());
+    assert!(user_dict.get_entries(DictionaryEntryOrder::Lru).is_empty());
+}
+
+#[test]
+fn export_and_import_dictionary() {
+    let static_dict =
+        CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+            .unwrap();
+    let exported = make_temp_file().unwrap();
+    let exported_path = exported.to_str().unwrap();
+    static_dict
+        .export_entries(exported_path, "utf-8", DictionaryEntryOrder::Sorted)
+        .unwrap();
+
+    let temppath = make_temp_file().unwrap();
+    let user_dict =
+        CskkDictionary::new_user_dict(temppath.to_str().unwrap(), "utf-8", false).unwrap();
+    assert!(user_dict.import_entries(exported_path, "utf-8").unwrap() > 0);
+    assert_eq!(
+        static_dict.get_entries(DictionaryEntryOrder::Sorted),
+        user_dict.get_entries(DictionaryEntryOrder::Sorted)
+    );
+    // Nothing new on second import.
+    assert_eq!(0, user_dict.import_entries(exported_path, "utf-8").unwrap());
+}
+
+#[test]
+fn import_dictionary_keeps_lru_order() {
+    let source = make_temp_file().unwrap();
+    let source_path = source.to_str().unwrap();
+    write(
+        source_path,
+        ";; okuri-ari entries.\n;; okuri-nasi entries.\nあい /愛/\nうえ /上/\nかき /柿/\n",
+    )
+    .unwrap();
+    let source_dict = CskkDictionary::new_user_dict(source_path, "utf-8", false).unwrap();
+    let exported = make_temp_file().unwrap();
+    let exported_path = exported.to_str().unwrap();
+    source_dict
+        .export_entries(exported_path, "utf-8", DictionaryEntryOrder::Lru)
+        .unwrap();
+
+    let temppath = make_temp_file().unwrap();
+    let user_dict =
+        CskkDictionary::new_user_dict(temppath.to_str().unwrap(), "utf-8", false).unwrap();
+    assert_eq!(3, user_dict.import_entries(exported_path, "utf-8").unwrap());
+    let midashi = user_dict
+        .get_entries(DictionaryEntryOrder::Lru)
+        .into_iter()
+        .map(|entry| entry.midashi)
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["あい", "うえ", "かき"], midashi);
+    assert_eq!(
+        source_dict.get_entries(DictionaryEntryOrder::Lru),
+        user_dict.get_entries(DictionaryEntryOrder::Lru)
+    );
+}
+
+#[test]
+fn convert_ime_dictionary() {
+    let skk_jisyo = make_temp_file().unwrap();
+    let skk_jisyo_path = skk_jisyo.to_str().unwrap();
+    assert_eq!(
+        4,
+        convert_ime_to_skk_jisyo(
+            "tests/data/dictionaries/mozc_user_dictionary.tsv",
+            ImeDictionaryFormat::MozcTsv,
+            skk_jisyo_path,
+            "utf-8",
+        )
+        .unwrap()
+    );
+    let user_dict =
+        Arc::new(CskkDictionary::new_user_dict(skk_jisyo_path, "utf-8", false).unwrap());
+    let mut context = test_context_with_dictionaries(vec![user_dict]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n g a E r u",
+        "",
+        "考える",
+        InputMode::Hiragana,
+    );
+
+    let exported = make_temp_file().unwrap();
+    let exported_path = exported.to_str().unwrap();
+    let report = convert_skk_jisyo_to_ime(
+        skk_jisyo_path,
+        "utf-8",
+        exported_path,
+        ImeDictionaryFormat::MozcTsv,
+    )
+    .unwrap();
+    assert_eq!(4, report.written);
+    assert!(report.skipped.is_empty());
+    let mut original = read_ime_dictionary(
+        "tests/data/dictionaries/mozc_user_dictionary.tsv",
+        ImeDictionaryFormat::MozcTsv,
+    )
+    .unwrap();
+    // 送りなしエントリには品詞が無いので、形容動詞は名詞として書き出される。
+    original
+        .iter_mut()
+        .filter(|word| word.part_of_speech == "形容動詞")
+        .for_each(|word| word.part_of_speech = "名詞".to_string());
+    let mut words = read_ime_dictionary(exported_path, ImeDictionaryFormat::MozcTsv).unwrap();
+    original.sort_by(|a, b| a.reading.cmp(&b.reading));
+    words.sort_by(|a, b| a.reading.cmp(&b.reading));
+    assert_eq!(original, words);
+}
+
+#[test]
+fn convert_skk_dictionary_in_file_order() {
+    let skk_jisyo = make_temp_file().unwrap();
+    let skk_jisyo_path = skk_jisyo.to_str().unwrap();
+    write(
+        skk_jisyo_path,
+        ";; okuri-ari entries.\n;; okuri-nasi entries.\nうえ /上/\nあい /愛/\n",
+    )
+    .unwrap();
+    let exported = make_temp_file().unwrap();
+    let exported_path = exported.to_str().unwrap();
+    convert_skk_jisyo_to_ime(
+        skk_jisyo_path,
+        "utf-8",
+        exported_path,
+        ImeDictionaryFormat::MozcTsv,
+    )
+    .unwrap();
+    let readings = read_ime_dictionary(exported_path, ImeDictionaryFormat::MozcTsv)
+        .unwrap()
+        .into_iter()
+        .map(|word| word.reading)
+        .collect::<Vec<_>>();
+    // 辞書ファイル上の順に書き出す
+    assert_eq!(vec!["うえ", "あい"], readings);
+}
+
+#[test]
+fn load_static_dict_in_background() {
+    let (sender, receiver) = channel();
+    let dict = CskkDictionary::new_static_dict_in_background(
+        "tests/data/dictionaries/SKK-JISYO.S",
+        "euc-jp",
+        false,
+        Some(Box::new(move || sender.send(()).unwrap())),
+    )
+    .unwrap();
+    receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert!(dict.is_ready());
+    assert_eq!(1.0, dict.load_progress());
+
+    let sync_dict =
+        CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+            .unwrap();
+    assert_eq!(
+        sync_dict.get_entries(DictionaryEntryOrder::Sorted),
+        dict.get_entries(DictionaryEntryOrder::Sorted)
+    );
+    let mut context = test_context_with_dictionaries(vec![Arc::new(dict)]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "A i space Return",
+        "",
+        "愛",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn load_static_dict_in_background_missing_file() {
+    assert!(CskkDictionary::new_static_dict_in_background(
+        "tests/data/dictionaries/not_exist.dat",
+        "utf-8",
+        false,
+        None,
+    )
+    .is_err());
+}
+
+#[test]
+fn load_user_dict_in_background_keeps_lru_order() {
+    let temppath = make_temp_file().unwrap();
+    let temppath = temppath.to_str().unwrap();
+    write(
+        temppath,
+        ";; okuri-ari entries.\n;; okuri-nasi entries.\nあい /愛/\nうえ /上/\nおか /丘/\n",
+    )
+    .unwrap();
+    let (sender, receiver) = channel();
+    let dict = CskkDictionary::new_user_dict_in_background(
+        temppath,
+        "utf-8",
+        false,
+        Some(Box::new(move || sender.send(()).unwrap())),
+    )
+    .unwrap();
+    // 読み込みの前後どちらで確定しても、読み込んだエントリより最近使用したものになる。
+    dict.add_candidate("うえ", "飢", None, None).unwrap();
+    dict.add_candidate("かき", "柿", None, None).unwrap();
+    receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert!(dict.is_ready());
+
+    let entries = dict.get_entries(DictionaryEntryOrder::Lru);
+    let midashi = entries
+        .iter()
+        .map(|entry| entry.midashi.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["かき", "うえ", "あい", "おか"], midashi);
+    let kouho = entries[1]
+        .candidates
+        .iter()
+        .map(|candidate| candidate.kouho_text.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["飢", "上"], kouho);
+}
+
+#[test]
+fn save_user_dict_while_loading_in_background() {
+    let temppath = make_temp_file().unwrap();
+    let temppath = temppath.to_str().unwrap();
+    copy("tests/data/dictionaries/SKK-JISYO.S", temppath).unwrap();
+    let dict = Arc::new(
+        CskkDictionary::new_user_dict_in_background(temppath, "euc-jp", false, None).unwrap(),
+    );
+    // 読み込みの途中でも確定でき、保存は読み込みを待つので元のエントリを失わない。
+    dict.add_candidate("ほげ", "保下", None, None).unwrap();
+    assert!(dict.save().unwrap());
+    assert!(dict.is_ready());
+
+    let reloaded = CskkDictionary::new_user_dict(temppath, "euc-jp", false).unwrap();
+    let entries = reloaded.get_entries(DictionaryEntryOrder::Sorted);
+    assert!(entries.iter().any(|entry| entry.midashi == "ほげ"));
+    let original =
+        CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+            .unwrap();
+    assert_eq!(
+        original.get_entries(DictionaryEntryOrder::Sorted).len() + 1,
+        entries.len()
+    );
+}
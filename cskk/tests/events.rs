@@ -0,0 +1,123 @@
+use crate::utils::{
+    default_test_context, init_test_logger, static_and_empty_user_dictionary,
+    test_context_with_dictionaries,
+};
+use cskk::event::{CskkEvent, DictionaryUpdateKind};
+use cskk::skk_modes::{CompositionMode, InputMode};
+use cskk::CskkContext;
+
+mod utils;
+
+fn user_dict_test_context() -> CskkContext {
+    let (static_dict, user_dict) = static_and_empty_user_dictionary(false);
+    test_context_with_dictionaries(vec![user_dict, static_dict])
+}
+
+#[test]
+fn commit_and_mode_change_events() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("a");
+    assert_eq!(
+        vec![CskkEvent::Commit("あ".to_string())],
+        context.poll_events()
+    );
+    // poll_eventsで取り出した確定文字列はpoll_outputでは返らない
+    assert_eq!(None, context.poll_output());
+
+    context.process_key_events_string("K");
+    assert_eq!(
+        vec![CskkEvent::ModeChanged {
+            input_mode: InputMode::Hiragana,
+            composition_mode: CompositionMode::PreComposition,
+        }],
+        context.poll_events()
+    );
+    context.process_key_events_string("a n j i");
+    assert_eq!(Vec::<CskkEvent>::new(), context.poll_events());
+    context.process_key_events_string("space");
+    assert_eq!(
+        vec![
+            CskkEvent::ModeChanged {
+                input_mode: InputMode::Hiragana,
+                composition_mode: CompositionMode::CompositionSelection,
+            },
+            CskkEvent::CandidatesChanged,
+        ],
+        context.poll_events()
+    );
+    context.process_key_events_string("space");
+    assert_eq!(vec![CskkEvent::CandidatesChanged], context.poll_events());
+}
+
+#[test]
+fn learned_event() {
+    init_test_logger();
+    let mut context = user_dict_test_context();
+    context.process_key_events_string("K a n j i space");
+    context.poll_events();
+    context.process_key_events_string("Return");
+    assert_eq!(
+        vec![
+            CskkEvent::DictionaryUpdated {
+                kind: DictionaryUpdateKind::Learned,
+                midashi: "かんじ".to_string(),
+                kouho_text: "漢字".to_string(),
+            },
+            CskkEvent::ModeChanged {
+                input_mode: InputMode::Hiragana,
+                composition_mode: CompositionMode::Direct,
+            },
+            CskkEvent::CandidatesChanged,
+            CskkEvent::Commit("漢字".to_string()),
+        ],
+        context.poll_events()
+    );
+}
+
+#[test]
+fn registered_event() {
+    init_test_logger();
+    let mut context = user_dict_test_context();
+    context.process_key_events_string("H o g e space a");
+    context.poll_events();
+    context.process_key_events_string("Return");
+    let events = context.poll_events();
+    assert_eq!(
+        Some(&CskkEvent::DictionaryUpdated {
+            kind: DictionaryUpdateKind::Registered,
+            midashi: "ほげ".to_string(),
+            kouho_text: "あ".to_string(),
+        }),
+        events.first()
+    );
+    assert_eq!(Some(&CskkEvent::Commit("あ".to_string())), events.last());
+}
+
+#[test]
+fn delete_surrounding_before_commit() {
+    init_test_logger();
+    let mut context = default_test_context();
+    assert!(context.start_reconversion("漢字", None));
+    context.process_key_events_string("space Return");
+    let events = context.poll_events();
+    assert_eq!(
+        vec![
+            CskkEvent::DeleteSurrounding(2),
+            CskkEvent::Commit("幹事".to_string()),
+        ],
+        events[events.len() - 2..]
+    );
+}
+
+#[test]
+fn beep_on_too_deep_registration() {
+    init_test_logger();
+    let mut context = default_test_context();
+    for _ in 0..9 {
+        context.process_key_events_string("H o g e space");
+    }
+    assert!(!context.poll_events().contains(&CskkEvent::Beep));
+    context.process_key_events_string("H o g e space");
+    assert!(context.poll_events().contains(&CskkEvent::Beep));
+}
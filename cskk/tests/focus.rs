@@ -0,0 +1,100 @@
+use crate::utils::{default_test_context, init_test_logger, transition_check};
+use cskk::skk_context_set_input_mode_rs;
+use cskk::skk_modes::{CompositionMode, FocusOutPolicy, InputMode};
+
+mod utils;
+
+#[test]
+fn commit_reading_on_focus_out() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K a n n j i");
+    context.focus_out(FocusOutPolicy::FocusOutCommit);
+    assert_eq!("かんじ", context.poll_output().unwrap());
+    assert_eq!("", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::Direct,
+        context.get_current_composition_mode()
+    );
+}
+
+#[test]
+fn commit_okuri_and_drop_unconverted_on_focus_out() {
+    init_test_logger();
+    let mut context = default_test_context();
+    skk_context_set_input_mode_rs(&mut context, InputMode::Katakana);
+    context.process_key_events_string("H a S i r");
+    context.focus_out(FocusOutPolicy::FocusOutCommit);
+    assert_eq!("ハシ", context.poll_output().unwrap());
+    assert_eq!("", context.get_preedit().unwrap());
+    assert_eq!(InputMode::Katakana, context.get_current_input_mode());
+}
+
+#[test]
+fn commit_candidate_on_focus_out() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("a K a n n j i space space");
+    assert_eq!("あ▼幹事", context.get_preedit().unwrap());
+    context.focus_out(FocusOutPolicy::FocusOutCommit);
+    assert_eq!("あ幹事", context.poll_output().unwrap());
+    assert_eq!("", context.get_preedit().unwrap());
+}
+
+#[test]
+fn commit_reading_without_registering_on_focus_out() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("W a w a w a space A i space");
+    assert_eq!("▼わわわ【▼愛】", context.get_preedit().unwrap());
+    context.focus_out(FocusOutPolicy::FocusOutCommit);
+    assert_eq!("わわわ", context.poll_output().unwrap());
+    assert_eq!("", context.get_preedit().unwrap());
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "W a w a w a space",
+        "▼わわわ【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn discard_on_focus_out() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("a W a w a w a space A i");
+    context.focus_out(FocusOutPolicy::FocusOutDiscard);
+    assert_eq!("あ", context.poll_output().unwrap());
+    assert_eq!("", context.get_preedit().unwrap());
+    assert!(!context.focus_in());
+}
+
+#[test]
+fn keep_and_restore_on_focus_in() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("a K a n n j i space");
+    context.focus_out(FocusOutPolicy::FocusOutKeep);
+    assert_eq!("あ", context.poll_output().unwrap());
+    assert_eq!("", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::Direct,
+        context.get_current_composition_mode()
+    );
+
+    assert!(context.focus_in());
+    assert_eq!("▼漢字", context.get_preedit().unwrap());
+    assert!(!context.focus_in());
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "Return",
+        "",
+        "漢字",
+        InputMode::Hiragana,
+    );
+}
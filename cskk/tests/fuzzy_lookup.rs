@@ -0,0 +1,115 @@
+mod utils;
+
+use crate::utils::{default_test_context, init_test_logger, transition_check, transition_test};
+use cskk::cskkstate::{CompositionSelectionData, CskkStateInfo};
+use cskk::skk_context_reset_rs;
+use cskk::skk_modes::{CompositionMode, InputMode};
+
+#[test]
+fn fuzzy_fallback_disabled_by_default() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "S h u o r u i space",
+        "▼しゅおるい【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn fuzzy_fallback_before_register_mode() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_fuzzy_fallback_max_cost(2);
+    transition_test(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "S h u o r u i space",
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        CskkStateInfo::CompositionSelection(CompositionSelectionData {
+            confirmed: "".to_string(),
+            composited: "種類".to_string(),
+            okuri: None,
+            annotation: None,
+            corrected_reading: Some("しゅるい".to_string()),
+            hint: None,
+            hint_unconverted: None,
+            page: None,
+        }),
+    );
+    skk_context_reset_rs(&mut context);
+    // 濁点の付け忘れ(かっこ)より長音の脱落(がっこう)は後に並ぶ
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "G a k k o space space Return",
+        "",
+        "学校",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn fuzzy_fallback_not_used_when_found() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_fuzzy_fallback_max_cost(2);
+    transition_test(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i space",
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        CskkStateInfo::CompositionSelection(CompositionSelectionData {
+            confirmed: "".to_string(),
+            composited: "漢字".to_string(),
+            okuri: None,
+            annotation: None,
+            corrected_reading: None,
+            hint: None,
+            hint_unconverted: None,
+            page: None,
+        }),
+    );
+}
+
+#[test]
+fn fuzzy_fallback_register_after_corrections() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_fuzzy_fallback_max_cost(1);
+    // 訂正した候補を全て見た後は通常通り入力した読みで登録モードに入る
+    context.process_key_events_string("G a k k o space");
+    assert_eq!(
+        CompositionMode::CompositionSelection,
+        context.get_current_composition_mode()
+    );
+    let candidate_count = cskk::skk_context_get_current_candidate_count_rs(&context);
+    assert_eq!(2, candidate_count);
+    context.process_key_events_string("space space");
+    assert_eq!("▼がっこ【】", context.get_preedit().unwrap());
+}
+
+#[test]
+fn fuzzy_fallback_nothing_found() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_fuzzy_fallback_max_cost(2);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "N u p e p o space",
+        "▼ぬぺぽ【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
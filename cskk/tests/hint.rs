@@ -0,0 +1,121 @@
+use crate::utils::{default_test_context, init_test_logger, transition_check};
+use cskk::skk_context_reset_rs;
+use cskk::skk_modes::{CompositionMode, InputMode};
+
+mod utils;
+
+#[test]
+fn hint_narrows_candidates() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space semicolon g a k k o u space",
+        "▼校",
+        "",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "Return",
+        "",
+        "校",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn hint_preedit() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space semicolon",
+        "▼行▽",
+        "",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "g a k",
+        "▼行▽がk",
+        "",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "BackSpace BackSpace",
+        "▼行▽",
+        "",
+        InputMode::Hiragana,
+    );
+    // 読みが空の状態で消すとヒントの入力を終える
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "BackSpace",
+        "▼行",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn hint_abort() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space semicolon g a k u Escape",
+        "▼行",
+        "",
+        InputMode::Hiragana,
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "space",
+        "▼高",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn hint_without_match_keeps_candidates() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space semicolon n u p e p o space",
+        "▼行",
+        "",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space space space semicolon g a k k o u space",
+        "▼校",
+        "",
+        InputMode::Hiragana,
+    );
+}
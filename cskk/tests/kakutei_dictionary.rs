@@ -0,0 +1,162 @@
+use crate::utils::{
+    init_test_logger, make_temp_file, test_context_with_dictionaries, transition_check,
+};
+use cskk::dictionary::CskkDictionary;
+use cskk::skk_modes::{CompositionMode, InputMode};
+use cskk::CskkContext;
+use std::sync::Arc;
+
+mod utils;
+
+fn kakutei_test_context(kakutei_dict_first: bool) -> CskkContext {
+    let kakutei_dict = Arc::new(
+        CskkDictionary::new_static_dict("tests/data/dictionaries/kakutei.dat", "utf-8", false)
+            .unwrap()
+            .with_kakutei(true),
+    );
+    let static_dict = Arc::new(
+        CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+            .unwrap(),
+    );
+    if kakutei_dict_first {
+        test_context_with_dictionaries(vec![kakutei_dict, static_dict])
+    } else {
+        test_context_with_dictionaries(vec![static_dict, kakutei_dict])
+    }
+}
+
+#[test]
+fn kakutei_dictionary_confirms_immediately() {
+    init_test_logger();
+    let mut context = kakutei_test_context(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i space",
+        "",
+        "幹事",
+        InputMode::Hiragana,
+    );
+    assert_eq!(
+        CompositionMode::Direct,
+        context.get_current_composition_mode()
+    );
+}
+
+#[test]
+fn kakutei_dictionary_okuri_ari() {
+    init_test_logger();
+    let mut context = kakutei_test_context(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a K u",
+        "",
+        "書く",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn kakutei_dictionary_not_first_candidate() {
+    init_test_logger();
+    let mut context = kakutei_test_context(false);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i space",
+        "▼漢字",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn kakutei_dictionary_reopen_before_poll() {
+    init_test_logger();
+    let mut context = kakutei_test_context(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i space space",
+        "▼漢字",
+        "",
+        InputMode::Hiragana,
+    );
+    assert_eq!(0, context.poll_delete_surrounding());
+}
+
+#[test]
+fn kakutei_dictionary_reopen_after_poll() {
+    init_test_logger();
+    let mut context = kakutei_test_context(true);
+    context.process_key_events_string("K a n j i space");
+    assert_eq!("幹事", context.poll_output().unwrap());
+    context.process_key_events_string("space");
+    assert_eq!(2, context.poll_delete_surrounding());
+    assert_eq!(0, context.poll_delete_surrounding());
+    assert_eq!("▼漢字", context.get_preedit().unwrap());
+    context.process_key_events_string("Return");
+    assert_eq!("漢字", context.poll_output().unwrap());
+}
+
+#[test]
+fn kakutei_dictionary_reopen_only_right_after_confirmation() {
+    init_test_logger();
+    let mut context = kakutei_test_context(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i space a space",
+        "",
+        "幹事あ ",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn kakutei_dictionary_reopen_single_candidate() {
+    init_test_logger();
+    let mut context = kakutei_test_context(true);
+    // 他に候補が無ければ登録モードに入る
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a b u s i k i g a i s h a space space",
+        "▼かぶしきがいしゃ【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn kakutei_user_dictionary() {
+    init_test_logger();
+    let temppath = make_temp_file().unwrap();
+    std::fs::copy("tests/data/dictionaries/kakutei.dat", &temppath).unwrap();
+    let kakutei_dict = Arc::new(
+        CskkDictionary::new_user_dict(temppath.to_str().unwrap(), "utf-8", false)
+            .unwrap()
+            .with_kakutei(true),
+    );
+    let static_dict = Arc::new(
+        CskkDictionary::new_static_dict("tests/data/dictionaries/SKK-JISYO.S", "euc-jp", false)
+            .unwrap(),
+    );
+    let mut context = test_context_with_dictionaries(vec![kakutei_dict, static_dict]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i space",
+        "",
+        "幹事",
+        InputMode::Hiragana,
+    );
+}
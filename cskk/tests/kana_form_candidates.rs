@@ -0,0 +1,130 @@
+use crate::utils::{
+    default_test_context, init_test_logger, static_and_empty_user_dictionary,
+    test_context_with_dictionaries, transition_check,
+};
+use cskk::dictionary::CskkDictionary;
+use cskk::skk_context_reset_rs;
+use cskk::skk_modes::{CompositionMode, InputMode};
+use std::sync::Arc;
+
+mod utils;
+
+fn context_with_user_dictionary() -> (cskk::CskkContext, Arc<CskkDictionary>) {
+    let (static_dict, user_dict) = static_and_empty_user_dictionary(true);
+    (
+        test_context_with_dictionaries(vec![static_dict, user_dict.clone()]),
+        user_dict,
+    )
+}
+
+#[test]
+fn no_kana_form_candidates_by_default() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "R a i t o space",
+        "▼らいと【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn kana_form_candidates_after_dictionary_candidates() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_kana_form_candidates(vec![InputMode::Katakana, InputMode::Hiragana]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i space space",
+        "▼幹事",
+        "",
+        InputMode::Hiragana,
+    );
+    context.process_key_events_string("space");
+    assert_eq!("▼カンジ", context.get_preedit().unwrap());
+    context.process_key_events_string("space");
+    assert_eq!("▼かんじ", context.get_preedit().unwrap());
+    context.process_key_events_string("space");
+    assert_eq!("▼かんじ【】", context.get_preedit().unwrap());
+}
+
+#[test]
+fn kana_form_candidates_for_unknown_word() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_kana_form_candidates(vec![InputMode::Katakana]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "R a i t o space Return",
+        "",
+        "ライト",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    context.set_kana_form_candidates(vec![InputMode::HankakuKatakana]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "R a i t o space Return",
+        "",
+        "ﾗｲﾄ",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn kana_form_candidates_with_okuri() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_kana_form_candidates(vec![InputMode::Katakana]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "M o G u Return",
+        "",
+        "モぐ",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn kana_form_candidate_is_learned() {
+    init_test_logger();
+    let (mut context, user_dict) = context_with_user_dictionary();
+    context.set_kana_form_candidates(vec![InputMode::Katakana]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "R a i t o space Return",
+        "",
+        "ライト",
+        InputMode::Hiragana,
+    );
+    let entries = user_dict.search_entries_by_midashi_prefix("らいと");
+    assert_eq!(1, entries.len());
+    assert_eq!("ライト", entries[0].candidates[0].kouho_text);
+
+    // 学習した後は辞書の候補として引ける
+    skk_context_reset_rs(&mut context);
+    context.set_kana_form_candidates(vec![]);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "R a i t o space",
+        "▼ライト",
+        "",
+        InputMode::Hiragana,
+    );
+}
@@ -0,0 +1,135 @@
+use crate::utils::{default_test_context, init_test_logger};
+use cskk::event::CskkChange;
+use cskk::skk_modes::InputMode;
+use cskk::{skk_context_reset_rs, skk_context_set_input_mode_rs, CskkContext};
+use std::sync::{Arc, Mutex};
+
+mod utils;
+
+fn observe(context: &mut CskkContext) -> (usize, Arc<Mutex<Vec<CskkChange>>>) {
+    let changes = Arc::new(Mutex::new(vec![]));
+    let observed = changes.clone();
+    let id = context.add_observer(Box::new(move |change| {
+        observed.lock().unwrap().push(change);
+    }));
+    (id, changes)
+}
+
+fn take_changes(changes: &Arc<Mutex<Vec<CskkChange>>>) -> Vec<CskkChange> {
+    std::mem::take(&mut *changes.lock().unwrap())
+}
+
+#[test]
+fn observer_is_notified_of_changes() {
+    init_test_logger();
+    let mut context = default_test_context();
+    let (_, changes) = observe(&mut context);
+
+    context.process_key_events_string("K");
+    assert_eq!(
+        vec![
+            CskkChange::CompositionModeChanged,
+            CskkChange::PreeditChanged
+        ],
+        take_changes(&changes)
+    );
+    context.process_key_events_string("a n j i");
+    assert_eq!(vec![CskkChange::PreeditChanged; 4], take_changes(&changes));
+    context.process_key_events_string("space");
+    assert_eq!(
+        vec![
+            CskkChange::CompositionModeChanged,
+            CskkChange::CandidatesChanged,
+            CskkChange::PreeditChanged
+        ],
+        take_changes(&changes)
+    );
+    context.process_key_events_string("Return q");
+    assert_eq!(
+        vec![
+            CskkChange::CompositionModeChanged,
+            CskkChange::CandidatesChanged,
+            CskkChange::PreeditChanged,
+            CskkChange::InputModeChanged,
+        ],
+        take_changes(&changes)
+    );
+}
+
+#[test]
+fn observer_is_not_notified_without_changes() {
+    init_test_logger();
+    let mut context = default_test_context();
+    let (_, changes) = observe(&mut context);
+    // 処理されないキー入力では何も変わらない
+    context.process_key_events_string("Return");
+    assert_eq!(Vec::<CskkChange>::new(), take_changes(&changes));
+}
+
+#[test]
+fn remove_observer() {
+    init_test_logger();
+    let mut context = default_test_context();
+    let (first_id, first_changes) = observe(&mut context);
+    let (_, second_changes) = observe(&mut context);
+    assert!(context.remove_observer(first_id));
+    assert!(!context.remove_observer(first_id));
+    context.process_key_events_string("K");
+    assert_eq!(Vec::<CskkChange>::new(), take_changes(&first_changes));
+    assert_eq!(
+        vec![
+            CskkChange::CompositionModeChanged,
+            CskkChange::PreeditChanged
+        ],
+        take_changes(&second_changes)
+    );
+}
+
+#[test]
+fn observer_is_notified_of_api_changes() {
+    init_test_logger();
+    let mut context = default_test_context();
+    let (_, changes) = observe(&mut context);
+
+    skk_context_set_input_mode_rs(&mut context, InputMode::Katakana);
+    assert_eq!(vec![CskkChange::InputModeChanged], take_changes(&changes));
+    skk_context_set_input_mode_rs(&mut context, InputMode::Hiragana);
+    take_changes(&changes);
+
+    assert!(context.start_reconversion("漢字", Some("かんじ")));
+    assert_eq!(
+        vec![
+            CskkChange::CompositionModeChanged,
+            CskkChange::CandidatesChanged,
+            CskkChange::PreeditChanged
+        ],
+        take_changes(&changes)
+    );
+    skk_context_reset_rs(&mut context);
+    assert_eq!(
+        vec![
+            CskkChange::CompositionModeChanged,
+            CskkChange::CandidatesChanged,
+            CskkChange::PreeditChanged
+        ],
+        take_changes(&changes)
+    );
+}
+
+#[test]
+fn observer_compares_modes_on_register_mode() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("W a w a w a");
+    let (_, changes) = observe(&mut context);
+    // 登録モードに入ってもひらがな入力のままなので入力モードは変わらない
+    context.process_key_events_string("space");
+    assert_eq!("▼わわわ【】", context.get_preedit().unwrap());
+    assert_eq!(
+        vec![
+            CskkChange::CompositionModeChanged,
+            CskkChange::PreeditChanged
+        ],
+        take_changes(&changes)
+    );
+}
@@ -0,0 +1,110 @@
+use crate::utils::{default_test_context, init_test_logger, transition_check};
+use cskk::cskkstate::CskkStateInfo;
+use cskk::skk_modes::{CompositionMode, InputMode};
+use cskk::{skk_context_set_auto_start_henkan_keywords_rs, CskkContext};
+
+mod utils;
+
+fn precomposition_cursor(context: &CskkContext) -> usize {
+    match context.get_preedit_detail().last() {
+        Some(CskkStateInfo::PreComposition(precomposition_data)) => precomposition_data.cursor,
+        other => panic!("Not in PreComposition: {:?}", other),
+    }
+}
+
+#[test]
+fn insert_at_cursor() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K a n j i Left Left");
+    assert_eq!(1, precomposition_cursor(&context));
+    // かな変換前の入力はカーソルの位置に付く
+    context.process_key_events_string("k");
+    assert_eq!("▽かkんじ", context.get_preedit().unwrap());
+    context.process_key_events_string("a");
+    assert_eq!("▽かかんじ", context.get_preedit().unwrap());
+    assert_eq!(2, precomposition_cursor(&context));
+}
+
+#[test]
+fn fix_reading_near_start() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K i n j i Home Right BackSpace");
+    assert_eq!("▽んじ", context.get_preedit().unwrap());
+    assert_eq!(0, precomposition_cursor(&context));
+    context.process_key_events_string("k a");
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    assert_eq!(1, precomposition_cursor(&context));
+    transition_check(
+        &mut context,
+        CompositionMode::PreComposition,
+        InputMode::Hiragana,
+        "space Return",
+        "",
+        "漢字",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn delete_at_start_of_reading() {
+    init_test_logger();
+    let mut context = default_test_context();
+    // カーソルの後ろに読みが残っていれば▽モードのまま
+    context.process_key_events_string("K a n j i Home BackSpace");
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::PreComposition,
+        context.get_current_composition_mode()
+    );
+    context.process_key_events_string("End");
+    assert_eq!(3, precomposition_cursor(&context));
+    context.process_key_events_string("Right");
+    assert_eq!(3, precomposition_cursor(&context));
+}
+
+#[test]
+fn no_auto_start_henkan_before_end_of_reading() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context
+        .set_rule_from_directory("backward_henkan", "tests/data/rules")
+        .unwrap();
+    skk_context_set_auto_start_henkan_keywords_rs(&mut context, vec!["を".to_string()]);
+    context.process_key_events_string("k a n n j i w o C-q");
+    assert_eq!("▽かんじを", context.get_preedit().unwrap());
+    // 読みの末尾が「を」でも、読みの先頭に入力しただけなので変換を始めない。
+    context.process_key_events_string("Home k a");
+    assert_eq!("▽かかんじを", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::PreComposition,
+        context.get_current_composition_mode()
+    );
+    assert_eq!(1, precomposition_cursor(&context));
+}
+
+#[test]
+fn move_cursor_discards_unconverted() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K a n j i k Left");
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    assert_eq!(2, precomposition_cursor(&context));
+}
+
+#[test]
+fn cursor_resets_on_conversion() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i Left space C-g",
+        "▽かんじ",
+        "",
+        InputMode::Hiragana,
+    );
+    assert_eq!(3, precomposition_cursor(&context));
+}
@@ -0,0 +1,126 @@
+use crate::utils::{default_test_context, init_test_logger, transition_check};
+use cskk::keyevent::CskkKeyEvent;
+use cskk::skk_modes::{CompositionMode, InputMode};
+use cskk::CskkContext;
+use std::str::FromStr;
+
+mod utils;
+
+fn preedit_history_test_context() -> CskkContext {
+    let mut context = default_test_context();
+    context
+        .set_rule_from_directory("preedit_history", "tests/data/rules")
+        .unwrap();
+    context
+}
+
+#[test]
+fn undo_abort_of_reading() {
+    init_test_logger();
+    let mut context = preedit_history_test_context();
+    context.process_key_events_string("K a n n j i C-g");
+    assert_eq!("", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::Direct,
+        context.get_current_composition_mode()
+    );
+    context.process_key_events_string("C-u");
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    transition_check(
+        &mut context,
+        CompositionMode::PreComposition,
+        InputMode::Hiragana,
+        "space Return",
+        "",
+        "漢字",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn undo_abort_of_candidate() {
+    init_test_logger();
+    let mut context = preedit_history_test_context();
+    context.process_key_events_string("K a n n j i space space C-g");
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    context.process_key_events_string("C-u");
+    assert_eq!("▼幹事", context.get_preedit().unwrap());
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "Return",
+        "",
+        "幹事",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn redo_preedit() {
+    init_test_logger();
+    let mut context = preedit_history_test_context();
+    context.process_key_events_string("K a n n j i C-u");
+    assert_eq!("▽かんj", context.get_preedit().unwrap());
+    context.process_key_events_string("C-u");
+    assert_eq!("▽かん", context.get_preedit().unwrap());
+    context.process_key_events_string("C-r");
+    assert_eq!("▽かんj", context.get_preedit().unwrap());
+    context.process_key_events_string("C-r");
+    assert_eq!("▽かんじ", context.get_preedit().unwrap());
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-r").unwrap()));
+
+    // 新たに入力するとやり直しの履歴は消える
+    context.process_key_events_string("C-u BackSpace");
+    assert_eq!("▽かん", context.get_preedit().unwrap());
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-r").unwrap()));
+    context.process_key_events_string("C-u");
+    assert_eq!("▽かんj", context.get_preedit().unwrap());
+}
+
+#[test]
+fn confirmation_clears_preedit_history() {
+    init_test_logger();
+    let mut context = preedit_history_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n n j i space Return",
+        "",
+        "漢字",
+        InputMode::Hiragana,
+    );
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-u").unwrap()));
+    assert_eq!("", context.get_preedit().unwrap());
+}
+
+#[test]
+fn preedit_history_is_bounded() {
+    init_test_logger();
+    let mut context = preedit_history_test_context();
+    context.process_key_events_string("A");
+    for _ in 0..150 {
+        context.process_key_events_string("a");
+    }
+    let mut undo_count = 0;
+    while context.process_key_event(&CskkKeyEvent::from_str("C-u").unwrap()) {
+        undo_count += 1;
+    }
+    assert_eq!(100, undo_count);
+    assert_eq!(
+        "▽".to_string() + &"あ".repeat(51),
+        context.get_preedit().unwrap()
+    );
+}
+
+#[test]
+fn preedit_history_starts_from_reading() {
+    init_test_logger();
+    let mut context = preedit_history_test_context();
+    context.process_key_events_string("K a C-u");
+    assert_eq!("▽k", context.get_preedit().unwrap());
+    // 読みの無い直接入力の状態は履歴に積まない
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-u").unwrap()));
+    assert_eq!("▽k", context.get_preedit().unwrap());
+}
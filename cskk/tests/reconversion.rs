@@ -0,0 +1,87 @@
+use crate::utils::{default_test_context, init_test_logger, transition_check};
+use cskk::skk_modes::{CompositionMode, InputMode};
+
+mod utils;
+
+#[test]
+fn reconversion_by_reverse_lookup() {
+    init_test_logger();
+    let mut context = default_test_context();
+    assert!(context.start_reconversion("漢字", None));
+    assert_eq!("▼漢字", context.get_preedit().unwrap());
+    assert_eq!(
+        CompositionMode::CompositionSelection,
+        context.get_current_composition_mode()
+    );
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "space",
+        "▼幹事",
+        "",
+        InputMode::Hiragana,
+    );
+    context.process_key_events_string("Return");
+    assert_eq!(2, context.poll_delete_surrounding());
+    assert_eq!("幹事", context.poll_output().unwrap());
+    assert_eq!(0, context.poll_delete_surrounding());
+}
+
+#[test]
+fn reconversion_okuri_ari() {
+    init_test_logger();
+    let mut context = default_test_context();
+    assert!(context.start_reconversion("書く", None));
+    assert_eq!("▼書く", context.get_preedit().unwrap());
+    context.process_key_events_string("space Return");
+    assert_eq!(2, context.poll_delete_surrounding());
+    assert_eq!("掛く", context.poll_output().unwrap());
+}
+
+#[test]
+fn reconversion_with_reading() {
+    init_test_logger();
+    let mut context = default_test_context();
+    assert!(context.start_reconversion("変える", Some("かえる")));
+    assert_eq!("▼変える", context.get_preedit().unwrap());
+    context.process_key_events_string("Return");
+    assert_eq!(3, context.poll_delete_surrounding());
+    assert_eq!("変える", context.poll_output().unwrap());
+}
+
+#[test]
+fn reconversion_abort() {
+    init_test_logger();
+    let mut context = default_test_context();
+    assert!(context.start_reconversion("漢字", None));
+    context.process_key_events_string("C-g C-g");
+    assert_eq!(
+        CompositionMode::Direct,
+        context.get_current_composition_mode()
+    );
+    assert_eq!(0, context.poll_delete_surrounding());
+    assert_eq!(None, context.poll_output());
+    // 中止した後の確定では何も消さない
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a n j i space Return",
+        "",
+        "漢字",
+        InputMode::Hiragana,
+    );
+    assert_eq!(0, context.poll_delete_surrounding());
+}
+
+#[test]
+fn reconversion_not_started() {
+    init_test_logger();
+    let mut context = default_test_context();
+    assert!(!context.start_reconversion("未登録語", None));
+    assert!(!context.start_reconversion("", None));
+    context.process_key_events_string("K a");
+    assert!(!context.start_reconversion("漢字", None));
+    assert_eq!("▽か", context.get_preedit().unwrap());
+}
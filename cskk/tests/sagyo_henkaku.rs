@@ -0,0 +1,82 @@
+use crate::utils::{
+    default_test_context, init_test_logger, static_and_empty_user_dictionary,
+    test_context_with_dictionaries, transition_check,
+};
+use cskk::skk_context_reset_rs;
+use cskk::skk_modes::{CompositionMode, InputMode};
+
+mod utils;
+
+#[test]
+fn sagyo_henkaku_search_disabled_by_default() {
+    init_test_logger();
+    let mut context = default_test_context();
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "B e n k y o u S u",
+        "▼べんきょう*す【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn sagyo_henkaku_search() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.set_sagyo_henkaku_search(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "B e n k y o u S u",
+        "▼勉強す",
+        "",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a k u n i n S i t a",
+        "",
+        "確認した",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    // さ行以外の送り仮名では語幹を引かない
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K a k u n i n K a",
+        "▼かくにん*か【】",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn sagyo_henkaku_candidate_is_learned_as_okuri_ari() {
+    init_test_logger();
+    let (static_dict, user_dict) = static_and_empty_user_dictionary(true);
+    let mut context = test_context_with_dictionaries(vec![static_dict, user_dict.clone()]);
+    context.set_sagyo_henkaku_search(true);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "S e t u m e i S u Return",
+        "",
+        "説明す",
+        InputMode::Hiragana,
+    );
+    let entries = user_dict.search_entries_by_midashi_prefix("せつめい");
+    assert_eq!(1, entries.len());
+    assert_eq!("せつめいs", entries[0].midashi);
+    assert!(entries[0].is_okuri_ari);
+    assert_eq!("説明", entries[0].candidates[0].kouho_text);
+}
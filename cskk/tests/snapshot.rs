@@ -0,0 +1,161 @@
+use crate::utils::{default_test_context, init_test_logger, transition_check};
+use cskk::skk_context_reset_rs;
+use cskk::skk_modes::{CompositionMode, InputMode};
+
+mod utils;
+
+#[test]
+fn restore_reading_in_new_context() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K a n n j i");
+    let snapshot = context.snapshot().unwrap();
+
+    let mut restored = default_test_context();
+    restored.restore(&snapshot).unwrap();
+    assert_eq!("▽かんじ", restored.get_preedit().unwrap());
+    transition_check(
+        &mut restored,
+        CompositionMode::PreComposition,
+        InputMode::Hiragana,
+        "space Return",
+        "",
+        "漢字",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn restore_unconverted_input() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K a k");
+    let snapshot = context.snapshot().unwrap();
+    context.process_key_events_string("C-g");
+
+    context.restore(&snapshot).unwrap();
+    assert_eq!("▽かk", context.get_preedit().unwrap());
+    context.process_key_events_string("u");
+    assert_eq!("▽かく", context.get_preedit().unwrap());
+}
+
+#[test]
+fn restore_candidate_selection() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K a n n j i space space");
+    assert_eq!("▼幹事", context.get_preedit().unwrap());
+    let snapshot = context.snapshot().unwrap();
+    context.process_key_events_string("C-g C-g");
+
+    context.restore(&snapshot).unwrap();
+    assert_eq!("▼幹事", context.get_preedit().unwrap());
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "x",
+        "▼漢字",
+        "",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn restore_register_mode() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("W a w a w a space A i space");
+    let snapshot = context.snapshot().unwrap();
+    skk_context_reset_rs(&mut context);
+    assert_eq!("", context.get_preedit().unwrap());
+
+    context.restore(&snapshot).unwrap();
+    assert_eq!("▼わわわ【▼愛】", context.get_preedit().unwrap());
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "Return Return",
+        "",
+        "愛",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn restore_keeps_unpolled_output() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("u K a");
+    let snapshot = context.snapshot().unwrap();
+
+    let mut restored = default_test_context();
+    restored.process_key_events_string("i");
+    restored.restore(&snapshot).unwrap();
+    assert_eq!("い▽か", restored.get_preedit().unwrap());
+    assert_eq!("い", restored.poll_output().unwrap());
+    assert_eq!("▽か", restored.get_preedit().unwrap());
+}
+
+#[test]
+fn restore_rejects_invalid_snapshot() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K a");
+    let snapshot = context.snapshot().unwrap();
+    let other_version = snapshot.replacen("version = 1", "version = 2", 1);
+    assert_ne!(snapshot, other_version);
+
+    assert!(context.restore(&other_version).is_err());
+    assert!(context.restore("version = 1\nstate_stack = []").is_err());
+    assert!(context.restore("not a snapshot").is_err());
+    assert_eq!("▽か", context.get_preedit().unwrap());
+}
+
+#[test]
+fn restore_rejects_tampered_snapshot() {
+    init_test_logger();
+    let mut context = default_test_context();
+    context.process_key_events_string("K a n n j i space space");
+    let selection = context.snapshot().unwrap();
+    skk_context_reset_rs(&mut context);
+    context.process_key_events_string("K a n n j i Left");
+    let reading = context.snapshot().unwrap();
+    skk_context_reset_rs(&mut context);
+    context.process_key_events_string("W a w a w a space");
+    let register = context.snapshot().unwrap();
+    skk_context_reset_rs(&mut context);
+    context.process_key_events_string("K a");
+
+    let tampered = [
+        // 候補の数を超えた選択位置
+        selection.replacen(
+            "selection_cursor_position = 1",
+            "selection_cursor_position = 5",
+            1,
+        ),
+        // 候補の無い▼モード
+        reading.replacen(
+            "\ncomposition_mode = \"PreComposition\"",
+            "\ncomposition_mode = \"CompositionSelection\"",
+            1,
+        ),
+        // 読みの外のカーソル位置
+        reading.replacen("composite_cursor = 2", "composite_cursor = 3", 1),
+        // 登録モードでない状態の上に積まれた状態
+        register.replacen(
+            "\ncomposition_mode = \"Register\"",
+            "\ncomposition_mode = \"PreComposition\"",
+            1,
+        ),
+    ];
+    for snapshot in [&selection, &reading, &register] {
+        assert!(default_test_context().restore(snapshot).is_ok());
+    }
+    for snapshot in &tampered {
+        assert!(![&selection, &reading, &register].contains(&snapshot));
+        assert!(context.restore(snapshot).is_err());
+        assert_eq!("▽か", context.get_preedit().unwrap());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::utils::{
+    default_test_context, init_test_logger, static_and_empty_user_dictionary,
+    test_context_with_dictionaries, transition_check,
+};
+use cskk::keyevent::CskkKeyEvent;
+use cskk::skk_context_reset_rs;
+use cskk::skk_modes::{CompositionMode, InputMode};
+use cskk::CskkContext;
+use std::str::FromStr;
+
+mod utils;
+
+fn set_undo_kakutei_rule(context: &mut CskkContext) {
+    context
+        .set_rule_from_directory("undo_kakutei", "tests/data/rules")
+        .unwrap();
+}
+
+#[test]
+fn undo_kakutei_before_poll() {
+    init_test_logger();
+    let mut context = default_test_context();
+    set_undo_kakutei_rule(&mut context);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space Return C-u",
+        "▼高",
+        "",
+        InputMode::Hiragana,
+    );
+    assert_eq!(0, context.poll_delete_surrounding());
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "Return",
+        "",
+        "高",
+        InputMode::Hiragana,
+    );
+}
+
+#[test]
+fn undo_kakutei_after_poll() {
+    init_test_logger();
+    let mut context = default_test_context();
+    set_undo_kakutei_rule(&mut context);
+    context.process_key_events_string("K o u space space Return");
+    assert_eq!("高", context.poll_output().unwrap());
+    assert!(context.process_key_event(&CskkKeyEvent::from_str("C-u").unwrap()));
+    assert_eq!(1, context.poll_delete_surrounding());
+    assert_eq!("▼構", context.get_preedit().unwrap());
+    // 候補の一覧に戻るので前候補も選べる
+    context.process_key_events_string("x x Return");
+    assert_eq!("行", context.poll_output().unwrap());
+}
+
+#[test]
+fn undo_kakutei_only_right_after_confirmation() {
+    init_test_logger();
+    let mut context = default_test_context();
+    set_undo_kakutei_rule(&mut context);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space Return a C-u",
+        "",
+        "行あ",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    // 確定と同じキー入力で入力が続いた場合も取り消さない
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space a C-u",
+        "",
+        "行あ",
+        InputMode::Hiragana,
+    );
+    skk_context_reset_rs(&mut context);
+    assert!(!context.process_key_event(&CskkKeyEvent::from_str("C-u").unwrap()));
+}
+
+#[test]
+fn undo_kakutei_reverts_learning() {
+    init_test_logger();
+    let (static_dict, user_dict) = static_and_empty_user_dictionary(true);
+    let mut context = test_context_with_dictionaries(vec![user_dict.clone(), static_dict]);
+    set_undo_kakutei_rule(&mut context);
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space Return",
+        "",
+        "行",
+        InputMode::Hiragana,
+    );
+    assert_eq!(1, user_dict.search_entries_by_midashi_prefix("こう").len());
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "C-u",
+        "▼高",
+        "",
+        InputMode::Hiragana,
+    );
+    assert!(user_dict
+        .search_entries_by_midashi_prefix("こう")
+        .is_empty());
+    transition_check(
+        &mut context,
+        CompositionMode::CompositionSelection,
+        InputMode::Hiragana,
+        "Return",
+        "",
+        "高",
+        InputMode::Hiragana,
+    );
+    let entries = user_dict.search_entries_by_midashi_prefix("こう");
+    assert_eq!(1, entries.len());
+    assert_eq!(1, entries[0].candidates.len());
+    assert_eq!("高", entries[0].candidates[0].kouho_text);
+
+    // 既にあったエントリは学習前の候補の順に戻る
+    transition_check(
+        &mut context,
+        CompositionMode::Direct,
+        InputMode::Hiragana,
+        "K o u space space Return C-u",
+        "▼構",
+        "",
+        InputMode::Hiragana,
+    );
+    let entries = user_dict.search_entries_by_midashi_prefix("こう");
+    assert_eq!(1, entries[0].candidates.len());
+    assert_eq!("高", entries[0].candidates[0].kouho_text);
+}
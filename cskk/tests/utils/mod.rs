@@ -2,6 +2,7 @@ use cskk::cskkstate::CskkStateInfo;
 use cskk::dictionary::CskkDictionary;
 use cskk::skk_modes::{CompositionMode, InputMode};
 use cskk::{skk_context_set_composition_mode, skk_context_set_input_mode_rs, CskkContext};
+use std::fs::File;
 use std::sync::Arc;
 use tempfile::{NamedTempFile, TempPath};
 
@@ -92,6 +93,28 @@ pub fn test_context_with_dictionaries(dictionaries: Vec<Arc<CskkDictionary>>) ->
     )
 }
 
+/// SKK-JISYO.Sの静的辞書と、空のファイルから読み込んだユーザー辞書を作る。
+pub fn static_and_empty_user_dictionary(
+    completable: bool,
+) -> (Arc<CskkDictionary>, Arc<CskkDictionary>) {
+    let static_dict = Arc::new(
+        CskkDictionary::new_static_dict(
+            "tests/data/dictionaries/SKK-JISYO.S",
+            "euc-jp",
+            completable,
+        )
+        .unwrap(),
+    );
+    let temppath = make_temp_file().unwrap();
+    File::create(&temppath).unwrap();
+    let user_dict = Arc::new(
+        CskkDictionary::new_user_dict(temppath.to_str().unwrap(), "utf-8", completable).unwrap(),
+    );
+    // 辞書を読み込んだ後はファイルを使わないので消してよい。
+    drop(temppath);
+    (static_dict, user_dict)
+}
+
 pub fn init_test_logger() {
     let _ = env_logger::builder()
         // Include all events in tests
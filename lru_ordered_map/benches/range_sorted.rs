@@ -0,0 +1,52 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lru_ordered_map::LruOrderedMap;
+use std::ops::Bound;
+
+// SKK-JISYO.Lの送りなしエントリ程度の数
+const ENTRY_COUNT: u32 = 150_000;
+
+fn make_map() -> LruOrderedMap<String, u32> {
+    let mut map = LruOrderedMap::new();
+    for i in 0..ENTRY_COUNT {
+        // ひらがなのみの見出しを模して、"あ"から始まる範囲に散らばらせる
+        let midashi = (0..3)
+            .map(|digit| char::from_u32('ぁ' as u32 + (i / 80u32.pow(digit)) % 80).unwrap())
+            .rev()
+            .collect::<String>();
+        map.push(midashi, i);
+    }
+    map
+}
+
+fn prefix_by_range(map: &LruOrderedMap<String, u32>, prefix: &str) -> usize {
+    map.range_sorted::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+        .take_while(|(k, _)| k.is_some_and(|k| k.starts_with(prefix)))
+        .count()
+}
+
+fn prefix_by_scan(map: &LruOrderedMap<String, u32>, prefix: &str) -> usize {
+    map.iter_sorted()
+        .skip_while(|(k, _)| k.is_some_and(|k| !k.starts_with(prefix)))
+        .take_while(|(k, _)| k.is_some_and(|k| k.starts_with(prefix)))
+        .count()
+}
+
+fn bench_prefix(c: &mut Criterion) {
+    let map = make_map();
+    // 末尾近くの見出しの補完が線形探索で最も遅い
+    let prefix = "じぁ";
+    assert_eq!(80, prefix_by_range(&map, prefix));
+    assert_eq!(prefix_by_range(&map, prefix), prefix_by_scan(&map, prefix));
+
+    let mut group = c.benchmark_group("prefix");
+    group.bench_function("range_sorted", |b| {
+        b.iter(|| prefix_by_range(black_box(&map), black_box(prefix)))
+    });
+    group.bench_function("iter_sorted_scan", |b| {
+        b.iter(|| prefix_by_scan(black_box(&map), black_box(prefix)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_prefix);
+criterion_main!(benches);
@@ -2,8 +2,9 @@ use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
 use std::ptr;
-use std::rc::Rc;
+use std::sync::Arc;
 
 /// Entry in the LRU linked list
 ///
@@ -11,10 +12,12 @@ use std::rc::Rc;
 /// duplicate the raw pointers (prev/next), which could break linked list invariants.
 #[derive(Debug)]
 pub struct LruEntry<K, V> {
-    key: Option<Rc<K>>,
+    key: Option<Arc<K>>,
     val: Option<V>,
     prev: *mut LruEntry<K, V>,
     next: *mut LruEntry<K, V>,
+    // リストに繋いだ時に決まる値。大きいほど最近使われた。
+    recency: i64,
 }
 
 impl<K, V> LruEntry<K, V> {
@@ -24,15 +27,17 @@ impl<K, V> LruEntry<K, V> {
             val: None,
             prev: ptr::null_mut(),
             next: ptr::null_mut(),
+            recency: 0,
         }
     }
 
-    pub fn new(k: Rc<K>, v: V) -> Self {
+    pub fn new(k: Arc<K>, v: V) -> Self {
         Self {
             key: Some(k),
             val: Some(v),
             prev: ptr::null_mut(),
             next: ptr::null_mut(),
+            recency: 0,
         }
     }
 }
@@ -69,9 +74,9 @@ pub struct LinkedListIter<'a, K: 'a, V: 'a> {
 }
 
 impl<'a, K, V> Iterator for LinkedListIter<'a, K, V> {
-    type Item = (Option<&'a Rc<K>>, Option<&'a V>);
+    type Item = (Option<&'a Arc<K>>, Option<&'a V>);
 
-    fn next(&mut self) -> Option<(Option<&'a Rc<K>>, Option<&'a V>)> {
+    fn next(&mut self) -> Option<(Option<&'a Arc<K>>, Option<&'a V>)> {
         if self.len == 0 {
             return None;
         }
@@ -95,13 +100,13 @@ impl<'a, K, V> Iterator for LinkedListIter<'a, K, V> {
 }
 
 impl<'a, K, V> DoubleEndedIterator for LinkedListIter<'a, K, V> {
-    fn next_back(&mut self) -> Option<(Option<&'a Rc<K>>, Option<&'a V>)> {
+    fn next_back(&mut self) -> Option<(Option<&'a Arc<K>>, Option<&'a V>)> {
         if self.len == 0 {
             return None;
         }
 
-        let key = unsafe { &(*self.ptr).key };
-        let val = unsafe { &(*self.ptr).val };
+        let key = unsafe { &(*self.end).key };
+        let val = unsafe { &(*self.end).val };
 
         self.len -= 1;
         self.end = unsafe { (*self.end).prev };
@@ -128,9 +133,9 @@ pub struct LinkedListIterMut<'a, K: 'a, V: 'a> {
 }
 
 impl<'a, K, V> Iterator for LinkedListIterMut<'a, K, V> {
-    type Item = (Option<&'a Rc<K>>, Option<&'a mut V>);
+    type Item = (Option<&'a Arc<K>>, Option<&'a mut V>);
 
-    fn next(&mut self) -> Option<(Option<&'a Rc<K>>, Option<&'a mut V>)> {
+    fn next(&mut self) -> Option<(Option<&'a Arc<K>>, Option<&'a mut V>)> {
         if self.len == 0 {
             return None;
         }
@@ -154,13 +159,13 @@ impl<'a, K, V> Iterator for LinkedListIterMut<'a, K, V> {
 }
 
 impl<'a, K, V> DoubleEndedIterator for LinkedListIterMut<'a, K, V> {
-    fn next_back(&mut self) -> Option<(Option<&'a Rc<K>>, Option<&'a mut V>)> {
+    fn next_back(&mut self) -> Option<(Option<&'a Arc<K>>, Option<&'a mut V>)> {
         if self.len == 0 {
             return None;
         }
 
-        let key = unsafe { &(*self.ptr).key };
-        let val = unsafe { &mut (*self.ptr).val };
+        let key = unsafe { &(*self.end).key };
+        let val = unsafe { &mut (*self.end).val };
 
         self.len -= 1;
         self.end = unsafe { (*self.end).prev };
@@ -182,15 +187,15 @@ where
 {
     start: usize,
     end: usize,
-    key_vec: &'a Vec<Rc<K>>,
-    val_map: &'a HashMap<Rc<K>, Box<LruEntry<K, V>>>,
+    key_vec: &'a Vec<Arc<K>>,
+    val_map: &'a HashMap<Arc<K>, Box<LruEntry<K, V>>>,
 }
 
 impl<'a, K, V> Iterator for SliceIter<'a, K, V>
 where
     K: Eq + Hash + Ord,
 {
-    type Item = (Option<&'a Rc<K>>, Option<&'a V>);
+    type Item = (Option<&'a Arc<K>>, Option<&'a V>);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.start < self.end {
@@ -251,11 +256,30 @@ where
     K: Eq + Hash + Ord,
 {
     // keys in vec to get the ordered entries
-    keys: Vec<Rc<K>>,
+    keys: Vec<Arc<K>>,
     /// head and tail nodes are marker entry which doesn't contain real value to facilitate inserting entries in double linked list
     lru_head: *mut LruEntry<K, V>,
     lru_tail: *mut LruEntry<K, V>,
-    value_map: HashMap<Rc<K>, Box<LruEntry<K, V>>>,
+    value_map: HashMap<Arc<K>, Box<LruEntry<K, V>>>,
+    /// 最も最近のエントリと最も古いエントリのrecency
+    most_recent: i64,
+    least_recent: i64,
+}
+
+// LruOrderedMapは全てのノードを所有し、リストの生ポインタは自身の所有するノードのみを指す。
+// &selfからはノードを読むだけなので、std::collections::LinkedListと同じ条件でスレッド間で共有できる。
+unsafe impl<K, V> Send for LruOrderedMap<K, V>
+where
+    K: Eq + Hash + Ord + Send + Sync,
+    V: Send,
+{
+}
+
+unsafe impl<K, V> Sync for LruOrderedMap<K, V>
+where
+    K: Eq + Hash + Ord + Send + Sync,
+    V: Sync,
+{
 }
 
 impl<K, V> Default for LruOrderedMap<K, V>
@@ -272,7 +296,7 @@ where
     K: Eq + Hash + Ord,
 {
     fn drop(&mut self) {
-        // Clear the keys vector first to drop all Rc references
+        // Clear the keys vector first to drop all Arc references
         self.keys.clear();
 
         // Clear the value_map to drop all entries
@@ -310,6 +334,8 @@ where
             keys: Vec::new(),
             lru_head: Box::into_raw(Box::new(LruEntry::new_marker())),
             lru_tail: Box::into_raw(Box::new(LruEntry::new_marker())),
+            most_recent: 0,
+            least_recent: 0,
         };
         unsafe {
             (*initial_map.lru_head).next = initial_map.lru_tail;
@@ -323,6 +349,19 @@ where
     /// kが存在しない場合は(k,v)を最も最近のエントリとして入れる。
     /// kが存在した場合は既存の(k,old_v)を(k,v)に置き換えて最も最近のエントリにする。
     pub fn push(&mut self, k: K, v: V) {
+        self.push_inner(k, v, true);
+    }
+
+    ///
+    /// kが存在しない場合は(k,v)を最も古いエントリとして入れる。
+    /// kが存在した場合は既存の(k,old_v)を(k,v)に置き換えて最も古いエントリにする。
+    ///
+    /// iter_lru()の順に並んだエントリを先頭から入れると、同じLRU順で入る。
+    pub fn push_least_recent(&mut self, k: K, v: V) {
+        self.push_inner(k, v, false);
+    }
+
+    fn push_inner(&mut self, k: K, v: V, most_recent: bool) {
         let node_ref = self.value_map.get_mut(&k);
         match node_ref {
             Some(node_ref) => {
@@ -331,16 +370,24 @@ where
                     (*node_ptr).val = Some(v);
                 }
                 self.detach(node_ptr);
-                self.attach(node_ptr);
+                if most_recent {
+                    self.attach(node_ptr);
+                } else {
+                    self.attach_least_recent(node_ptr);
+                }
             }
 
             None => {
-                let keyref: Rc<K> = Rc::from(k);
-                let mut node = Box::new(LruEntry::new(Rc::clone(&keyref), v));
+                let keyref: Arc<K> = Arc::from(k);
+                let mut node = Box::new(LruEntry::new(Arc::clone(&keyref), v));
                 let node_ptr: *mut LruEntry<K, V> = &mut *node;
-                self.attach(node_ptr);
+                if most_recent {
+                    self.attach(node_ptr);
+                } else {
+                    self.attach_least_recent(node_ptr);
+                }
                 let idx = self.keys.partition_point(|x| (*x).lt(&keyref));
-                self.keys.insert(idx, Rc::clone(&keyref));
+                self.keys.insert(idx, Arc::clone(&keyref));
                 self.value_map.insert(keyref, node);
             }
         }
@@ -351,7 +398,7 @@ where
     pub fn get_mut<KCmp>(&mut self, k: &KCmp) -> Option<&mut V>
     where
         KCmp: Eq + Hash + ?Sized,
-        Rc<K>: Borrow<KCmp>,
+        Arc<K>: Borrow<KCmp>,
     {
         let node_ref = self.value_map.get_mut(k);
         match node_ref {
@@ -426,6 +473,47 @@ where
         }
     }
 
+    ///
+    /// keyのソート昇順で、rangeに含まれるkeyの部分のIteratorを返す。
+    /// 範囲の両端は二分探索で求めるので、O(log n)で最初の要素を返す。
+    ///
+    pub fn range_sorted<Q, R>(&self, range: R) -> SliceIter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(from) => self.keys.partition_point(|x| (**x).borrow() < from),
+            Bound::Excluded(from) => self.keys.partition_point(|x| (**x).borrow() <= from),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(to) => self.keys.partition_point(|x| (**x).borrow() <= to),
+            Bound::Excluded(to) => self.keys.partition_point(|x| (**x).borrow() < to),
+            Bound::Unbounded => self.keys.len(),
+        };
+        SliceIter {
+            start,
+            end: end.max(start),
+            key_vec: &self.keys,
+            val_map: &self.value_map,
+        }
+    }
+
+    ///
+    /// kのエントリがどれだけ最近使われたかを返す。値が大きいほど最近使われたエントリで、iter_lru()の順と一致する。
+    /// 値は同じmapのエントリ同士の比較にのみ使える。
+    /// kが存在しない場合はNone
+    ///
+    pub fn recency<KCmp>(&self, k: &KCmp) -> Option<i64>
+    where
+        KCmp: Eq + Hash + ?Sized,
+        Arc<K>: Borrow<KCmp>,
+    {
+        self.value_map.get(k).map(|node_ref| node_ref.recency)
+    }
+
     pub fn iter_lru(&self) -> LinkedListIter<'_, K, V> {
         LinkedListIter {
             len: self.keys.len(),
@@ -443,13 +531,26 @@ where
     }
 
     fn attach(&mut self, ptr: *mut LruEntry<K, V>) {
+        self.most_recent += 1;
         unsafe {
+            (*ptr).recency = self.most_recent;
             (*ptr).next = (*self.lru_head).next;
             (*ptr).prev = self.lru_head;
             (*self.lru_head).next = ptr;
             (*(*ptr).next).prev = ptr;
         }
     }
+
+    fn attach_least_recent(&mut self, ptr: *mut LruEntry<K, V>) {
+        self.least_recent -= 1;
+        unsafe {
+            (*ptr).recency = self.least_recent;
+            (*ptr).prev = (*self.lru_tail).prev;
+            (*ptr).next = self.lru_tail;
+            (*self.lru_tail).prev = ptr;
+            (*(*ptr).prev).next = ptr;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -507,6 +608,46 @@ mod test {
         }
     }
 
+    #[test]
+    pub fn push_least_recent_lru_order() {
+        let mut target = LruOrderedMap::new();
+        target.push("a", "a");
+        target.push_least_recent("b", "b");
+        target.push_least_recent("c", "c");
+        target.push_least_recent("a", "a2");
+
+        let lru_keys = target
+            .iter_lru()
+            .map(|(k, _v)| **k.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["b", "c", "a"], lru_keys);
+        assert_eq!(Some(&"a2"), target.peek(&"a"));
+        let lru_keys_rev = target
+            .iter_lru()
+            .rev()
+            .map(|(k, _v)| **k.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["a", "c", "b"], lru_keys_rev);
+    }
+
+    #[test]
+    pub fn recency_follows_lru_order() {
+        let mut target = LruOrderedMap::new();
+        target.push("a", "a");
+        target.push("b", "b");
+        target.push_least_recent("c", "c");
+        target.get(&"a");
+
+        let mut keys = vec!["a", "b", "c"];
+        keys.sort_by_key(|k| std::cmp::Reverse(target.recency(k)));
+        let lru_keys = target
+            .iter_lru()
+            .map(|(k, _v)| **k.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lru_keys, keys);
+        assert_eq!(None, target.recency(&"d"));
+    }
+
     #[test]
     pub fn get_ord_order() {
         let mut target = LruOrderedMap::new();
@@ -278,3 +278,101 @@ fn test_iter_sorted_bidirectional() {
     assert!(iter.next().is_none());
     assert!(iter.next_back().is_none());
 }
+
+#[test]
+fn test_iter_lru_reverse() {
+    let mut map: LruOrderedMap<String, i32> = LruOrderedMap::new();
+    map.push("a".to_string(), 1);
+    map.push("b".to_string(), 2);
+    map.push("c".to_string(), 3);
+
+    // iter_lru is most recently used first, so reversed is the pushed order.
+    let reversed: Vec<_> = map
+        .iter_lru()
+        .rev()
+        .filter_map(|(key, value)| match (key, value) {
+            (Some(k), Some(v)) => Some((k.to_string(), *v)),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(reversed.len(), 3);
+    assert_eq!(reversed[0].0, "a");
+    assert_eq!(reversed[1].0, "b");
+    assert_eq!(reversed[2].0, "c");
+}
+
+#[test]
+fn test_share_between_threads() {
+    let mut map: LruOrderedMap<String, i32> = LruOrderedMap::new();
+    for i in 0..100 {
+        map.push(format!("{i:03}"), i);
+    }
+    let map = std::sync::Arc::new(map);
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let map = map.clone();
+            std::thread::spawn(move || {
+                (0..100)
+                    .filter(|i| map.peek(&format!("{i:03}")) == Some(i))
+                    .count()
+            })
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(100, handle.join().unwrap());
+    }
+
+    // Can move to another thread and be modified there.
+    let mut map = std::sync::Arc::try_unwrap(map).unwrap();
+    let map = std::thread::spawn(move || {
+        if let Some(v) = map.get_mut(&"000".to_string()) {
+            *v = -1;
+        }
+        map
+    })
+    .join()
+    .unwrap();
+    assert_eq!(Some(&-1), map.peek(&"000".to_string()));
+}
+
+#[test]
+fn test_range_sorted() {
+    use std::ops::Bound;
+
+    let mut map: LruOrderedMap<String, i32> = LruOrderedMap::new();
+    for (i, key) in ["b", "ab", "a", "ba", "c", "abc"].iter().enumerate() {
+        map.push(key.to_string(), i as i32);
+    }
+    let keys = |from: Bound<&str>, to: Bound<&str>| -> Vec<String> {
+        map.range_sorted::<str, _>((from, to))
+            .filter_map(|(k, _)| k.map(|k| k.to_string()))
+            .collect()
+    };
+
+    assert_eq!(
+        vec!["ab", "abc", "b", "ba", "c"],
+        keys(Bound::Included("ab"), Bound::Unbounded)
+    );
+    assert_eq!(
+        vec!["abc", "b"],
+        keys(Bound::Excluded("ab"), Bound::Included("b"))
+    );
+    assert_eq!(
+        vec!["a", "ab"],
+        keys(Bound::Unbounded, Bound::Excluded("abc"))
+    );
+    assert_eq!(
+        Vec::<String>::new(),
+        keys(Bound::Included("bb"), Bound::Excluded("b"))
+    );
+    assert_eq!(
+        vec![("ba".to_string(), 3)],
+        map.range_sorted::<str, _>((Bound::Included("b"), Bound::Unbounded))
+            .skip(1)
+            .take(1)
+            .map(|(k, v)| (k.unwrap().to_string(), *v.unwrap()))
+            .collect::<Vec<_>>()
+    );
+}